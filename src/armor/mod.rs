@@ -0,0 +1,179 @@
+use crate::base64;
+use crate::hash::sha256;
+
+const VERSION: &str = "1";
+const LINE_LEN: usize = 64;
+
+/// Kinds of serialized data that can be armored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A serialized vault (see `Vault::serialize`).
+    Vault,
+    /// An encrypted bundle of items (see `Vault::export_items`).
+    Bundle,
+}
+
+impl Kind {
+    fn label(&self) -> &'static str {
+        match self {
+            Kind::Vault => "CYBELE VAULT",
+            Kind::Bundle => "CYBELE BUNDLE",
+        }
+    }
+}
+
+/// Encode binary data to text that can safely be pasted in emails, chats or printed, e.g.:
+/// ```text
+/// -----BEGIN CYBELE VAULT-----
+/// Version: 1
+///
+/// <base64 data, wrapped at 64 chars>
+/// =<base64 SHA-256 checksum of the data>
+/// -----END CYBELE VAULT-----
+/// ```
+/// The format is inspired by OpenPGP armor: https://www.rfc-editor.org/rfc/rfc4880#section-6.2
+pub fn encode(kind: Kind, data: &[u8]) -> String {
+    let body = base64::encode(data);
+    let mut armored = format!("-----BEGIN {}-----\nVersion: {}\n\n", kind.label(), VERSION);
+    for line in body.as_bytes().chunks(LINE_LEN) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str(&format!("={}\n-----END {}-----\n", base64::encode(sha256::hash(data)), kind.label()));
+    armored
+}
+
+/// Decode armored data, returning its kind. Text surrounding the armor and whitespace (indentation, blank lines, CRLF
+/// line endings, etc) are ignored, and body lines may have been wrapped at any length (e.g. by an email client). Errors
+/// mention the (1-based) number of the invalid line.
+pub fn decode(text: &str) -> Result<(Kind, Vec<u8>), String> {
+    // Whitespace may have been inserted anywhere in a line (e.g. when retyping it).
+    let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.chars().filter(|c| !c.is_whitespace()).collect::<String>()));
+    let (begin_index, label) = lines
+        .by_ref()
+        .find_map(|(i, l)| Some((i, String::from(l.strip_prefix("-----BEGIN")?.strip_suffix("-----")?))))
+        .ok_or("missing -----BEGIN CYBELE ...----- line")?;
+    // Whitespace was removed from the label too (e.g. "CYBELEVAULT").
+    let kind = [Kind::Vault, Kind::Bundle]
+        .into_iter()
+        .find(|k| k.label().replace(' ', "") == label)
+        .ok_or_else(|| format!("line {}: unknown armor label: {}", begin_index, label))?;
+    let end = format!("-----END{}-----", label);
+    let mut version = None;
+    let mut body = String::new();
+    let mut last_data_index = begin_index;
+    let mut padded = false;
+    let mut checksum = None;
+    let mut end_found = false;
+    for (index, line) in lines.by_ref() {
+        if line.is_empty() {
+            continue;
+        }
+        if line == end {
+            end_found = true;
+            break;
+        }
+        if checksum.is_some() {
+            return Err(format!("line {}: unexpected line after the checksum", index));
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if !body.is_empty() {
+                return Err(format!("line {}: unexpected header after the data", index));
+            }
+            if key == "Version" {
+                version = Some((index, String::from(value)));
+            }
+            // Other headers are informative.
+            continue;
+        }
+        if let Some(encoded_checksum) = line.strip_prefix('=') {
+            let decoded = base64::decode(encoded_checksum).map_err(|e| format!("line {}: invalid checksum: {}", index, e))?;
+            checksum = Some((index, decoded));
+            continue;
+        }
+        if padded {
+            return Err(format!("line {}: unexpected data after the padding", index));
+        }
+        // Lines are only checked for invalid chars here, since they may not contain a multiple of 4 chars.
+        if let Some(c) = line.trim_end_matches('=').chars().find(|c| !c.is_ascii_alphanumeric() && *c != '+' && *c != '/') {
+            return Err(format!("line {}: corrupted data: non base64 char: {}", index, c));
+        }
+        padded = line.ends_with('=');
+        body.push_str(&line);
+        last_data_index = index;
+    }
+    if !end_found {
+        return Err(format!("missing -----END {}----- line", kind.label()));
+    }
+    match version {
+        Some((_, version)) if version == VERSION => (),
+        Some((index, version)) => return Err(format!("line {}: unsupported armor version: {}", index, version)),
+        None => return Err(format!("line {}: missing Version header", begin_index + 1)),
+    }
+    let data = base64::decode(&body).map_err(|e| format!("line {}: corrupted data: {}", last_data_index, e))?;
+    let Some((checksum_index, checksum)) = checksum else {
+        return Err(String::from("missing checksum line (starting with '=')"));
+    };
+    if checksum != sha256::hash(&data) {
+        return Err(format!("line {}: checksum mismatch: the data or the checksum is corrupted", checksum_index));
+    }
+    Ok((kind, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let data: Vec<u8> = (0..100).collect();
+        let armored = encode(Kind::Bundle, &data);
+        assert_eq!(
+            armored,
+            "-----BEGIN CYBELE BUNDLE-----\n\
+             Version: 1\n\
+             \n\
+             AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4v\n\
+             MDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5f\n\
+             YGFiYw==\n\
+             =vOCv8Zz1qmp0aaMNYdBOQ3bku/Y4EFLunn8zklyVTVI=\n\
+             -----END CYBELE BUNDLE-----\n"
+        );
+        assert_eq!(decode(&armored).unwrap(), (Kind::Bundle, data.clone()));
+        // Surrounding text and whitespace are ignored.
+        let pasted = format!("Here is my vault:\r\n\r\n{}\r\nCheers", armored.replace("AAEC", "  AA EC\t").replace('\n', "\r\n\n"));
+        assert_eq!(decode(&pasted).unwrap(), (Kind::Bundle, data.clone()));
+        assert_eq!(decode(&encode(Kind::Vault, b"")).unwrap(), (Kind::Vault, Vec::new()));
+        // Body lines may be wrapped at any length.
+        let body = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5fYGFiYw==";
+        for line_len in [6, 7, 70, 76, body.len()] {
+            let wrapped: Vec<&str> = body.as_bytes().chunks(line_len).map(|l| std::str::from_utf8(l).unwrap()).collect();
+            let rewrapped = armored.replace(&armored[armored.find("AAEC").unwrap()..armored.find("\n=").unwrap()], &wrapped.join("\n"));
+            assert_eq!(decode(&rewrapped).unwrap(), (Kind::Bundle, data.clone()));
+        }
+    }
+
+    #[test]
+    fn decode_failure() {
+        let armored = encode(Kind::Vault, &(0..100).collect::<Vec<u8>>());
+        let test_cases = vec![
+            (String::new(), "missing -----BEGIN CYBELE ...----- line"),
+            (String::from("\n-----BEGIN CYBELE KEY-----"), "line 2: unknown armor label: CYBELEKEY"),
+            (armored.replace("-----END CYBELE VAULT-----\n", ""), "missing -----END CYBELE VAULT----- line"),
+            (armored.replace("Version: 1\n", ""), "line 2: missing Version header"),
+            (armored.replace("Version: 1", "Version: 2"), "line 2: unsupported armor version: 2"),
+            (armored.replace("AAEC", "AA*C"), "line 4: corrupted data: non base64 char: *"),
+            (armored.replace("MDEy", "MDE"), "line 6: corrupted data: invalid base64 length"),
+            (armored.replace("YGFi", "YGFj"), "line 7: checksum mismatch: the data or the checksum is corrupted"),
+            (armored.replace("Yw==\n", "Yw==\nAAAA\n"), "line 7: unexpected data after the padding"),
+            (armored.replace("Yw==\n", "Yw==\nComment: late\n"), "line 7: unexpected header after the data"),
+            (armored.replace("=\n-----END", "=\nAAAA\n-----END"), "line 8: unexpected line after the checksum"),
+            (armored.replace("=vOCv", "=*OCv"), "line 7: invalid checksum: non base64 char: *"),
+        ];
+        for (text, error) in test_cases {
+            assert_eq!(decode(&text), Err(String::from(error)));
+        }
+        let no_checksum: String = armored.lines().filter(|l| !l.starts_with('=')).map(|l| format!("{}\n", l)).collect();
+        assert_eq!(decode(&no_checksum), Err(String::from("missing checksum line (starting with '=')")));
+    }
+}
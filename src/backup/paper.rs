@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+
+use crate::base32;
+use crate::gf256;
+use crate::hash::sha256;
+
+const FORMAT_VERSION: u8 = 1;
+// [u8: version][4*u8: backup_id][u8: index][u8: data_chunks][u8: total_chunks][u32: data_len]
+const HEADER_LEN: usize = 12;
+const CHECKSUM_LEN: usize = 4;
+// Chunks are printed as lines of 8 groups of 4 chars.
+const GROUP_LEN: usize = 4;
+const GROUPS_PER_LINE: usize = 8;
+// Maximum number of combinations of chunks tried when restoring data that doesn't match the backup.
+const MAX_COMBINATIONS: usize = 10_000;
+
+/// A numbered piece of a paper backup. The first `data_chunks` chunks contain the data, and the others contain
+/// Reed-Solomon parity: any `data_chunks` valid chunks are enough to restore the data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    /// Truncated SHA-256 of the data, so that chunks of different backups are never mixed.
+    pub backup_id: [u8; 4],
+    /// 0-based index (chunks are numbered from 1 when printed).
+    pub index: u8,
+    pub data_chunks: u8,
+    pub total_chunks: u8,
+    pub data_len: u32,
+    pub payload: Vec<u8>,
+}
+
+impl Chunk {
+    /// Encode the chunk to base32 (which doesn't use easily confused chars such as 0, 1 and 8), with its checksum.
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len() + CHECKSUM_LEN);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend(self.backup_id);
+        bytes.extend([self.index, self.data_chunks, self.total_chunks]);
+        bytes.extend(self.data_len.to_be_bytes());
+        bytes.extend(&self.payload);
+        let checksum = sha256::hash(&bytes);
+        bytes.extend(&checksum[..CHECKSUM_LEN]);
+        base32::encode_unpadded(bytes)
+    }
+
+    /// Decode a chunk, which may have been retyped or OCR-ed: whitespace and case are ignored, and digits that look
+    /// like letters are replaced by these letters.
+    pub fn decode(encoded: &str) -> Result<Chunk, String> {
+        let normalized: String = encoded
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c.to_ascii_uppercase() {
+                '0' => 'O',
+                '1' => 'I',
+                '8' => 'B',
+                c => c,
+            })
+            .collect();
+        let bytes = base32::decode(&normalized)?;
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(String::from("chunk is too short"));
+        }
+        let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if sha256::hash(content)[..CHECKSUM_LEN] != *checksum {
+            return Err(String::from("invalid checksum"));
+        }
+        if content[0] != FORMAT_VERSION {
+            return Err(format!("unsupported version: {}", content[0]));
+        }
+        let chunk = Chunk {
+            backup_id: content[1..5].try_into().unwrap(),
+            index: content[5],
+            data_chunks: content[6],
+            total_chunks: content[7],
+            data_len: u32::from_be_bytes(content[8..12].try_into().unwrap()),
+            payload: content[HEADER_LEN..].to_vec(),
+        };
+        if chunk.data_chunks == 0 || chunk.data_chunks > chunk.total_chunks || chunk.index >= chunk.total_chunks {
+            return Err(String::from("invalid chunk numbering"));
+        }
+        if chunk.payload.len() != payload_len(chunk.data_len as usize, chunk.data_chunks) {
+            return Err(String::from("invalid chunk length"));
+        }
+        Ok(chunk)
+    }
+}
+
+fn payload_len(data_len: usize, data_chunks: u8) -> usize {
+    data_len.div_ceil(data_chunks as usize).max(1)
+}
+
+/// Split data (e.g. a serialized vault or an emergency key) into `data_chunks` chunks, followed by `parity_chunks`
+/// chunks that can replace missing or corrupted ones. There can be at most 255 chunks.
+/// Parity chunks use a systematic Reed-Solomon code: each byte of a parity chunk is the value, at the chunk's index, of
+/// the polynomial going through the data bytes of the same column.
+pub fn split(data: &[u8], data_chunks: u8, parity_chunks: u8) -> Result<Vec<Chunk>, String> {
+    if data_chunks == 0 {
+        return Err(String::from("at least one data chunk is required"));
+    }
+    let total_chunks = data_chunks.checked_add(parity_chunks).ok_or("there can be at most 255 chunks")?;
+    let data_len = u32::try_from(data.len()).map_err(|_| "data is too large")?;
+    let backup_id: [u8; 4] = sha256::hash(data)[..4].try_into().unwrap();
+    let payload_len = payload_len(data.len(), data_chunks);
+    // Each column of bytes contains the values of a polynomial at x = 0, 1, ..., total_chunks - 1, where the values
+    // at x < data_chunks are the data bytes.
+    let mut payloads: Vec<Vec<u8>> = (0..data_chunks as usize)
+        .map(|i| {
+            let mut payload = data.iter().skip(i * payload_len).take(payload_len).copied().collect::<Vec<u8>>();
+            payload.resize(payload_len, 0);
+            payload
+        })
+        .collect();
+    let xs: Vec<u8> = (0..data_chunks).collect();
+    for x in data_chunks..total_chunks {
+        let coefficients = gf256::lagrange_coefficients(&xs, x);
+        let parity = (0..payload_len)
+            .map(|column| gf256::interpolate(&coefficients, &payloads.iter().map(|p| p[column]).collect::<Vec<u8>>()))
+            .collect();
+        payloads.push(parity);
+    }
+    Ok(payloads
+        .into_iter()
+        .enumerate()
+        .map(|(index, payload)| Chunk {
+            backup_id,
+            index: index as u8,
+            data_chunks,
+            total_chunks,
+            data_len,
+            payload,
+        })
+        .collect())
+}
+
+/// Restore the data from any `data_chunks` distinct chunks of a backup. Chunks of other backups are ignored, and other
+/// combinations of chunks are tried when the restored data doesn't match the backup (e.g. when a chunk was retyped
+/// with mistakes that its checksum didn't catch).
+pub fn restore(chunks: &[Chunk]) -> Result<Vec<u8>, String> {
+    if chunks.is_empty() {
+        return Err(String::from("no chunks"));
+    }
+    let backup = |c: &Chunk| (c.backup_id, c.data_chunks, c.total_chunks, c.data_len);
+    // The backup of the first chunk is restored, or else the first other backup that can be.
+    let mut backups: Vec<Vec<&Chunk>> = Vec::new();
+    for chunk in chunks {
+        match backups.iter_mut().find(|b| backup(b[0]) == backup(chunk)) {
+            Some(b) if b.contains(&chunk) => {}
+            Some(b) => b.push(chunk),
+            None => backups.push(vec![chunk]),
+        }
+    }
+    let mut error = None;
+    for backup in backups {
+        match restore_backup(&backup) {
+            Ok(data) => return Ok(data),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(error.unwrap_or_default())
+}
+
+// Restore the data from distinct chunks of the same backup, trying combinations of chunks with distinct indexes until
+// one of them matches the backup.
+fn restore_backup(chunks: &[&Chunk]) -> Result<Vec<u8>, String> {
+    let first = chunks[0];
+    let payload_len = payload_len(first.data_len as usize, first.data_chunks);
+    let mut candidates: Vec<&Chunk> = chunks.iter().filter(|c| c.payload.len() == payload_len).copied().collect();
+    // Data chunks come first, since they don't need to be interpolated.
+    candidates.sort_by_key(|c| c.index);
+    let mut indexes: Vec<u8> = candidates.iter().map(|c| c.index).collect();
+    indexes.dedup();
+    if indexes.len() < first.data_chunks as usize {
+        return Err(format!("not enough chunks: {} of the {} required", indexes.len(), first.data_chunks));
+    }
+    let mut attempts = 0;
+    restore_combinations(&candidates, first.data_chunks as usize, &mut Vec::new(), &mut attempts).ok_or_else(|| String::from("restored data doesn't match the backup"))
+}
+
+// Try the combinations of `data_chunks` chunks with distinct indexes that extend `selected`, in order, until one of them
+// restores data that matches the backup. At most MAX_COMBINATIONS combinations are tried.
+fn restore_combinations<'a>(chunks: &[&'a Chunk], data_chunks: usize, selected: &mut Vec<&'a Chunk>, attempts: &mut usize) -> Option<Vec<u8>> {
+    if selected.len() == data_chunks {
+        *attempts += 1;
+        return restore_data(selected);
+    }
+    for (i, &chunk) in chunks.iter().enumerate() {
+        if *attempts >= MAX_COMBINATIONS || chunks.len() - i < data_chunks - selected.len() {
+            return None;
+        }
+        if selected.iter().any(|c| c.index == chunk.index) {
+            continue;
+        }
+        selected.push(chunk);
+        let data = restore_combinations(&chunks[i + 1..], data_chunks, selected, attempts);
+        selected.pop();
+        if data.is_some() {
+            return data;
+        }
+    }
+    None
+}
+
+// Restore the data from `data_chunks` chunks with distinct indexes, or None if it doesn't match the backup.
+fn restore_data(chunks: &[&Chunk]) -> Option<Vec<u8>> {
+    let first = chunks[0];
+    let xs: Vec<u8> = chunks.iter().map(|c| c.index).collect();
+    let payload_len = payload_len(first.data_len as usize, first.data_chunks);
+    let mut data = Vec::with_capacity(payload_len * first.data_chunks as usize);
+    for x in 0..first.data_chunks {
+        match chunks.iter().find(|c| c.index == x) {
+            Some(chunk) => data.extend(&chunk.payload),
+            None => {
+                let coefficients = gf256::lagrange_coefficients(&xs, x);
+                data.extend((0..payload_len).map(|column| gf256::interpolate(&coefficients, &chunks.iter().map(|c| c.payload[column]).collect::<Vec<u8>>())));
+            }
+        }
+    }
+    data.truncate(first.data_len as usize);
+    (sha256::hash(&data)[..4] == first.backup_id).then_some(data)
+}
+
+/// Lay out the chunks as plain text to be printed, e.g.:
+/// ```text
+/// CYBELE PAPER BACKUP: <title>
+/// Backup 1a2b3c4d: any 3 of the 5 chunks are enough to restore it.
+///
+/// 01: AEVB UNG7 AAAD AUAA AAAE GV5E RUEI
+/// 01: 5LKU ...
+/// ```
+pub fn to_text(title: &str, chunks: &[Chunk]) -> String {
+    let mut text = format!("CYBELE PAPER BACKUP: {}\n", title);
+    if let Some(first) = chunks.first() {
+        text.push_str(&format!(
+            "Backup {}: any {} of the {} chunks are enough to restore it.\n",
+            crate::hex::encode(first.backup_id),
+            first.data_chunks,
+            first.total_chunks
+        ));
+    }
+    for chunk in chunks {
+        text.push('\n');
+        let encoded = chunk.encode();
+        for line in encoded.as_bytes().chunks(GROUP_LEN * GROUPS_PER_LINE) {
+            let groups: Vec<&str> = line.chunks(GROUP_LEN).map(|g| std::str::from_utf8(g).unwrap()).collect();
+            text.push_str(&format!("{:02}: {}\n", chunk.index as u16 + 1, groups.join(" ")));
+        }
+    }
+    text
+}
+
+/// Lay out the chunks as an SVG image to be printed, with the same content as `to_text`.
+pub fn to_svg(title: &str, chunks: &[Chunk]) -> String {
+    let text = to_text(title, chunks);
+    let lines: Vec<&str> = text.lines().collect();
+    let width = 40 + 10 * lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let height = 40 + 20 * lines.len();
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n\
+         <g font-family=\"monospace\" font-size=\"16\" fill=\"black\">\n",
+        width, height, width, height
+    );
+    for (i, line) in lines.iter().enumerate().filter(|(_, l)| !l.is_empty()) {
+        let escaped = line.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        svg.push_str(&format!("<text x=\"20\" y=\"{}\" xml:space=\"preserve\">{}</text>\n", 35 + 20 * i, escaped));
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// Restore the data from text laid out by `to_text` (e.g. OCR-ed or retyped). Lines that don't start with a chunk
+/// number are ignored, and invalid chunks are skipped as long as enough valid chunks remain.
+pub fn restore_text(text: &str) -> Result<Vec<u8>, String> {
+    let mut encoded_chunks: BTreeMap<u32, String> = BTreeMap::new();
+    for line in text.lines() {
+        let Some((number, encoded)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let Ok(number) = number.trim().parse::<u32>() else {
+            continue;
+        };
+        encoded_chunks.entry(number).or_default().push_str(encoded);
+    }
+    let mut chunks = Vec::with_capacity(encoded_chunks.len());
+    let mut errors = Vec::new();
+    for (number, encoded) in encoded_chunks {
+        match Chunk::decode(&encoded) {
+            Ok(chunk) if chunk.index as u32 + 1 == number => chunks.push(chunk),
+            Ok(_) => errors.push(format!("chunk {}: unexpected chunk number", number)),
+            Err(e) => errors.push(format!("chunk {}: {}", number, e)),
+        }
+    }
+    restore(&chunks).map_err(|e| match errors.is_empty() {
+        true => e,
+        false => format!("{} ({})", e, errors.join(", ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::vault::Vault;
+    use crate::Version;
+
+    #[test]
+    fn split_restore() {
+        let data: Vec<u8> = (0..100).map(|i| (i * 7) as u8).collect();
+        let chunks = split(&data, 4, 3).unwrap();
+        assert_eq!(chunks.len(), 7);
+        assert!(chunks.iter().all(|c| c.payload.len() == 25));
+        assert_eq!(chunks[0].payload, data[..25]);
+        assert_eq!(restore(&chunks).unwrap(), data);
+        // Any 4 chunks are enough.
+        for missing in [[0, 1, 2], [3, 4, 5], [0, 3, 6], [4, 5, 6]] {
+            let remaining: Vec<Chunk> = chunks.iter().filter(|c| !missing.contains(&c.index)).cloned().collect();
+            assert_eq!(restore(&remaining).unwrap(), data, "{:?}", missing);
+        }
+        let duplicated = vec![chunks[0].clone(), chunks[0].clone(), chunks[1].clone(), chunks[5].clone()];
+        assert_eq!(restore(&duplicated), Err(String::from("not enough chunks: 3 of the 4 required")));
+        let other = split(b"other data", 4, 3).unwrap();
+        // Chunks of other backups are skipped.
+        assert_eq!(restore(&[chunks[0].clone(), other[1].clone()]), Err(String::from("not enough chunks: 1 of the 4 required")));
+        let mixed = [&other[..1], &chunks[2..], &other[5..6]].concat();
+        assert_eq!(restore(&mixed).unwrap(), data);
+        let mixed = [&other[..1], &chunks[..4], &other[5..]].concat();
+        assert_eq!(restore(&mixed).unwrap(), data);
+        let mut corrupted = chunks[4..].to_vec();
+        corrupted[0].payload[0] ^= 1;
+        corrupted.push(chunks[0].clone());
+        assert_eq!(restore(&corrupted), Err(String::from("restored data doesn't match the backup")));
+        // Other combinations are tried when a chunk is corrupted.
+        corrupted.push(chunks[1].clone());
+        assert_eq!(restore(&corrupted).unwrap(), data);
+        let mut corrupted = chunks.clone();
+        corrupted[0].payload[3] ^= 1;
+        corrupted[5].payload[7] ^= 1;
+        assert_eq!(restore(&corrupted).unwrap(), data);
+        // Both versions of a chunk that was retyped twice are tried.
+        let retyped = [&corrupted[..1], &chunks[..1], &chunks[1..4]].concat();
+        assert_eq!(restore(&retyped).unwrap(), data);
+        assert_eq!(restore(&[]), Err(String::from("no chunks")));
+        // Small secrets (e.g. an emergency key) can be split too.
+        assert_eq!(restore(&split(&[42u8; 3], 5, 2).unwrap()[2..]).unwrap(), vec![42u8; 3]);
+        assert_eq!(restore(&split(b"", 1, 1).unwrap()[1..]).unwrap(), b"");
+        assert_eq!(split(&data, 0, 3), Err(String::from("at least one data chunk is required")));
+        assert_eq!(split(&data, 200, 56), Err(String::from("there can be at most 255 chunks")));
+    }
+
+    #[test]
+    fn encode_decode_chunk() {
+        let chunk = split(b"secret", 2, 1).unwrap().remove(2);
+        let encoded = chunk.encode();
+        assert_eq!(Chunk::decode(&encoded).unwrap(), chunk);
+        // OCR mistakes and whitespace are tolerated.
+        let retyped = encoded.to_lowercase().replace('o', "0").replace('i', "1").replace('b', "8");
+        assert_eq!(Chunk::decode(&format!(" {} \n", retyped)).unwrap(), chunk);
+        let mut corrupted = encoded.clone().into_bytes();
+        corrupted[10] = if corrupted[10] == b'A' { b'B' } else { b'A' };
+        assert_eq!(Chunk::decode(std::str::from_utf8(&corrupted).unwrap()), Err(String::from("invalid checksum")));
+        assert_eq!(Chunk::decode("AAAA"), Err(String::from("chunk is too short")));
+        assert!(Chunk::decode("not base32!").is_err());
+    }
+
+    #[test]
+    fn paper_vault_backup() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        let serialized = vault.serialize("f1l3 p4ss").unwrap();
+        let chunks = split(&serialized, 3, 2).unwrap();
+        let text = to_text("Personal vault", &chunks);
+        assert!(text.starts_with(&format!(
+            "CYBELE PAPER BACKUP: Personal vault\nBackup {}: any 3 of the 5 chunks are enough to restore it.\n\n01: ",
+            crate::hex::encode(&sha256::hash(&serialized)[..4])
+        )));
+        assert_eq!(restore_text(&text).unwrap(), serialized);
+        // One chunk is missing and another one was badly retyped.
+        let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with("02:")).collect();
+        let retyped = lines.join("\n").replacen("04: A", "04: Q", 1);
+        let restored = restore_text(&retyped).unwrap();
+        assert_eq!(Vault::deserialize(&restored, "f1l3 p4ss").unwrap().get("GitHub", "m4st3r").unwrap(), "kT9#vLq2$wXz7!mP");
+        let retyped = retyped.replacen("05: A", "05: Q", 1);
+        assert_eq!(
+            restore_text(&retyped),
+            Err(String::from(
+                "not enough chunks: 2 of the 3 required (chunk 4: invalid checksum, chunk 5: invalid checksum)"
+            ))
+        );
+        let svg = to_svg("<Personal> vault", &chunks);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains(">CYBELE PAPER BACKUP: &lt;Personal&gt; vault</text>"));
+        assert_eq!(svg.matches("<text ").count(), text.lines().filter(|l| !l.is_empty()).count());
+    }
+}
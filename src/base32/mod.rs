@@ -0,0 +1,162 @@
+/// Encode to a padded base32 string.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc4648#section-6
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    let mut encoded = encode_unpadded(&data);
+    while !encoded.len().is_multiple_of(8) {
+        encoded.push('=');
+    }
+    encoded
+}
+
+/// Encode to a base32 string without padding (e.g. for TOTP secrets).
+pub fn encode_unpadded<T: AsRef<[u8]>>(data: T) -> String {
+    let data = data.as_ref();
+    let mut encoded = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits_count = 0;
+    for &x in data {
+        buffer = (buffer << 8) | x as u32;
+        bits_count += 8;
+        while bits_count >= 5 {
+            bits_count -= 5;
+            encoded.push(byte_to_char(((buffer >> bits_count) & 0x1f) as u8));
+        }
+        buffer &= (1 << bits_count) - 1;
+    }
+    if bits_count > 0 {
+        encoded.push(byte_to_char(((buffer << (5 - bits_count)) & 0x1f) as u8));
+    }
+    encoded
+}
+
+/// Decode a base32 string: padding is optional and lowercase characters are accepted.
+/// Non-canonical encodings (invalid padding or non-zero trailing bits) are rejected.
+pub fn decode(data: &str) -> Result<Vec<u8>, String> {
+    let unpadded = data.trim_end_matches('=');
+    let padding_len = data.len() - unpadded.len();
+    let expected_padding_len = match unpadded.len() % 8 {
+        0 => 0,
+        2 => 6,
+        4 => 4,
+        5 => 3,
+        7 => 1,
+        _ => return Err(String::from("invalid base32 length")),
+    };
+    if padding_len != 0 && padding_len != expected_padding_len {
+        return Err(String::from("invalid base32 padding"));
+    }
+    let mut bytes = Vec::<u8>::with_capacity(unpadded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits_count = 0;
+    // We only check validity at the end, to avoid branching on the data.
+    let mut invalid = 0u8;
+    for &c in unpadded.as_bytes() {
+        let (value, valid) = char_to_byte(c);
+        invalid |= !valid;
+        buffer = (buffer << 5) | value as u32;
+        bits_count += 5;
+        if bits_count >= 8 {
+            bits_count -= 8;
+            bytes.push((buffer >> bits_count) as u8);
+            buffer &= (1 << bits_count) - 1;
+        }
+    }
+    if invalid != 0 {
+        let c = unpadded.chars().find(|&c| !c.is_ascii() || char_to_byte(c as u8).1 == 0).unwrap_or('=');
+        return Err(format!("non base32 char: {}", c));
+    }
+    if buffer != 0 {
+        return Err(String::from("non-canonical base32 encoding"));
+    }
+    Ok(bytes)
+}
+
+// Encoded data may be secret (e.g. TOTP seeds), so we don't branch on it or use it to index tables.
+// Returns 0xff if a < b, 0x00 otherwise.
+fn lt(a: u8, b: u8) -> u8 {
+    ((a as u16).wrapping_sub(b as u16) >> 8) as u8
+}
+
+// Returns 0xff if lo <= a <= hi, 0x00 otherwise.
+fn between(a: u8, lo: u8, hi: u8) -> u8 {
+    !lt(a, lo) & !lt(hi, a)
+}
+
+fn byte_to_char(b: u8) -> char {
+    // 0..=25 map to 'A'..='Z' and 26..=31 map to '2'..='7'.
+    let letter = lt(b, 26);
+    ((letter & b.wrapping_add(b'A')) | (!letter & b.wrapping_add(b'2' - 26))) as char
+}
+
+// Returns the decoded value and 0xff if the char is valid (0x00 otherwise).
+fn char_to_byte(c: u8) -> (u8, u8) {
+    let upper = between(c, b'A', b'Z');
+    let lower = between(c, b'a', b'z');
+    let digit = between(c, b'2', b'7');
+    let value = (upper & c.wrapping_sub(b'A')) | (lower & c.wrapping_sub(b'a')) | (digit & c.wrapping_sub(b'2').wrapping_add(26));
+    (value, upper | lower | digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::OsRng;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn official_test_vectors() {
+        // Test vectors from RFC 4648.
+        let test_cases = vec![
+            ("", ""),
+            ("f", "MY======"),
+            ("fo", "MZXQ===="),
+            ("foo", "MZXW6==="),
+            ("foob", "MZXW6YQ="),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI======"),
+        ];
+        for (decoded, encoded) in test_cases {
+            assert_eq!(encode(decoded), encoded);
+            assert_eq!(encode_unpadded(decoded), encoded.trim_end_matches('='));
+            assert_eq!(decode(encoded).unwrap(), decoded.as_bytes());
+            assert_eq!(decode(encoded.trim_end_matches('=')).unwrap(), decoded.as_bytes());
+            assert_eq!(decode(&encoded.to_lowercase()).unwrap(), decoded.as_bytes());
+        }
+    }
+
+    #[test]
+    fn encode_decode_random_data() {
+        let mut csprng = OsRng {};
+        for _ in 0..100 {
+            let mut data = vec![0u8; csprng.gen_range(0..100)];
+            csprng.fill_bytes(&mut data);
+            assert_eq!(decode(&encode(&data)).unwrap(), data);
+            assert_eq!(decode(&encode_unpadded(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn decode_failure() {
+        let test_cases = vec![
+            // invalid length
+            "MZXW6YTBO",
+            // invalid padding
+            "MZXQ==",
+            "MZXQ=====",
+            "MY=Q====",
+            // invalid chars
+            "MZXW6YT1",
+            "MZXW6YT8",
+            "MZXW 6YTB",
+            "MZXW6YTé",
+            // non-zero trailing bits
+            "MZ======",
+            "MZXW6YTBOJ",
+        ];
+        for t in test_cases {
+            assert!(decode(t).is_err(), "{}", t);
+        }
+        assert_eq!(decode("MZXW6YT1"), Err(String::from("non base32 char: 1")));
+    }
+}
@@ -0,0 +1,164 @@
+/// Encode to a padded base64 string.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc4648#section-4
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    encode_with(data.as_ref(), b'+', b'/', true)
+}
+
+/// Decode a padded base64 string.
+/// Non-canonical encodings (missing padding, non-zero trailing bits or whitespace) are rejected.
+pub fn decode(data: &str) -> Result<Vec<u8>, String> {
+    decode_with(data, b'+', b'/', true)
+}
+
+// The standard and url-safe alphabets only differ in the characters used for 62 and 63.
+pub(crate) fn encode_with(data: &[u8], c62: u8, c63: u8, padding: bool) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let mut buffer = [0u8; 3];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = ((buffer[0] as u32) << 16) | ((buffer[1] as u32) << 8) | buffer[2] as u32;
+        let chars_count = chunk.len() + 1;
+        (0..chars_count).for_each(|i| encoded.push(byte_to_char(((bits >> (18 - 6 * i)) & 0x3f) as u8, c62, c63)));
+        if padding {
+            (chars_count..4).for_each(|_| encoded.push('='));
+        }
+    }
+    encoded
+}
+
+pub(crate) fn decode_with(data: &str, c62: u8, c63: u8, padding_required: bool) -> Result<Vec<u8>, String> {
+    let unpadded = data.strip_suffix("==").or_else(|| data.strip_suffix('=')).unwrap_or(data);
+    let padding_len = data.len() - unpadded.len();
+    let expected_padding_len = match unpadded.len() % 4 {
+        0 => 0,
+        2 => 2,
+        3 => 1,
+        _ => return Err(String::from("invalid base64 length")),
+    };
+    if padding_len != expected_padding_len && (padding_required || padding_len != 0) {
+        return Err(String::from("invalid base64 padding"));
+    }
+    let mut bytes = Vec::<u8>::with_capacity(unpadded.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits_count = 0;
+    // We only check validity at the end, to avoid branching on the data.
+    let mut invalid = 0u8;
+    for &c in unpadded.as_bytes() {
+        let (value, valid) = char_to_byte(c, c62, c63);
+        invalid |= !valid;
+        buffer = (buffer << 6) | value as u32;
+        bits_count += 6;
+        if bits_count >= 8 {
+            bits_count -= 8;
+            bytes.push((buffer >> bits_count) as u8);
+            buffer &= (1 << bits_count) - 1;
+        }
+    }
+    if invalid != 0 {
+        let c = unpadded.chars().find(|&c| !c.is_ascii() || char_to_byte(c as u8, c62, c63).1 == 0).unwrap_or('=');
+        return Err(format!("non base64 char: {}", c));
+    }
+    if buffer != 0 {
+        return Err(String::from("non-canonical base64 encoding"));
+    }
+    Ok(bytes)
+}
+
+// Encoded data may be secret (e.g. keys), so we don't branch on it or use it to index tables.
+// Returns 0xff if a < b, 0x00 otherwise.
+fn lt(a: u8, b: u8) -> u8 {
+    ((a as u16).wrapping_sub(b as u16) >> 8) as u8
+}
+
+// Returns 0xff if a == b, 0x00 otherwise.
+fn eq(a: u8, b: u8) -> u8 {
+    !lt(a, b) & !lt(b, a)
+}
+
+// Returns 0xff if lo <= a <= hi, 0x00 otherwise.
+fn between(a: u8, lo: u8, hi: u8) -> u8 {
+    !lt(a, lo) & !lt(hi, a)
+}
+
+fn byte_to_char(b: u8, c62: u8, c63: u8) -> char {
+    // 0..=25 map to 'A'..='Z', 26..=51 map to 'a'..='z' and 52..=61 map to '0'..='9'.
+    let upper = lt(b, 26);
+    let lower = between(b, 26, 51);
+    let digit = between(b, 52, 61);
+    ((upper & b.wrapping_add(b'A')) | (lower & b.wrapping_add(b'a' - 26)) | (digit & b.wrapping_sub(52 - b'0')) | (eq(b, 62) & c62) | (eq(b, 63) & c63)) as char
+}
+
+// Returns the decoded value and 0xff if the char is valid (0x00 otherwise).
+fn char_to_byte(c: u8, c62: u8, c63: u8) -> (u8, u8) {
+    let upper = between(c, b'A', b'Z');
+    let lower = between(c, b'a', b'z');
+    let digit = between(c, b'0', b'9');
+    let is_c62 = eq(c, c62);
+    let is_c63 = eq(c, c63);
+    let value = (upper & c.wrapping_sub(b'A')) | (lower & c.wrapping_sub(b'a').wrapping_add(26)) | (digit & c.wrapping_sub(b'0').wrapping_add(52)) | (is_c62 & 62) | (is_c63 & 63);
+    (value, upper | lower | digit | is_c62 | is_c63)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::OsRng;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn official_test_vectors() {
+        // Test vectors from RFC 4648.
+        let test_cases = vec![
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (decoded, encoded) in test_cases {
+            assert_eq!(encode(decoded), encoded);
+            assert_eq!(decode(encoded).unwrap(), decoded.as_bytes());
+        }
+        assert_eq!(encode([0xfb, 0xff]), "+/8=");
+        assert_eq!(decode("+/8=").unwrap(), [0xfb, 0xff]);
+    }
+
+    #[test]
+    fn encode_decode_random_data() {
+        let mut csprng = OsRng {};
+        for _ in 0..100 {
+            let mut data = vec![0u8; csprng.gen_range(0..100)];
+            csprng.fill_bytes(&mut data);
+            assert_eq!(decode(&encode(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn decode_failure() {
+        let test_cases = vec![
+            // invalid length
+            "Zm9vY",
+            // missing or invalid padding
+            "Zg",
+            "Zg=",
+            "Zm8==",
+            "Zg===",
+            "Z===",
+            // invalid chars
+            "Zm9v-_8=",
+            "Zm9 v",
+            "Zm9vYg==\n",
+            "Zm9é",
+            // non-zero trailing bits
+            "Zh==",
+            "Zm9=",
+        ];
+        for t in test_cases {
+            assert!(decode(t).is_err(), "{}", t);
+        }
+        assert_eq!(decode("Zm9v-_8="), Err(String::from("non base64 char: -")));
+    }
+}
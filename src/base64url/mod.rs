@@ -0,0 +1,59 @@
+use crate::base64;
+
+/// Encode to a url-safe base64 string, without padding.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc4648#section-5
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    base64::encode_with(data.as_ref(), b'-', b'_', false)
+}
+
+/// Decode a url-safe base64 string: padding is optional, but must be valid when provided.
+/// Non-canonical encodings (non-zero trailing bits or whitespace) are rejected.
+pub fn decode(data: &str) -> Result<Vec<u8>, String> {
+    base64::decode_with(data, b'-', b'_', false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::OsRng;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn encode_decode_url_safe() {
+        let test_cases = vec![(vec![0xfb, 0xff], "-_8"), (b"\xfb\xfffoob".to_vec(), "-_9mb29i"), (b"\xfb\xfffooba".to_vec(), "-_9mb29iYQ")];
+        for (decoded, encoded) in test_cases {
+            assert_eq!(encode(&decoded), encoded);
+            assert_eq!(decode(encoded).unwrap(), decoded);
+        }
+        assert_eq!(decode("-_9mb29iYQ==").unwrap(), b"\xfb\xfffooba");
+    }
+
+    #[test]
+    fn encode_decode_random_data() {
+        let mut csprng = OsRng {};
+        for _ in 0..100 {
+            let mut data = vec![0u8; csprng.gen_range(0..100)];
+            csprng.fill_bytes(&mut data);
+            assert_eq!(decode(&encode(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn decode_failure() {
+        let test_cases = vec![
+            // invalid length
+            "-_9mb",
+            // invalid padding
+            "-_9mb29iYQ=",
+            "-_8==",
+            // standard alphabet
+            "+/8",
+            // non-zero trailing bits
+            "-_9",
+        ];
+        for t in test_cases {
+            assert!(decode(t).is_err(), "{}", t);
+        }
+    }
+}
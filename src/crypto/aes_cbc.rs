@@ -0,0 +1,46 @@
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::Aes256;
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// AES-256 in CBC mode with PKCS#7 padding, which isn't authenticated: it is only provided to read and write data
+/// produced by other applications, which authenticate it separately.
+pub fn encrypt(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+    Aes256CbcEnc::new(key.into(), iv.into()).encrypt_padded_vec_mut::<Pkcs7>(plaintext)
+}
+
+pub fn decrypt(key: &[u8; 32], iv: &[u8; 16], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    Aes256CbcDec::new(key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| eprintln!("Failed to decrypt"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    #[test]
+    fn encrypt_decrypt() {
+        // Test vector from NIST SP 800-38A (F.2.5), with an additional padding block.
+        let key: [u8; 32] = hex::decode_array("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4").unwrap();
+        let iv: [u8; 16] = hex::decode_array("000102030405060708090a0b0c0d0e0f").unwrap();
+        let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51").unwrap();
+        let ciphertext = encrypt(&key, &iv, &plaintext);
+        assert_eq!(ciphertext.len(), 48);
+        assert_eq!(hex::encode(&ciphertext[..32]), "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d");
+        assert_eq!(decrypt(&key, &iv, &ciphertext).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn decryption_failure() {
+        // Invalid length.
+        assert_eq!(decrypt(&[0u8; 32], &[0u8; 16], &[0u8; 15]), None);
+        // Invalid padding.
+        let ciphertext = encrypt(&[0u8; 32], &[0u8; 16], b"some data");
+        assert_eq!(decrypt(&[1u8; 32], &[0u8; 16], &ciphertext), None);
+    }
+}
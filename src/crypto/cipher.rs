@@ -1,16 +1,24 @@
 use chacha20poly1305::aead::Aead;
 use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
 
+// A constant nonce is only safe for keys that encrypt a single plaintext (e.g. derived from a fresh random salt).
+// Keys that encrypt several plaintexts must use `encrypt_with_nonce` with random nonces.
 pub fn encrypt(key: [u8; 32], plaintext: &[u8]) -> Option<Vec<u8>> {
-    let cipher = ChaCha20Poly1305::new_from_slice(key.as_slice()).expect("32 bytes should be a valid ChaCha20 key size");
-    let nonce = Nonce::from([0u8; 12]);
-    cipher.encrypt(&nonce, plaintext).map_err(|_| eprintln!("Failed to encrypt")).ok()
+    encrypt_with_nonce(key, [0u8; 12], plaintext)
 }
 
 pub fn decrypt(key: [u8; 32], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    decrypt_with_nonce(key, [0u8; 12], ciphertext)
+}
+
+pub fn encrypt_with_nonce(key: [u8; 32], nonce: [u8; 12], plaintext: &[u8]) -> Option<Vec<u8>> {
     let cipher = ChaCha20Poly1305::new_from_slice(key.as_slice()).expect("32 bytes should be a valid ChaCha20 key size");
-    let nonce = Nonce::from([0u8; 12]);
-    cipher.decrypt(&nonce, ciphertext).map_err(|_| eprintln!("Failed to decrypt")).ok()
+    cipher.encrypt(&Nonce::from(nonce), plaintext).map_err(|_| eprintln!("Failed to encrypt")).ok()
+}
+
+pub fn decrypt_with_nonce(key: [u8; 32], nonce: [u8; 12], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new_from_slice(key.as_slice()).expect("32 bytes should be a valid ChaCha20 key size");
+    cipher.decrypt(&Nonce::from(nonce), ciphertext).map_err(|_| eprintln!("Failed to decrypt")).ok()
 }
 
 #[cfg(test)]
@@ -36,4 +44,12 @@ mod tests {
         let result = decrypt([0u8; 32], &[0u8; 48]);
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn encrypt_decrypt_with_nonce() {
+        let encrypted = encrypt_with_nonce([1u8; 32], [2u8; 12], b"this is very secret").unwrap();
+        assert_ne!(encrypted, encrypt([1u8; 32], b"this is very secret").unwrap());
+        assert_eq!(decrypt_with_nonce([1u8; 32], [2u8; 12], &encrypted).unwrap(), b"this is very secret");
+        assert_eq!(decrypt_with_nonce([1u8; 32], [3u8; 12], &encrypted), None);
+    }
 }
@@ -0,0 +1,78 @@
+// Arithmetic in GF(2^8), using the same reducing polynomial as AES: x^8 + x^4 + x^3 + x + 1.
+// Values may be secret (e.g. key shares), so we don't branch on them or use them to index tables.
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a, b, 0u8);
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse, computed as a^254 (so the inverse of 0 is 0).
+pub fn inv(a: u8) -> u8 {
+    let (mut square, mut result) = (a, 1u8);
+    // a^254 = a^2 * a^4 * ... * a^128
+    for _ in 0..7 {
+        square = mul(square, square);
+        result = mul(result, square);
+    }
+    result
+}
+
+/// Lagrange coefficients to evaluate at `x` the polynomial going through points whose x coordinates are `xs` (which
+/// must be distinct): P(x) = sum(coefficients[i] * y[i]).
+pub fn lagrange_coefficients(xs: &[u8], x: u8) -> Vec<u8> {
+    xs.iter()
+        .enumerate()
+        .map(|(i, &xi)| {
+            let (mut numerator, mut denominator) = (1u8, 1u8);
+            for (j, &xj) in xs.iter().enumerate() {
+                if i != j {
+                    // Subtraction is the same as addition (XOR) in GF(2^8).
+                    numerator = mul(numerator, x ^ xj);
+                    denominator = mul(denominator, xi ^ xj);
+                }
+            }
+            mul(numerator, inv(denominator))
+        })
+        .collect()
+}
+
+/// Evaluate at `x` the polynomial going through the given points, using coefficients from `lagrange_coefficients`.
+pub fn interpolate(coefficients: &[u8], ys: &[u8]) -> u8 {
+    coefficients.iter().zip(ys).fold(0u8, |acc, (&c, &y)| acc ^ mul(c, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_inv() {
+        assert_eq!(mul(0x53, 0xca), 0x01);
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+        assert_eq!(mul(0xff, 0), 0);
+        assert_eq!(inv(0x53), 0xca);
+        assert_eq!(inv(1), 1);
+        assert_eq!(inv(0), 0);
+        for a in 1..=255 {
+            assert_eq!(mul(a, inv(a)), 1, "{}", a);
+        }
+    }
+
+    #[test]
+    fn interpolate_polynomial() {
+        // P(x) = 42 + 7x + 3x^2
+        let p = |x: u8| 42 ^ mul(7, x) ^ mul(3, mul(x, x));
+        let xs = [1, 5, 200];
+        let ys: Vec<u8> = xs.iter().map(|&x| p(x)).collect();
+        for x in [0, 1, 2, 77, 255] {
+            assert_eq!(interpolate(&lagrange_coefficients(&xs, x), &ys), p(x), "{}", x);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::hash::HashFunction;
+
+/// Compute the HMAC of the given message with the hash function `H`.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc2104
+pub fn authenticate<H: HashFunction>(key: &[u8], message: &[u8]) -> H::Output {
+    let mut hmac = Hmac::<H>::new(key);
+    hmac.update(message);
+    hmac.finalize()
+}
+
+/// Check the HMAC of the given message in constant time.
+pub fn verify<H: HashFunction>(key: &[u8], message: &[u8], mac: &[u8]) -> bool {
+    let expected = authenticate::<H>(key, message);
+    let expected = expected.as_ref();
+    expected.len() == mac.len() && expected.iter().zip(mac).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Incremental HMAC computation.
+/// Cloning an instance that has only been keyed avoids re-hashing the padded key, which matters for
+/// constructions that compute many HMACs with the same key (e.g. PBKDF2).
+#[derive(Clone, Debug)]
+pub struct Hmac<H: HashFunction> {
+    inner: H,
+    outer: H,
+}
+
+impl<H: HashFunction> Hmac<H> {
+    pub fn new(key: &[u8]) -> Hmac<H> {
+        // The key must be expanded to the hash function's block size: K0 = K || 0x00...
+        // Keys that are longer than the block size are hashed first: K0 = H(K) || 0x00...
+        let mut k0: Vec<u8> = vec![0u8; H::BLOCK_SIZE];
+        if key.len() > H::BLOCK_SIZE {
+            k0[..H::OUTPUT_SIZE].copy_from_slice(H::digest(key).as_ref());
+        } else {
+            k0[..key.len()].copy_from_slice(key);
+        }
+        // The inner hash starts with (K0 ^ ipad) and the outer hash with (K0 ^ opad).
+        let mut inner = H::new();
+        inner.update(&k0.iter().map(|&x| x ^ 0x36).collect::<Vec<u8>>());
+        let mut outer = H::new();
+        outer.update(&k0.iter().map(|&x| x ^ 0x5c).collect::<Vec<u8>>());
+        Hmac { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// We compute H((K0 ^ opad) || H((K0 ^ ipad) || message)).
+    pub fn finalize(self) -> H::Output {
+        let mut outer = self.outer;
+        outer.update(self.inner.finalize().as_ref());
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hash::sha1::Sha1;
+    use crate::hash::sha256::Sha256;
+    use crate::hash::sha512::Sha512;
+    use crate::hex;
+    use rand::rngs::OsRng;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn authenticate_random_messages() {
+        let mut csprng = OsRng {};
+        let mut key: [u8; 32] = [0u8; 32];
+        csprng.fill_bytes(&mut key);
+        let message_size: usize = csprng.gen_range(1..1000);
+        let mut message: Vec<u8> = vec![0; message_size];
+        csprng.fill_bytes(&mut message);
+        let mac1: [u8; 32] = authenticate::<Sha256>(&key, &message);
+        let mac2: [u8; 32] = authenticate::<Sha256>(&key, &message);
+        assert_eq!(mac1, mac2);
+        let mac3: [u8; 32] = authenticate::<Sha256>(&key, b"this is not the same message");
+        assert_ne!(mac1, mac3);
+        csprng.fill_bytes(&mut key);
+        let mac4: [u8; 32] = authenticate::<Sha256>(&key, &message);
+        assert_ne!(mac1, mac4);
+    }
+
+    #[test]
+    fn verify_macs() {
+        let mac = authenticate::<Sha256>(b"key", b"message");
+        assert!(verify::<Sha256>(b"key", b"message", &mac));
+        assert!(!verify::<Sha256>(b"key", b"messages", &mac));
+        assert!(!verify::<Sha256>(b"kez", b"message", &mac));
+        assert!(!verify::<Sha256>(b"key", b"message", &mac[..31]));
+        let mut invalid = mac;
+        invalid[31] ^= 1;
+        assert!(!verify::<Sha256>(b"key", b"message", &invalid));
+    }
+
+    #[test]
+    fn test_vector() {
+        let key: [u8; 32] = hex::decode_array("a3a07ba8aaaeb0d60fad767437b544cbfd790a95702af8e0819f2eb706b46660").unwrap();
+        let message = "cybele controls the keys to the world";
+        let expected: [u8; 32] = hex::decode_array("6397c4768a0a7b122dfbb5d45cd9a3cbed6a6c826365f133a331489ecc5fbcdf").unwrap();
+        let mac = authenticate::<Sha256>(&key, message.as_bytes());
+        assert_eq!(expected, mac);
+    }
+
+    #[test]
+    fn official_test_vectors() {
+        // Test vectors from RFC 2202 and RFC 4231.
+        let message = b"what do ya want for nothing?";
+        assert_eq!(hex::encode(authenticate::<Sha1>(b"Jefe", message)), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+        assert_eq!(
+            hex::encode(authenticate::<Sha512>(b"Jefe", message)),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+        // Keys that are longer than the block size.
+        let message = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        assert_eq!(hex::encode(authenticate::<Sha1>(&[0xaa; 80], message)), "aa4ae5e15272d00e95705637ce8a3b55ed402112");
+        assert_eq!(
+            hex::encode(authenticate::<Sha256>(&[0xaa; 131], message)),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+        assert_eq!(
+            hex::encode(authenticate::<Sha512>(&[0xaa; 131], message)),
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598"
+        );
+    }
+
+    #[test]
+    fn authenticate_incrementally() {
+        let key = [42u8; 20];
+        let message = [7u8; 300];
+        let mut hmac = Hmac::<Sha512>::new(&key);
+        message.chunks(100).for_each(|c| hmac.update(c));
+        assert_eq!(hmac.finalize(), authenticate::<Sha512>(&key, &message));
+    }
+
+    // To run benchmarks:
+    //  - add #![feature(test)] to lib.rs
+    //  - add extern crate test; to lib.rs
+    //  - run cargo +nightly bench
+    // #[bench]
+    // fn bench_sha256(b: &mut Bencher) {
+    //     let key: [u8; 32] = hex::decode_array("a3a07ba8aaaeb0d60fad767437b544cbfd790a95702af8e0819f2eb706b46660").unwrap();
+    //     b.iter(|| authenticate::<Sha256>(&key, b"authentication matters folks"));
+    // }
+}
@@ -1,13 +1,19 @@
 use argon2::password_hash::{Output, PasswordHasher, SaltString};
 use argon2::{Algorithm, Argon2, Params};
 
-use crate::crypto::hmac256;
+use crate::crypto::{hmac, pbkdf2};
+use crate::hash::sha256::Sha256;
 use crate::Version;
 
 #[derive(Debug)]
 pub enum Purpose {
     File,
     Password,
+    Otp,
+    Site,
+    Bundle,
+    Recovery,
+    Attachment,
 }
 
 impl Purpose {
@@ -15,30 +21,105 @@ impl Purpose {
         match self {
             Purpose::File => b"file",
             Purpose::Password => b"password",
+            Purpose::Otp => b"otp",
+            Purpose::Site => b"site",
+            Purpose::Bundle => b"bundle",
+            Purpose::Recovery => b"recovery",
+            Purpose::Attachment => b"attachment",
+        }
+    }
+}
+
+/// Key derivation functions that can be used to derive keys from a password.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    /// Argon2d is only provided to read and write KeePass databases, which use it by default.
+    Argon2d {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    /// PBKDF2 is only provided to decrypt data produced by other applications.
+    Pbkdf2HmacSha256 {
+        iterations: u32,
+    },
+}
+
+impl Kdf {
+    pub fn from_version(version: Version) -> Kdf {
+        // Argon2 parameters are frozen for each version.
+        match version {
+            Version::Test => Kdf::Argon2id {
+                memory_kib: 512,
+                iterations: 1,
+                parallelism: 1,
+            },
+            Version::V1 | Version::V2 => Kdf::Argon2id {
+                memory_kib: 32_768,
+                iterations: 64,
+                parallelism: 4,
+            },
         }
     }
 }
 
 pub fn derive_key(version: Version, password: &str, salt: &[u8], purpose: Purpose) -> Option<[u8; 32]> {
-    // Argon2 parameters are frozen for each version.
-    let params: Params = match version {
-        Version::Test => Params::new(512, 1, 1, Some(32)).unwrap(),
-        Version::V1 => Params::new(32_768, 64, 4, Some(32)).unwrap(),
-    };
-    let argon2: Argon2 = Argon2::new(Algorithm::Argon2id, argon2::Version::V0x13, params);
+    derive_key_with(Kdf::from_version(version), password, salt, purpose)
+}
 
+pub fn derive_key_with(kdf: Kdf, password: &str, salt: &[u8], purpose: Purpose) -> Option<[u8; 32]> {
     // We first derive a 256-bit master key based on the password and salt.
+    let master_key = derive_master_key(kdf, password, salt)?;
+    // We then use HMAC-SHA256 to derive an encryption key.
+    Some(derive_subkey(&master_key, purpose))
+}
+
+/// When multiple keys must be derived from the same password, the (expensive) master key should only be derived once.
+pub fn derive_master_key(kdf: Kdf, password: &str, salt: &[u8]) -> Option<[u8; 32]> {
+    derive_master_key_from_bytes(kdf, password.as_bytes(), salt)
+}
+
+/// Derive a master key from a secret that isn't a password (e.g. the hash of a password and a key file).
+pub fn derive_master_key_from_bytes(kdf: Kdf, secret: &[u8], salt: &[u8]) -> Option<[u8; 32]> {
     let mut master_key: [u8; 32] = [0u8; 32];
-    let salt_str: SaltString = SaltString::encode_b64(salt).map_err(|e| eprintln!("Invalid Argon2 salt: {}", e)).ok()?;
-    let password_hash: Output = argon2
-        .hash_password(password.as_bytes(), &salt_str)
-        .map_err(|e| eprintln!("Cannot hash password: {}", e))
-        .ok()?
-        .hash?;
-    master_key.copy_from_slice(password_hash.as_bytes());
+    match kdf {
+        Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+        | Kdf::Argon2d {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => {
+            let params: Params = Params::new(memory_kib, iterations, parallelism, Some(32))
+                .map_err(|e| eprintln!("Invalid Argon2 parameters: {}", e))
+                .ok()?;
+            let algorithm = match kdf {
+                Kdf::Argon2d { .. } => Algorithm::Argon2d,
+                _ => Algorithm::Argon2id,
+            };
+            let argon2: Argon2 = Argon2::new(algorithm, argon2::Version::V0x13, params);
+            let salt_str: SaltString = SaltString::encode_b64(salt).map_err(|e| eprintln!("Invalid Argon2 salt: {}", e)).ok()?;
+            let password_hash: Output = argon2.hash_password(secret, &salt_str).map_err(|e| eprintln!("Cannot hash password: {}", e)).ok()?.hash?;
+            master_key.copy_from_slice(password_hash.as_bytes());
+        }
+        Kdf::Pbkdf2HmacSha256 { iterations } => {
+            let derived = pbkdf2::derive::<Sha256>(secret, salt, iterations, 32)?;
+            master_key.copy_from_slice(&derived);
+        }
+    }
+    Some(master_key)
+}
 
-    // We then use HMAC-SHA256 to derive an encryption key.
-    Some(hmac256::authenticate(&master_key, purpose.encode()))
+pub fn derive_subkey(master_key: &[u8; 32], purpose: Purpose) -> [u8; 32] {
+    hmac::authenticate::<Sha256>(master_key, purpose.encode())
 }
 
 #[cfg(test)]
@@ -50,8 +131,8 @@ mod tests {
     fn derive_keys() {
         let password1: &str = "this is a strong password";
         let password2: &str = "tH1s m4Y b3 a str0ng#r p4sS0rD";
-        let salt1: [u8; 32] = hex::decode("06b301aadfabf3f756b0ef5d9c7318cf90c4ea4e24ee793bb160fe53e8921efa").unwrap().try_into().unwrap();
-        let salt2: [u8; 32] = hex::decode("da424954b09e6deb057d92c155d214e33cf863a42ac64e4eec42030823bc5f42").unwrap().try_into().unwrap();
+        let salt1: [u8; 32] = hex::decode_array("06b301aadfabf3f756b0ef5d9c7318cf90c4ea4e24ee793bb160fe53e8921efa").unwrap();
+        let salt2: [u8; 32] = hex::decode_array("da424954b09e6deb057d92c155d214e33cf863a42ac64e4eec42030823bc5f42").unwrap();
         let keys = [
             derive_key(Version::Test, password1, &salt1, Purpose::File).unwrap(),
             derive_key(Version::Test, password1, &salt1, Purpose::Password).unwrap(),
@@ -60,12 +141,12 @@ mod tests {
             derive_key(Version::Test, password2, &salt1, Purpose::File).unwrap(),
             derive_key(Version::Test, password2, &salt1, Purpose::Password).unwrap(),
         ];
-        assert_eq!(hex::encode(&keys[0]), "d0737c9cdfbe860348fbd31bf91187bf70a46ac5248f2cc0c9e2bc556718bb1d");
-        assert_eq!(hex::encode(&keys[1]), "ce2c731f80fa9adb43447a516e7c6919846725434169ddd45422ed664f560536");
-        assert_eq!(hex::encode(&keys[2]), "f46ee80977905dcf620b129bb8ac979a16af0f78a2211f579c2e88629713f5ed");
-        assert_eq!(hex::encode(&keys[3]), "f8fddfb3aec70a4e3fa438028f6b87c111ca3d5e0464f24e316bbcd4b03ee7d7");
-        assert_eq!(hex::encode(&keys[4]), "11198ccfdc63034b7406b3b62fa9a9873f1f12cccb3e77fea608415c2891bae2");
-        assert_eq!(hex::encode(&keys[5]), "16df8c15d638192b5ce739bd81ec623bc1359ba5b902087c4cf7bfe564cc1009");
+        assert_eq!(hex::encode(keys[0]), "d0737c9cdfbe860348fbd31bf91187bf70a46ac5248f2cc0c9e2bc556718bb1d");
+        assert_eq!(hex::encode(keys[1]), "ce2c731f80fa9adb43447a516e7c6919846725434169ddd45422ed664f560536");
+        assert_eq!(hex::encode(keys[2]), "f46ee80977905dcf620b129bb8ac979a16af0f78a2211f579c2e88629713f5ed");
+        assert_eq!(hex::encode(keys[3]), "f8fddfb3aec70a4e3fa438028f6b87c111ca3d5e0464f24e316bbcd4b03ee7d7");
+        assert_eq!(hex::encode(keys[4]), "11198ccfdc63034b7406b3b62fa9a9873f1f12cccb3e77fea608415c2891bae2");
+        assert_eq!(hex::encode(keys[5]), "16df8c15d638192b5ce739bd81ec623bc1359ba5b902087c4cf7bfe564cc1009");
     }
 
     #[test]
@@ -75,4 +156,48 @@ mod tests {
         let result = derive_key(Version::Test, password, salt, Purpose::File);
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn derive_keys_with_argon2d() {
+        // Cross-checked with Python's cryptography library.
+        let salt: [u8; 32] = hex::decode_array("06b301aadfabf3f756b0ef5d9c7318cf90c4ea4e24ee793bb160fe53e8921efa").unwrap();
+        let argon2d = Kdf::Argon2d {
+            memory_kib: 1024,
+            iterations: 2,
+            parallelism: 2,
+        };
+        let argon2id = Kdf::Argon2id {
+            memory_kib: 1024,
+            iterations: 2,
+            parallelism: 2,
+        };
+        let password: &str = "this is a strong password";
+        assert_eq!(
+            hex::encode(derive_master_key(argon2d, password, &salt).unwrap()),
+            "ab60737856553d83691158672af32a4fdbe6075995f19eb3583586399868e831"
+        );
+        assert_eq!(
+            hex::encode(derive_master_key(argon2id, password, &salt).unwrap()),
+            "28e2b48032e197a674eee2d70b27ef49f3f651ea3746e552150dcd224466c3f1"
+        );
+        let secret: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            hex::encode(derive_master_key_from_bytes(argon2d, &secret, &salt).unwrap()),
+            "5f2a6fdcba304a7c963aa7989fc5000352fd8b03eeef1a24f19ed1badc5cd6ef"
+        );
+    }
+
+    #[test]
+    fn derive_keys_with_pbkdf2() {
+        let password: &str = "this is a strong password";
+        let salt: [u8; 32] = hex::decode_array("06b301aadfabf3f756b0ef5d9c7318cf90c4ea4e24ee793bb160fe53e8921efa").unwrap();
+        let kdf = Kdf::Pbkdf2HmacSha256 { iterations: 1000 };
+        let master_key = derive_master_key(kdf, password, &salt).unwrap();
+        assert_eq!(hex::encode(master_key), "d899cf5140e4793d80fdd36470779af270b192ef297d631f2985af78ba74ba3d");
+        let file_key = derive_key_with(kdf, password, &salt, Purpose::File).unwrap();
+        let password_key = derive_key_with(kdf, password, &salt, Purpose::Password).unwrap();
+        assert_ne!(file_key, password_key);
+        assert_ne!(file_key, derive_key(Version::Test, password, &salt, Purpose::File).unwrap());
+        assert_eq!(derive_key_with(Kdf::Pbkdf2HmacSha256 { iterations: 0 }, password, &salt, Purpose::File), None);
+    }
 }
@@ -1,3 +1,6 @@
+pub mod aes_cbc;
 pub mod cipher;
-pub mod hmac256;
+pub mod gf256;
+pub mod hmac;
 pub mod keys;
+pub mod pbkdf2;
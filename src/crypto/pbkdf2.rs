@@ -0,0 +1,84 @@
+use crate::crypto::hmac::Hmac;
+use crate::hash::HashFunction;
+
+/// Derive `output_len` bytes from the given password using PBKDF2 with HMAC-`H` as pseudo-random function.
+/// This is weaker than Argon2 against GPU attackers: we only provide it to decrypt data produced by other applications.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc8018#section-5.2
+pub fn derive<H: HashFunction>(password: &[u8], salt: &[u8], iterations: u32, output_len: usize) -> Option<Vec<u8>> {
+    if iterations == 0 || output_len == 0 || output_len as u64 > (u32::MAX as u64) * (H::OUTPUT_SIZE as u64) {
+        eprintln!("Invalid PBKDF2 parameters");
+        return None;
+    }
+    // The password is used as HMAC key for every iteration, so we only process it once.
+    let prf = Hmac::<H>::new(password);
+    let mut output: Vec<u8> = Vec::with_capacity(output_len);
+    let mut block_index = 1u32;
+    while output.len() < output_len {
+        // U_1 = PRF(password, salt || INT(i))
+        let mut hmac = prf.clone();
+        hmac.update(salt);
+        hmac.update(&block_index.to_be_bytes());
+        let mut u = hmac.finalize();
+        // T_i = U_1 ^ U_2 ^ ... ^ U_c, with U_j = PRF(password, U_{j-1})
+        let mut t: Vec<u8> = u.as_ref().to_vec();
+        for _ in 1..iterations {
+            let mut hmac = prf.clone();
+            hmac.update(u.as_ref());
+            u = hmac.finalize();
+            t.iter_mut().zip(u.as_ref()).for_each(|(x, y)| *x ^= y);
+        }
+        let remaining = output_len - output.len();
+        output.extend_from_slice(&t[..remaining.min(t.len())]);
+        block_index += 1;
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hash::sha1::Sha1;
+    use crate::hash::sha256::Sha256;
+    use crate::hex;
+
+    #[test]
+    fn official_test_vectors_sha1() {
+        // Test vectors from RFC 6070.
+        let test_cases = vec![
+            ("password", "salt", 1, 20, "0c60c80f961f0e71f3a9b524af6012062fe037a6"),
+            ("password", "salt", 2, 20, "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957"),
+            ("password", "salt", 4096, 20, "4b007901b765489abead49d926f721d065a429c1"),
+            (
+                "passwordPASSWORDpassword",
+                "saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                25,
+                "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038",
+            ),
+            ("pass\0word", "sa\0lt", 4096, 16, "56fa6aa75548099dcc37d7f03425e0c3"),
+        ];
+        for (password, salt, iterations, output_len, expected) in test_cases {
+            assert_eq!(hex::encode(derive::<Sha1>(password.as_bytes(), salt.as_bytes(), iterations, output_len).unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn official_test_vectors_sha256() {
+        // Test vectors from RFC 7914.
+        assert_eq!(
+            hex::encode(derive::<Sha256>(b"passwd", b"salt", 1, 64).unwrap()),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+        );
+        assert_eq!(
+            hex::encode(derive::<Sha256>(b"Password", b"NaCl", 80000, 64).unwrap()),
+            "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d"
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(derive::<Sha256>(b"password", b"salt", 0, 32), None);
+        assert_eq!(derive::<Sha256>(b"password", b"salt", 1, 0), None);
+    }
+}
@@ -1 +1,24 @@
+pub mod sha1;
 pub mod sha256;
+pub mod sha512;
+
+/// Hash functions that can be computed incrementally.
+/// This lets us build generic constructions (such as HMAC) on top of any of our hash functions.
+pub trait HashFunction: Clone {
+    /// Size (in bytes) of the blocks processed by the compression function.
+    const BLOCK_SIZE: usize;
+    /// Size (in bytes) of the resulting digest.
+    const OUTPUT_SIZE: usize;
+    type Output: AsRef<[u8]> + Copy;
+
+    fn new() -> Self;
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self) -> Self::Output;
+
+    /// Hash a complete message at once.
+    fn digest(message: &[u8]) -> Self::Output {
+        let mut hasher = Self::new();
+        hasher.update(message);
+        hasher.finalize()
+    }
+}
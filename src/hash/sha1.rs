@@ -0,0 +1,150 @@
+use crate::hash::HashFunction;
+
+/// Compute the SHA-1 hash of the given message, without making any heap allocation.
+/// SHA-1 must not be used for new designs: we only provide it for interoperability (e.g. TOTP codes).
+/// Specification can be found here: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+pub fn hash(message: &[u8]) -> [u8; 20] {
+    Sha1::digest(message)
+}
+
+/// Incremental SHA-1 computation: data can be provided in chunks of any size.
+#[derive(Clone, Debug)]
+pub struct Sha1 {
+    // Intermediate hash values.
+    state: [u32; 5],
+    // Bytes that don't fill a complete block yet.
+    buffer: [u8; 64],
+    buffer_len: usize,
+    // Number of bytes processed so far.
+    message_len: u64,
+}
+
+impl HashFunction for Sha1 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_SIZE: usize = 20;
+    type Output = [u8; 20];
+
+    fn new() -> Sha1 {
+        Sha1 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            buffer: [0u8; 64],
+            buffer_len: 0,
+            message_len: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if self.message_len + (data.len() as u64) >= (1 << 61) {
+            panic!("cannot hash messages containing more than 2^64 bits");
+        }
+        self.message_len += data.len() as u64;
+        let mut data = data;
+        // We first complete the pending block, if any.
+        if self.buffer_len > 0 {
+            let n = data.len().min(64 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + n].copy_from_slice(&data[..n]);
+            self.buffer_len += n;
+            data = &data[n..];
+            if self.buffer_len < 64 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+        // We then process complete blocks directly from the input.
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        // And keep the remaining bytes for later.
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    fn finalize(mut self) -> [u8; 20] {
+        // The padding is identical to SHA-256: a '1' bit, '0' bits and the message bit size encoded using 64 bits.
+        let message_bits_count = self.message_len * 8;
+        let pad_len = if self.buffer_len < 56 { 56 - self.buffer_len } else { 120 - self.buffer_len };
+        let mut padding = [0u8; 72];
+        padding[0] = 0x80;
+        padding[pad_len..pad_len + 8].copy_from_slice(&message_bits_count.to_be_bytes());
+        // Padding must not be counted in the message length.
+        let message_len = self.message_len;
+        self.update(&padding[..pad_len + 8]);
+        self.message_len = message_len;
+
+        let mut result = [0u8; 20];
+        for (chunk, h) in result.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+        result
+    }
+}
+
+impl Sha1 {
+    fn compress(&mut self, block: &[u8]) {
+        // Prepare the message schedule.
+        let mut w = [0u32; 80];
+        for (j, wj) in w.iter_mut().enumerate().take(16) {
+            *wj = u32::from_be_bytes([block[4 * j], block[4 * j + 1], block[4 * j + 2], block[4 * j + 3]]);
+        }
+        for j in 16..80 {
+            w[j] = (w[j - 3] ^ w[j - 8] ^ w[j - 14] ^ w[j - 16]).rotate_left(1);
+        }
+        // Initialize working variables.
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        // Update working variables.
+        for (t, wt) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) ^ (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) ^ (b & d) ^ (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*wt);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        // Compute intermediate hash values.
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *s = v.wrapping_add(*s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    #[test]
+    fn official_test_vectors() {
+        let h1 = hex::encode(hash(b"abc"));
+        assert_eq!(h1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        let h2 = hex::encode(hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"));
+        assert_eq!(h2, "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    }
+
+    #[test]
+    fn hash_long_message() {
+        let message = [42u8; 150000];
+        let h = hash(&message);
+        assert_eq!(hex::encode(h), "0636e3031b95f0d25fb3e53b1163c85705e4c966");
+    }
+
+    #[test]
+    fn hash_incrementally() {
+        let message = [42u8; 1000];
+        let expected = hash(&message);
+        for chunk_size in [1, 7, 55, 56, 63, 64, 65, 128, 999] {
+            let mut hasher = Sha1::new();
+            message.chunks(chunk_size).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.finalize(), expected);
+        }
+    }
+}
@@ -1,71 +1,112 @@
 use std::ops::Shr;
 
+use crate::hash::HashFunction;
+
+// SHA-256 constants.
+#[rustfmt::skip]
+const K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
 /// Compute the SHA-256 hash of the given message, without making any heap allocation.
 /// Specification can be found here: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
 pub fn hash(message: &[u8]) -> [u8; 32] {
-    let message_bytes_count = message.len();
-    let message_bits_count = 8 * message_bytes_count;
-    if message_bytes_count >= (2 << 61) {
-        panic!("cannot hash messages containing more than 2^64 bits");
-    }
+    Sha256::digest(message)
+}
+
+/// Incremental SHA-256 computation: data can be provided in chunks of any size.
+#[derive(Clone, Debug)]
+pub struct Sha256 {
+    // Intermediate hash values.
+    state: [u32; 8],
+    // Bytes that don't fill a complete block yet.
+    buffer: [u8; 64],
+    buffer_len: usize,
+    // Number of bytes processed so far.
+    message_len: u64,
+}
 
-    // The message is padded to be a multiple of 64 bytes:
-    //  - start with a '1' bit followed by '0' bits
-    //  - end with the message bit size encoded using 64 bits (big-endian)
-    // Notes:
-    //  - we must use rem_euclid because rust's % doesn't work on negative integers
-    //  - the padding may use up to 72 bytes (for messages that are `64 * n + 56` bytes long)
-    let pad_len = ((((448i64 - (message_bits_count as i64) - 1).rem_euclid(512)) + 1) / 8) as usize;
+impl HashFunction for Sha256 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_SIZE: usize = 32;
+    type Output = [u8; 32];
 
-    // SHA-256 constants.
-    #[rustfmt::skip]
-    let k256: [u32; 64] = [
-        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
-        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
-        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
-        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
-        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
-        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
-        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
-        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
-    ];
+    fn new() -> Sha256 {
+        Sha256 {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            buffer: [0u8; 64],
+            buffer_len: 0,
+            message_len: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if self.message_len + (data.len() as u64) >= (1 << 61) {
+            panic!("cannot hash messages containing more than 2^64 bits");
+        }
+        self.message_len += data.len() as u64;
+        let mut data = data;
+        // We first complete the pending block, if any.
+        if self.buffer_len > 0 {
+            let n = data.len().min(64 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + n].copy_from_slice(&data[..n]);
+            self.buffer_len += n;
+            data = &data[n..];
+            if self.buffer_len < 64 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+        // We then process complete blocks directly from the input.
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        // And keep the remaining bytes for later.
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
 
-    // Initialize intermediate hash values.
-    let mut h0: u32 = 0x6a09e667;
-    let mut h1: u32 = 0xbb67ae85;
-    let mut h2: u32 = 0x3c6ef372;
-    let mut h3: u32 = 0xa54ff53a;
-    let mut h4: u32 = 0x510e527f;
-    let mut h5: u32 = 0x9b05688c;
-    let mut h6: u32 = 0x1f83d9ab;
-    let mut h7: u32 = 0x5be0cd19;
+    fn finalize(mut self) -> [u8; 32] {
+        // The message is padded to be a multiple of 64 bytes:
+        //  - start with a '1' bit followed by '0' bits
+        //  - end with the message bit size encoded using 64 bits (big-endian)
+        // We only support byte-aligned messages, so the first padding byte is 0x80.
+        // Note that the padding may use up to 72 bytes (for messages that are `64 * n + 56` bytes long).
+        let message_bits_count = self.message_len * 8;
+        let pad_len = if self.buffer_len < 56 { 56 - self.buffer_len } else { 120 - self.buffer_len };
+        let mut padding = [0u8; 72];
+        padding[0] = 0x80;
+        padding[pad_len..pad_len + 8].copy_from_slice(&message_bits_count.to_be_bytes());
+        // Padding must not be counted in the message length.
+        let message_len = self.message_len;
+        self.update(&padding[..pad_len + 8]);
+        self.message_len = message_len;
 
-    // Initialize message schedule.
-    let mut w = [0u32; 64];
+        let mut result = [0u8; 32];
+        for (chunk, h) in result.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+        result
+    }
+}
 
-    // Then process the message with its padding as 64-bytes blocks.
-    let blocks_count = (message_bytes_count + pad_len + 8) / 64;
-    for i in 0..blocks_count {
+impl Sha256 {
+    fn compress(&mut self, block: &[u8]) {
         // Parse the message block into 16 4-bytes words.
+        let mut w = [0u32; 64];
         for (j, wj) in w.iter_mut().enumerate().take(16) {
-            *wj = 0;
-            for k in 0..4 {
-                let index = 64 * i + 4 * j + k;
-                if index < message_bytes_count {
-                    *wj = wj.wrapping_add((message[index] as u32) << (8 * (3 - k)));
-                } else if index == message_bytes_count {
-                    // Padding starts with a '1' bit followed by '0' bits.
-                    // We only support byte-aligned messages, so the first padding byte is 0x80.
-                    *wj = wj.wrapping_add(128_u32 << (8 * (3 - k)));
-                } else if index < message_bytes_count + pad_len {
-                    // The message is then padded with '0' bytes.
-                    // The message schedule is not impacted.
-                } else {
-                    // Padding ends with the message bit size encoded using 64 bits (big-endian).
-                    let padding_byte = (message_bits_count >> (64 - 8 * (index - message_bytes_count - pad_len + 1))) as u8;
-                    *wj = wj.wrapping_add((padding_byte as u32) << (8 * (3 - k)));
-                }
-            }
+            *wj = u32::from_be_bytes([block[4 * j], block[4 * j + 1], block[4 * j + 2], block[4 * j + 3]]);
         }
         // Compute the next elements of the message schedule.
         for j in 16..64 {
@@ -75,20 +116,13 @@ pub fn hash(message: &[u8]) -> [u8; 32] {
             w[j] = w[j].wrapping_add(w[j - 16]);
         }
         // Initialize working variables.
-        let mut a = h0;
-        let mut b = h1;
-        let mut c = h2;
-        let mut d = h3;
-        let mut e = h4;
-        let mut f = h5;
-        let mut g = h6;
-        let mut h = h7;
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
         // Update working variables.
         for t in 0..64 {
             let t1: u32 = h
                 .wrapping_add(e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25))
                 .wrapping_add((e & f) ^ (!e & g))
-                .wrapping_add(k256[t])
+                .wrapping_add(K256[t])
                 .wrapping_add(w[t]);
             let t2: u32 = (a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22)).wrapping_add((a & b) ^ (a & c) ^ (b & c));
             h = g;
@@ -101,28 +135,10 @@ pub fn hash(message: &[u8]) -> [u8; 32] {
             a = t1.wrapping_add(t2);
         }
         // Compute intermediate hash values.
-        h0 = a.wrapping_add(h0);
-        h1 = b.wrapping_add(h1);
-        h2 = c.wrapping_add(h2);
-        h3 = d.wrapping_add(h3);
-        h4 = e.wrapping_add(h4);
-        h5 = f.wrapping_add(h5);
-        h6 = g.wrapping_add(h6);
-        h7 = h.wrapping_add(h7);
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = v.wrapping_add(*s);
+        }
     }
-
-    #[rustfmt::skip]
-    let result: [u8; 32] = [
-        (h0 >> 24) as u8, (h0 >> 16) as u8, (h0 >> 8) as u8, h0 as u8,
-        (h1 >> 24) as u8, (h1 >> 16) as u8, (h1 >> 8) as u8, h1 as u8,
-        (h2 >> 24) as u8, (h2 >> 16) as u8, (h2 >> 8) as u8, h2 as u8,
-        (h3 >> 24) as u8, (h3 >> 16) as u8, (h3 >> 8) as u8, h3 as u8,
-        (h4 >> 24) as u8, (h4 >> 16) as u8, (h4 >> 8) as u8, h4 as u8,
-        (h5 >> 24) as u8, (h5 >> 16) as u8, (h5 >> 8) as u8, h5 as u8,
-        (h6 >> 24) as u8, (h6 >> 16) as u8, (h6 >> 8) as u8, h6 as u8,
-        (h7 >> 24) as u8, (h7 >> 16) as u8, (h7 >> 8) as u8, h7 as u8,
-    ];
-    result
 }
 
 #[cfg(test)]
@@ -145,6 +161,17 @@ mod tests {
         assert_eq!(hex::encode(h), "dc7dc699db6610842790da50372dca1eec1609d3016bcefebb1f89abff64b020");
     }
 
+    #[test]
+    fn hash_incrementally() {
+        let message = [42u8; 1000];
+        let expected = hash(&message);
+        for chunk_size in [1, 7, 55, 56, 63, 64, 65, 128, 999] {
+            let mut hasher = Sha256::new();
+            message.chunks(chunk_size).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.finalize(), expected);
+        }
+    }
+
     // To run benchmarks:
     //  - add #![feature(test)] to lib.rs
     //  - add extern crate test; to lib.rs
@@ -0,0 +1,191 @@
+use std::ops::Shr;
+
+use crate::hash::HashFunction;
+
+// SHA-512 constants.
+#[rustfmt::skip]
+const K512: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc, 0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2, 0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65, 0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4, 0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df, 0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30, 0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8, 0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec, 0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178, 0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c, 0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+/// Compute the SHA-512 hash of the given message, without making any heap allocation.
+/// Specification can be found here: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+pub fn hash(message: &[u8]) -> [u8; 64] {
+    Sha512::digest(message)
+}
+
+/// Incremental SHA-512 computation: data can be provided in chunks of any size.
+#[derive(Clone, Debug)]
+pub struct Sha512 {
+    // Intermediate hash values.
+    state: [u64; 8],
+    // Bytes that don't fill a complete block yet.
+    buffer: [u8; 128],
+    buffer_len: usize,
+    // Number of bytes processed so far.
+    message_len: u128,
+}
+
+impl HashFunction for Sha512 {
+    const BLOCK_SIZE: usize = 128;
+    const OUTPUT_SIZE: usize = 64;
+    type Output = [u8; 64];
+
+    fn new() -> Sha512 {
+        Sha512 {
+            state: [
+                0x6a09e667f3bcc908,
+                0xbb67ae8584caa73b,
+                0x3c6ef372fe94f82b,
+                0xa54ff53a5f1d36f1,
+                0x510e527fade682d1,
+                0x9b05688c2b3e6c1f,
+                0x1f83d9abfb41bd6b,
+                0x5be0cd19137e2179,
+            ],
+            buffer: [0u8; 128],
+            buffer_len: 0,
+            message_len: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.message_len += data.len() as u128;
+        let mut data = data;
+        // We first complete the pending block, if any.
+        if self.buffer_len > 0 {
+            let n = data.len().min(128 - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + n].copy_from_slice(&data[..n]);
+            self.buffer_len += n;
+            data = &data[n..];
+            if self.buffer_len < 128 {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+        // We then process complete blocks directly from the input.
+        let mut blocks = data.chunks_exact(128);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        // And keep the remaining bytes for later.
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    fn finalize(mut self) -> [u8; 64] {
+        // The message is padded to be a multiple of 128 bytes:
+        //  - start with a '1' bit followed by '0' bits
+        //  - end with the message bit size encoded using 128 bits (big-endian)
+        let message_bits_count = self.message_len * 8;
+        let pad_len = if self.buffer_len < 112 { 112 - self.buffer_len } else { 240 - self.buffer_len };
+        let mut padding = [0u8; 144];
+        padding[0] = 0x80;
+        padding[pad_len..pad_len + 16].copy_from_slice(&message_bits_count.to_be_bytes());
+        // Padding must not be counted in the message length.
+        let message_len = self.message_len;
+        self.update(&padding[..pad_len + 16]);
+        self.message_len = message_len;
+
+        let mut result = [0u8; 64];
+        for (chunk, h) in result.chunks_exact_mut(8).zip(self.state.iter()) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+        result
+    }
+}
+
+impl Sha512 {
+    fn compress(&mut self, block: &[u8]) {
+        // Parse the message block into 16 8-bytes words.
+        let mut w = [0u64; 80];
+        for (j, wj) in w.iter_mut().enumerate().take(16) {
+            *wj = u64::from_be_bytes(block[8 * j..8 * j + 8].try_into().unwrap());
+        }
+        // Compute the next elements of the message schedule.
+        for j in 16..80 {
+            w[j] = w[j - 2].rotate_right(19) ^ w[j - 2].rotate_right(61) ^ w[j - 2].shr(6); // sigma_1
+            w[j] = w[j].wrapping_add(w[j - 7]);
+            w[j] = w[j].wrapping_add(w[j - 15].rotate_right(1) ^ w[j - 15].rotate_right(8) ^ w[j - 15].shr(7)); // sigma_0
+            w[j] = w[j].wrapping_add(w[j - 16]);
+        }
+        // Initialize working variables.
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        // Update working variables.
+        for t in 0..80 {
+            let t1: u64 = h
+                .wrapping_add(e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41))
+                .wrapping_add((e & f) ^ (!e & g))
+                .wrapping_add(K512[t])
+                .wrapping_add(w[t]);
+            let t2: u64 = (a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39)).wrapping_add((a & b) ^ (a & c) ^ (b & c));
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        // Compute intermediate hash values.
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = v.wrapping_add(*s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex;
+
+    #[test]
+    fn official_test_vectors() {
+        let h1 = hex::encode(hash(b"abc"));
+        assert_eq!(
+            h1,
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        let h2 = hex::encode(hash(
+            b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+        ));
+        assert_eq!(
+            h2,
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        );
+    }
+
+    #[test]
+    fn hash_long_message() {
+        let message = [42u8; 150000];
+        let h = hash(&message);
+        assert_eq!(
+            hex::encode(h),
+            "2e03528d2b4691e1ca2c99953f0557b57b03cc7ca11bbc6e62fb8cbd1cf8044f0ba4edfae44cd272b1497a4b702e833a8a3d7d64c46bce64d6431e578656a93c"
+        );
+    }
+
+    #[test]
+    fn hash_incrementally() {
+        let message = [42u8; 1000];
+        let expected = hash(&message);
+        for chunk_size in [1, 7, 111, 112, 127, 128, 129, 256, 999] {
+            let mut hasher = Sha512::new();
+            message.chunks(chunk_size).for_each(|c| hasher.update(c));
+            assert_eq!(hasher.finalize(), expected);
+        }
+    }
+}
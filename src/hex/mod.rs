@@ -1,88 +1,208 @@
-/// Encode to a hexadecimal string.
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Hexadecimal strings must contain an even number of chars.
+    OddLength,
+    InvalidChar {
+        c: char,
+        index: usize,
+    },
+    /// The decoded data doesn't have the expected size.
+    InvalidLength {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::OddLength => write!(f, "odd number of hexadecimal chars"),
+            DecodeError::InvalidChar { c, index } => write!(f, "non hexadecimal char at index {}: {}", index, c),
+            DecodeError::InvalidLength { expected, actual } => write!(f, "invalid decoded length: expected {} bytes, got {}", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Encode to a (lowercase) hexadecimal string.
 pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
-    let mut encoded = String::with_capacity(data.as_ref().len() * 2);
-    for &x in data.as_ref() {
-        encoded.push(byte_to_char(x >> 4));
-        encoded.push(byte_to_char(x & 0x0f));
+    encode_with(data.as_ref(), b'a')
+}
+
+/// Encode to an uppercase hexadecimal string.
+pub fn encode_upper<T: AsRef<[u8]>>(data: T) -> String {
+    encode_with(data.as_ref(), b'A')
+}
+
+fn encode_with(data: &[u8], a: u8) -> String {
+    let mut encoded = String::with_capacity(data.len() * 2);
+    for &x in data {
+        encoded.push(byte_to_char(x >> 4, a));
+        encoded.push(byte_to_char(x & 0x0f, a));
     }
     encoded
 }
 
-fn byte_to_char(b: u8) -> char {
-    match b {
-        0u8 => '0',
-        1u8 => '1',
-        2u8 => '2',
-        3u8 => '3',
-        4u8 => '4',
-        5u8 => '5',
-        6u8 => '6',
-        7u8 => '7',
-        8u8 => '8',
-        9u8 => '9',
-        10u8 => 'a',
-        11u8 => 'b',
-        12u8 => 'c',
-        13u8 => 'd',
-        14u8 => 'e',
-        15u8 => 'f',
-        _ => panic!("invalid half-byte value"),
-    }
+// We don't branch on the data, so that encoding secrets doesn't leak timing information.
+fn byte_to_char(b: u8, a: u8) -> char {
+    // Returns 0xff if b >= 10, 0x00 otherwise.
+    let letter = !(((b as u16).wrapping_sub(10) >> 8) as u8);
+    ((!letter & (b + b'0')) | (letter & (b.wrapping_sub(10).wrapping_add(a)))) as char
 }
 
-/// Decode an assumed valid hexadecimal string.
-pub fn decode(data: &str) -> Result<Vec<u8>, String> {
-    let mut bytes = Vec::<u8>::with_capacity(data.len() / 2);
-    let mut carry = true;
-    let mut half = 0u8;
-    for c in data.chars() {
-        let value = char_to_byte(c)?;
-        match carry {
-            true => {
-                half = value << 4;
-                carry = false;
-            }
-            false => {
-                bytes.push(half + value);
-                half = 0u8;
-                carry = true;
-            }
-        }
+/// Decode a hexadecimal string (lowercase and uppercase chars are accepted).
+pub fn decode(data: &str) -> Result<Vec<u8>, DecodeError> {
+    let mut bytes = vec![0u8; data.len() / 2];
+    decode_into(data, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decode a hexadecimal string into the provided buffer, which must have the exact decoded size.
+pub fn decode_into(data: &str, output: &mut [u8]) -> Result<(), DecodeError> {
+    let data = data.as_bytes();
+    if !data.len().is_multiple_of(2) {
+        return Err(DecodeError::OddLength);
+    }
+    if data.len() / 2 != output.len() {
+        return Err(DecodeError::InvalidLength {
+            expected: output.len(),
+            actual: data.len() / 2,
+        });
+    }
+    for (i, (out, chunk)) in output.iter_mut().zip(data.chunks_exact(2)).enumerate() {
+        let high = char_to_byte(chunk[0]).ok_or_else(|| invalid_char(data, 2 * i))?;
+        let low = char_to_byte(chunk[1]).ok_or_else(|| invalid_char(data, 2 * i + 1))?;
+        *out = (high << 4) | low;
     }
+    Ok(())
+}
+
+/// Decode a hexadecimal string of a known size (e.g. a salt or a key).
+pub fn decode_array<const N: usize>(data: &str) -> Result<[u8; N], DecodeError> {
+    let mut bytes = [0u8; N];
+    decode_into(data, &mut bytes)?;
     Ok(bytes)
 }
 
-fn char_to_byte(c: char) -> Result<u8, String> {
+fn char_to_byte(c: u8) -> Option<u8> {
     match c {
-        '0' => Ok(0u8),
-        '1' => Ok(1u8),
-        '2' => Ok(2u8),
-        '3' => Ok(3u8),
-        '4' => Ok(4u8),
-        '5' => Ok(5u8),
-        '6' => Ok(6u8),
-        '7' => Ok(7u8),
-        '8' => Ok(8u8),
-        '9' => Ok(9u8),
-        'a' => Ok(10u8),
-        'b' => Ok(11u8),
-        'c' => Ok(12u8),
-        'd' => Ok(13u8),
-        'e' => Ok(14u8),
-        'f' => Ok(15u8),
-        c => Err(format!("non hexadecimal char: {}", c)),
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
     }
 }
 
+/// Decode a hexadecimal string containing secret data (e.g. a key) in constant time.
+/// The running time only depends on the length of the data, even when it contains invalid chars.
+pub fn decode_ct(data: &str) -> Result<Vec<u8>, DecodeError> {
+    let data = data.as_bytes();
+    if !data.len().is_multiple_of(2) {
+        return Err(DecodeError::OddLength);
+    }
+    let mut bytes = vec![0u8; data.len() / 2];
+    let mut invalid = 0u8;
+    for (out, chunk) in bytes.iter_mut().zip(data.chunks_exact(2)) {
+        let (high, high_valid) = char_to_byte_ct(chunk[0]);
+        let (low, low_valid) = char_to_byte_ct(chunk[1]);
+        invalid |= !high_valid | !low_valid;
+        *out = (high << 4) | low;
+    }
+    if invalid != 0 {
+        // We've already detected an error, so we don't need to protect the position of the invalid char.
+        let index = data.iter().position(|&c| char_to_byte(c).is_none()).unwrap_or(0);
+        return Err(invalid_char(data, index));
+    }
+    Ok(bytes)
+}
+
+// Returns the decoded value and 0xff if the char is valid (0x00 otherwise).
+fn char_to_byte_ct(c: u8) -> (u8, u8) {
+    // Returns 0xff if lo <= c <= hi, 0x00 otherwise.
+    let between = |lo: u8, hi: u8| !(((c as u16).wrapping_sub(lo as u16) >> 8) as u8) & !(((hi as u16).wrapping_sub(c as u16) >> 8) as u8);
+    let digit = between(b'0', b'9');
+    let lower = between(b'a', b'f');
+    let upper = between(b'A', b'F');
+    let value = (digit & c.wrapping_sub(b'0')) | (lower & c.wrapping_sub(b'a' - 10)) | (upper & c.wrapping_sub(b'A' - 10));
+    (value, digit | lower | upper)
+}
+
+fn invalid_char(data: &[u8], index: usize) -> DecodeError {
+    // The index points to a byte, which may be in the middle of a multi-byte utf8 char.
+    let c = String::from_utf8_lossy(&data[index..]).chars().next().unwrap_or(char::REPLACEMENT_CHARACTER);
+    DecodeError::InvalidChar { c, index }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use rand::rngs::OsRng;
+    use rand::{Rng, RngCore};
+
     #[test]
     fn encode_decode_hex() {
         let bytes: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
         let str = "deadbeef";
         assert_eq!(encode(bytes), str);
+        assert_eq!(encode_upper(bytes), "DEADBEEF");
         assert_eq!(decode(str).unwrap(), bytes);
+        assert_eq!(decode("DeAdBeEf").unwrap(), bytes);
+        assert_eq!(decode_ct("DeAdBeEf").unwrap(), bytes);
+        assert_eq!(decode_array::<4>(str).unwrap(), bytes);
+        assert_eq!(encode([0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]), "0123456789abcdef");
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn decode_failure() {
+        assert_eq!(decode("abc"), Err(DecodeError::OddLength));
+        assert_eq!(decode_ct("abc"), Err(DecodeError::OddLength));
+        assert_eq!(decode("deadbeeg"), Err(DecodeError::InvalidChar { c: 'g', index: 7 }));
+        assert_eq!(decode_ct("deadbeeg"), Err(DecodeError::InvalidChar { c: 'g', index: 7 }));
+        assert_eq!(decode("0x00"), Err(DecodeError::InvalidChar { c: 'x', index: 1 }));
+        assert_eq!(decode("éé"), Err(DecodeError::InvalidChar { c: 'é', index: 0 }));
+        assert_eq!(decode_array::<4>("deadbe"), Err(DecodeError::InvalidLength { expected: 4, actual: 3 }));
+        assert_eq!(decode_array::<2>("deadbe"), Err(DecodeError::InvalidLength { expected: 2, actual: 3 }));
+        let mut output = [0u8; 3];
+        assert_eq!(decode_into("deadbe ", &mut output), Err(DecodeError::OddLength));
+        assert_eq!(decode_into("deadbe", &mut output), Ok(()));
+        assert_eq!(output, [0xde, 0xad, 0xbe]);
+        assert_eq!(DecodeError::InvalidChar { c: 'g', index: 7 }.to_string(), "non hexadecimal char at index 7: g");
+    }
+
+    #[test]
+    fn encode_decode_random_data() {
+        let mut csprng = OsRng {};
+        for _ in 0..100 {
+            let mut data = vec![0u8; csprng.gen_range(0..100)];
+            csprng.fill_bytes(&mut data);
+            let encoded = encode(&data);
+            assert_eq!(encoded.len(), 2 * data.len());
+            assert_eq!(decode(&encoded).unwrap(), data);
+            assert_eq!(decode_ct(&encoded).unwrap(), data);
+            assert_eq!(decode(&encode_upper(&data)).unwrap(), data);
+            let mut key = [0u8; 32];
+            csprng.fill_bytes(&mut key);
+            assert_eq!(decode_array::<32>(&encode(key)).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn decode_random_strings() {
+        // Both decoding paths must accept and reject exactly the same inputs.
+        let mut csprng = OsRng {};
+        let chars: Vec<char> = "0123456789abcdefABCDEFghxyzGHXYZ -_é".chars().collect();
+        for _ in 0..1000 {
+            let len = csprng.gen_range(0..10);
+            let s: String = (0..len).map(|_| chars[csprng.gen_range(0..chars.len())]).collect();
+            assert_eq!(decode(&s), decode_ct(&s), "{}", s);
+            if let Ok(decoded) = decode(&s) {
+                assert_eq!(encode(decoded), s.to_lowercase());
+            }
+        }
     }
 }
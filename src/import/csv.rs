@@ -0,0 +1,373 @@
+use crate::import::{self, Import, ImportedItem};
+use crate::otp::Otp;
+
+/// Columns of a CSV export, which differ between password managers.
+/// Column names are matched case-insensitively.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvFormat {
+    /// Header written on export, since other applications usually expect all of their columns.
+    pub columns: Vec<String>,
+    pub name: Option<String>,
+    /// When the name is missing, items are named after their URL's host (formats without name column store the name
+    /// in this column on export).
+    pub url: Option<String>,
+    /// Only used as the account of one-time passwords.
+    pub username: Option<String>,
+    pub password: String,
+    pub otp: Option<String>,
+    pub notes: Option<String>,
+    /// Folders are mapped to a prefix of item names (e.g. "Internet/Email").
+    pub folder: Option<String>,
+}
+
+fn column(name: &str) -> Option<String> {
+    Some(String::from(name))
+}
+
+impl CsvFormat {
+    fn new(columns: &[&str], password: &str) -> CsvFormat {
+        CsvFormat {
+            columns: columns.iter().map(|c| String::from(*c)).collect(),
+            name: None,
+            url: None,
+            username: None,
+            password: String::from(password),
+            otp: None,
+            notes: None,
+            folder: None,
+        }
+    }
+
+    pub fn chrome() -> CsvFormat {
+        CsvFormat {
+            name: column("name"),
+            url: column("url"),
+            username: column("username"),
+            notes: column("note"),
+            ..CsvFormat::new(&["name", "url", "username", "password", "note"], "password")
+        }
+    }
+
+    pub fn firefox() -> CsvFormat {
+        let columns = [
+            "url",
+            "username",
+            "password",
+            "httpRealm",
+            "formActionOrigin",
+            "guid",
+            "timeCreated",
+            "timeLastUsed",
+            "timePasswordChanged",
+        ];
+        CsvFormat {
+            url: column("url"),
+            username: column("username"),
+            ..CsvFormat::new(&columns, "password")
+        }
+    }
+
+    pub fn lastpass() -> CsvFormat {
+        CsvFormat {
+            name: column("name"),
+            url: column("url"),
+            username: column("username"),
+            otp: column("totp"),
+            notes: column("extra"),
+            folder: column("grouping"),
+            ..CsvFormat::new(&["url", "username", "password", "totp", "extra", "name", "grouping", "fav"], "password")
+        }
+    }
+
+    pub fn one_password() -> CsvFormat {
+        let columns = ["Title", "Url", "Username", "Password", "OTPAuth", "Favorite", "Archived", "Tags", "Notes"];
+        CsvFormat {
+            name: column("Title"),
+            url: column("Url"),
+            username: column("Username"),
+            otp: column("OTPAuth"),
+            notes: column("Notes"),
+            ..CsvFormat::new(&columns, "Password")
+        }
+    }
+}
+
+/// Parse a CSV export whose first row contains the column names.
+/// Passwords and one-time passwords are mapped to items, and notes to items named "<item> / Notes". Other columns are
+/// reported.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc4180
+pub fn parse(data: &str, format: &CsvFormat) -> Result<Import, String> {
+    let mut rows = parse_rows(data.strip_prefix('\u{feff}').unwrap_or(data))?.into_iter();
+    let header = rows.next().ok_or("missing CSV header")?;
+    let index = |name: &Option<String>| name.as_ref().and_then(|name| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name)));
+    let password_index = index(&Some(format.password.clone())).ok_or_else(|| format!("missing CSV column: {}", format.password))?;
+    let (name_index, url_index, username_index) = (index(&format.name), index(&format.url), index(&format.username));
+    let (otp_index, notes_index, folder_index) = (index(&format.otp), index(&format.notes), index(&format.folder));
+    let mut import = Import::default();
+    for (line, row) in rows.enumerate() {
+        if row.len() != header.len() {
+            return Err(format!("invalid CSV row {}: expected {} columns, found {}", line + 2, header.len(), row.len()));
+        }
+        let get = |index: Option<usize>| index.map(|i| row[i].as_str()).filter(|v| !v.is_empty());
+        let title = get(name_index).or_else(|| get(url_index).map(url_host)).unwrap_or("Untitled");
+        let name = match get(folder_index) {
+            Some(folder) => format!("{}/{}", folder, title),
+            None => String::from(title),
+        };
+        let mut mapped = vec![Some(password_index), name_index, notes_index, folder_index];
+        let otp = get(otp_index).and_then(|otp| import::parse_totp(otp, get(username_index).unwrap_or(title), title));
+        if otp.is_some() {
+            mapped.push(otp_index);
+        }
+        let password = get(Some(password_index)).unwrap_or("");
+        if !password.is_empty() || otp.is_some() {
+            import.add_item(&name, password, otp);
+        }
+        if let Some(notes) = get(notes_index) {
+            import.add_item(&format!("{} / Notes", name), notes, None);
+        }
+        for (i, value) in row.iter().enumerate() {
+            if !value.is_empty() && !mapped.contains(&Some(i)) {
+                import.add_unmapped(&name, header[i].trim());
+            }
+        }
+    }
+    Ok(import)
+}
+
+// Hosts are shorter than URLs, and usually enough to identify an item (e.g. "https://github.com/login").
+// Values that aren't URLs are kept (e.g. names exported to the URL column).
+fn url_host(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().filter(|h| !h.is_empty()).unwrap_or(url),
+        None => url,
+    }
+}
+
+fn parse_rows(data: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(String::from("invalid CSV: unterminated quoted field")),
+                }
+            },
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                // Empty lines are ignored.
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn escape(value: &str) -> String {
+    match value.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => String::from(value),
+    }
+}
+
+/// Write items as CSV, including all columns of the given format.
+pub fn write(items: &[ImportedItem], format: &CsvFormat) -> String {
+    let is = |column: &str, name: &Option<String>| name.as_deref().is_some_and(|name| column.eq_ignore_ascii_case(name));
+    let mut csv = format.columns.iter().map(|c| escape(c)).collect::<Vec<String>>().join(",");
+    csv.push('\n');
+    for item in items {
+        let (folder, title) = match format.folder {
+            Some(_) => {
+                let (groups, title) = import::split_name(&item.name);
+                (groups.join("/"), title)
+            }
+            None => (String::new(), item.name.as_str()),
+        };
+        let otp = item.otp.as_ref().map(Otp::to_uri).unwrap_or_default();
+        let row: Vec<String> = format
+            .columns
+            .iter()
+            .map(|c| match c {
+                c if c.eq_ignore_ascii_case(&format.password) => escape(&item.value),
+                c if is(c, &format.name) || (format.name.is_none() && is(c, &format.url)) => escape(title),
+                c if is(c, &format.folder) => escape(&folder),
+                c if is(c, &format.otp) => escape(&otp),
+                _ => String::new(),
+            })
+            .collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::base32;
+    use crate::import::UnmappedField;
+
+    fn item(name: &str, value: &str, otp: Option<Otp>) -> ImportedItem {
+        ImportedItem {
+            name: String::from(name),
+            value: String::from(value),
+            otp,
+            ..ImportedItem::default()
+        }
+    }
+
+    fn unmapped(item: &str, field: &str) -> UnmappedField {
+        UnmappedField {
+            item: String::from(item),
+            field: String::from(field),
+        }
+    }
+
+    #[test]
+    fn parse_csv_rows() {
+        let csv = "\u{feff}a,b,c\r\n\"quoted, \"\"field\"\"\",,\"multi\nline\"\n\n1,2,3";
+        let rows = parse_rows(csv.strip_prefix('\u{feff}').unwrap()).unwrap();
+        assert_eq!(rows, vec![vec!["a", "b", "c"], vec!["quoted, \"field\"", "", "multi\nline"], vec!["1", "2", "3"]]);
+        assert_eq!(parse_rows("").unwrap(), Vec::<Vec<String>>::new());
+        assert_eq!(parse_rows("a,\"b"), Err(String::from("invalid CSV: unterminated quoted field")));
+    }
+
+    #[test]
+    fn parse_failure() {
+        assert_eq!(parse("", &CsvFormat::chrome()), Err(String::from("missing CSV header")));
+        assert_eq!(parse("name,url\n", &CsvFormat::chrome()), Err(String::from("missing CSV column: password")));
+        assert_eq!(
+            parse("name,password\nGitHub,p4ss,extra\n", &CsvFormat::chrome()),
+            Err(String::from("invalid CSV row 2: expected 2 columns, found 3"))
+        );
+    }
+
+    #[test]
+    fn import_chrome() {
+        let csv = "name,url,username,password,note\n\
+                   github.com,https://github.com/login,alice,kT9#vLq2$wXz7!mP,\n\
+                   ,https://accounts.example.com/signin?next=home,bob,\"p4ss, \"\"quoted\"\"\",\"Security answer:\nCerberus\"\n";
+        let import = parse(csv, &CsvFormat::chrome()).unwrap();
+        assert_eq!(
+            import.items,
+            vec![
+                item("github.com", "kT9#vLq2$wXz7!mP", None),
+                item("accounts.example.com", "p4ss, \"quoted\"", None),
+                item("accounts.example.com / Notes", "Security answer:\nCerberus", None),
+            ]
+        );
+        assert_eq!(
+            import.unmapped,
+            vec![
+                unmapped("github.com", "url"),
+                unmapped("github.com", "username"),
+                unmapped("accounts.example.com", "url"),
+                unmapped("accounts.example.com", "username"),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_firefox() {
+        let csv = "\"url\",\"username\",\"password\",\"httpRealm\",\"formActionOrigin\",\"guid\",\"timeCreated\",\"timeLastUsed\",\"timePasswordChanged\"\r\n\
+                   \"https://github.com\",\"alice\",\"kT9#vLq2$wXz7!mP\",,\"https://github.com\",\"{0b5a4e48-b5e2-4a5c-9e3c-4d4f2b0f8f52}\",\"1700000000000\",\"1700000000000\",\"1700000000000\"\r\n";
+        let import = parse(csv, &CsvFormat::firefox()).unwrap();
+        assert_eq!(import.items, vec![item("github.com", "kT9#vLq2$wXz7!mP", None)]);
+        let fields: Vec<&str> = import.unmapped.iter().map(|u| u.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["url", "username", "formActionOrigin", "guid", "timeCreated", "timeLastUsed", "timePasswordChanged"]
+        );
+    }
+
+    #[test]
+    fn import_lastpass() {
+        let csv = "url,username,password,totp,extra,name,grouping,fav\n\
+                   https://github.com/login,alice,kT9#vLq2$wXz7!mP,JBSW Y3DP EHPK 3PXP,,GitHub,Internet,1\n\
+                   http://sn,,,,Recovery codes: 1f2e-3d4c,Recovery,,0\n\
+                   https://example.com,bob,p4ss,invalid!,,Example,,0\n";
+        let import = parse(csv, &CsvFormat::lastpass()).unwrap();
+        let mut otp = Otp::totp("alice", &base32::decode("JBSWY3DPEHPK3PXP").unwrap());
+        otp.issuer = Some(String::from("GitHub"));
+        assert_eq!(
+            import.items,
+            vec![
+                item("Internet/GitHub", "kT9#vLq2$wXz7!mP", Some(otp)),
+                item("Recovery / Notes", "Recovery codes: 1f2e-3d4c", None),
+                item("Example", "p4ss", None),
+            ]
+        );
+        assert_eq!(
+            import.unmapped,
+            vec![
+                unmapped("Internet/GitHub", "url"),
+                unmapped("Internet/GitHub", "username"),
+                unmapped("Internet/GitHub", "fav"),
+                unmapped("Recovery", "url"),
+                unmapped("Recovery", "fav"),
+                unmapped("Example", "url"),
+                unmapped("Example", "username"),
+                unmapped("Example", "totp"),
+                unmapped("Example", "fav"),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_one_password() {
+        let csv = "Title,Url,Username,Password,OTPAuth,Favorite,Archived,Tags,Notes\n\
+                   GitHub,https://github.com,alice,kT9#vLq2$wXz7!mP,otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub,false,false,,\n";
+        let import = parse(csv, &CsvFormat::one_password()).unwrap();
+        let otp = Otp::from_uri("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        assert_eq!(import.items, vec![item("GitHub", "kT9#vLq2$wXz7!mP", Some(otp))]);
+        let fields: Vec<&str> = import.unmapped.iter().map(|u| u.field.as_str()).collect();
+        assert_eq!(fields, vec!["Url", "Username", "Favorite", "Archived"]);
+    }
+
+    #[test]
+    fn write_parse() {
+        let otp = Otp::from_uri("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        let items = vec![
+            item("GitHub", "kT9#vLq2$wXz7!mP", Some(otp.clone())),
+            item("Internet/Email", "p4ss, \"quoted\"\nmultiline", None),
+        ];
+        let csv = write(&items, &CsvFormat::lastpass());
+        assert_eq!(
+            csv,
+            format!(
+                "url,username,password,totp,extra,name,grouping,fav\n\
+                 ,,kT9#vLq2$wXz7!mP,{},,GitHub,,\n\
+                 ,,\"p4ss, \"\"quoted\"\"\nmultiline\",,,Email,Internet,\n",
+                otp.to_uri()
+            )
+        );
+        for format in [CsvFormat::chrome(), CsvFormat::firefox(), CsvFormat::lastpass(), CsvFormat::one_password()] {
+            let import = parse(&write(&items, &format), &format).unwrap();
+            // Firefox doesn't have a name column: names are exported as URLs, which are reported.
+            assert!(import.unmapped.iter().all(|u| u.field == "url" && format.name.is_none()));
+            // Items keep their folder, but formats without one-time passwords lose them.
+            assert_eq!(import.items[1], items[1]);
+            assert_eq!(import.items[0].otp.is_some(), format.otp.is_some());
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+
+use crate::base32;
+use crate::otp::Otp;
+use crate::vault::{Attachment, ItemKind};
+
+pub mod bitwarden;
+pub mod csv;
+pub mod one_password;
+pub mod pass;
+
+/// Item read from another password manager's export, which can be added to a vault.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportedItem {
+    pub name: String,
+    pub value: String,
+    pub otp: Option<Otp>,
+    pub kind: ItemKind,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Field that exists in an export, but cannot be represented in vault items and is thus not imported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnmappedField {
+    /// Name of the item containing the field, as displayed by the other password manager.
+    pub item: String,
+    pub field: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Import {
+    pub items: Vec<ImportedItem>,
+    /// Fields that the user may need to copy manually.
+    pub unmapped: Vec<UnmappedField>,
+}
+
+impl Import {
+    pub(crate) fn add_item(&mut self, name: &str, value: &str, otp: Option<Otp>) {
+        self.items.push(ImportedItem {
+            name: String::from(name),
+            value: String::from(value),
+            otp,
+            ..ImportedItem::default()
+        });
+    }
+
+    pub(crate) fn add_unmapped(&mut self, item: &str, field: &str) {
+        self.unmapped.push(UnmappedField {
+            item: String::from(item),
+            field: String::from(field),
+        });
+    }
+}
+
+// JSON values that don't contain any data, and thus don't need to be reported.
+pub(crate) fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.iter().all(is_empty),
+        Value::Object(o) => o.values().all(is_empty),
+        _ => false,
+    }
+}
+
+// Password managers accept otpauth URIs, but also bare secrets (e.g. "JBSW Y3DP EHPK 3PXP") that use default parameters.
+pub(crate) fn parse_totp(totp: &str, account: &str, issuer: &str) -> Option<Otp> {
+    if totp.starts_with("otpauth://") {
+        return Otp::from_uri(totp);
+    }
+    let secret: String = totp.chars().filter(|c| !c.is_whitespace()).collect();
+    let secret = base32::decode(&secret.to_uppercase()).ok().filter(|s| !s.is_empty())?;
+    let mut otp = Otp::totp(account, &secret);
+    otp.issuer = Some(String::from(issuer));
+    Some(otp)
+}
+
+// Slashes separate groups, unless they're surrounded by spaces (e.g. "Internet/Email / Notes").
+pub(crate) fn split_name(name: &str) -> (Vec<&str>, &str) {
+    let (path, suffix) = match name.find(" / ") {
+        Some(i) => name.split_at(i),
+        None => (name, ""),
+    };
+    let mut groups: Vec<&str> = path.split('/').collect();
+    let entry = groups.pop().unwrap_or_default();
+    (groups, &name[name.len() - suffix.len() - entry.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_item_names() {
+        assert_eq!(split_name("GitHub"), (vec![], "GitHub"));
+        assert_eq!(split_name("Internet/Email"), (vec!["Internet"], "Email"));
+        assert_eq!(split_name("Internet/Shopping/Shop / Notes"), (vec!["Internet", "Shopping"], "Shop / Notes"));
+        assert_eq!(split_name("a / b/c"), (vec![], "a / b/c"));
+    }
+}
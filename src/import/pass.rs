@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::Path;
+
+use crate::import::{self, Import};
+use crate::otp::Otp;
+
+// Fields that identify the account, rather than secrets.
+const ACCOUNT_FIELDS: [&str; 7] = ["login", "user", "username", "email", "url", "website", "site"];
+
+/// Parse a password-store directory whose entries have been decrypted (e.g. with `gpg --decrypt`). Entries are named
+/// after their path (e.g. "Internet/email"), and hidden files (.gpg-id, .git, etc) are ignored. Symbolic links to
+/// directories are not followed, since they may create cycles.
+/// password-store has no formal specification, so we follow the conventions described at
+/// https://www.passwordstore.org/#organization.
+pub fn parse_dir(path: &Path) -> Result<Import, String> {
+    let mut import = Import::default();
+    parse_dir_into(&mut import, path, "")?;
+    Ok(import)
+}
+
+fn parse_dir_into(import: &mut Import, path: &Path, prefix: &str) -> Result<(), String> {
+    let mut entries = fs::read_dir(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_str().ok_or_else(|| format!("invalid file name in {}", path.display()))?;
+        if file_name.starts_with('.') {
+            continue;
+        }
+        if file_name.ends_with(".gpg") {
+            return Err(format!("{}{} is encrypted: entries must be decrypted before being imported", prefix, file_name));
+        }
+        let name = format!("{}{}", prefix, file_name);
+        // Unlike `Path::is_dir`, the file type of an entry doesn't follow symbolic links.
+        let file_type = entry.file_type().map_err(|e| format!("cannot read {}: {}", name, e))?;
+        if file_type.is_dir() {
+            parse_dir_into(import, &entry.path(), &format!("{}/", name))?;
+        } else if file_type.is_symlink() && entry.path().is_dir() {
+            continue;
+        } else {
+            let content = fs::read_to_string(entry.path()).map_err(|e| format!("cannot read {}: {}", name, e))?;
+            add_entry(import, &name, &content);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a single entry, as displayed by `pass show <name>`.
+/// The first line is the password. The following lines may contain one-time passwords (`otpauth://` URIs, as stored by
+/// pass-otp) and `key: value` fields: fields identifying the account (login, url, etc) are reported, and other fields are
+/// mapped to items named "<entry> / <key>". Remaining lines are mapped to an item named "<entry> / Notes".
+pub fn parse_entry(name: &str, content: &str) -> Import {
+    let mut import = Import::default();
+    add_entry(&mut import, name, content);
+    import
+}
+
+fn add_entry(import: &mut Import, name: &str, content: &str) {
+    let mut lines = content.lines();
+    let password = lines.next().unwrap_or("");
+    let title = name.rsplit('/').next().unwrap_or(name);
+    let mut account = title;
+    let mut otp: Option<Otp> = None;
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    let mut notes: Vec<&str> = Vec::new();
+    for line in lines {
+        if line.starts_with("otpauth://") && otp.is_none() {
+            otp = Otp::from_uri(line);
+            if otp.is_some() {
+                continue;
+            }
+        }
+        match line.split_once(": ").filter(|(key, _)| !key.trim().is_empty() && !key.contains("://")) {
+            Some((key, value)) if ["otp", "totp"].contains(&key.trim().to_lowercase().as_str()) && otp.is_none() => {
+                otp = import::parse_totp(value.trim(), account, title);
+                if otp.is_none() {
+                    import.add_unmapped(name, key.trim());
+                }
+            }
+            Some((key, value)) => {
+                let key = key.trim();
+                if ["login", "user", "username", "email"].contains(&key.to_lowercase().as_str()) {
+                    account = value.trim();
+                }
+                fields.push((key, value.trim()));
+            }
+            None => notes.push(line),
+        }
+    }
+    if !password.is_empty() || otp.is_some() {
+        import.add_item(name, password, otp);
+    }
+    for (key, value) in fields.into_iter().filter(|(_, v)| !v.is_empty()) {
+        match ACCOUNT_FIELDS.contains(&key.to_lowercase().as_str()) {
+            true => import.add_unmapped(name, key),
+            false => import.add_item(&format!("{} / {}", name, key), value, None),
+        }
+    }
+    let notes = notes.join("\n");
+    if !notes.trim().is_empty() {
+        import.add_item(&format!("{} / Notes", name), notes.trim(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::base32;
+    use crate::import::{ImportedItem, UnmappedField};
+
+    fn item(name: &str, value: &str, otp: Option<Otp>) -> ImportedItem {
+        ImportedItem {
+            name: String::from(name),
+            value: String::from(value),
+            otp,
+            ..ImportedItem::default()
+        }
+    }
+
+    fn unmapped(item: &str, field: &str) -> UnmappedField {
+        UnmappedField {
+            item: String::from(item),
+            field: String::from(field),
+        }
+    }
+
+    #[test]
+    fn parse_pass_entry() {
+        let import = parse_entry("Internet/email", "Zq8&Rt5^Hn2*Wd6%\nuser: alice\ntotp: JBSW Y3DP EHPK 3PXP\n\nRecovery codes:\n1f2e-3d4c\n");
+        let mut otp = Otp::totp("alice", &base32::decode("JBSWY3DPEHPK3PXP").unwrap());
+        otp.issuer = Some(String::from("email"));
+        assert_eq!(
+            import.items,
+            vec![
+                item("Internet/email", "Zq8&Rt5^Hn2*Wd6%", Some(otp)),
+                item("Internet/email / Notes", "Recovery codes:\n1f2e-3d4c", None),
+            ]
+        );
+        assert_eq!(import.unmapped, vec![unmapped("Internet/email", "user")]);
+        // Entries may only contain notes, and URLs are not mistaken for fields.
+        let import = parse_entry("notes", "\nhttps://example.com\nempty: \n");
+        assert_eq!(import.items, vec![item("notes / Notes", "https://example.com", None)]);
+        assert!(import.unmapped.is_empty());
+        // Invalid one-time passwords are reported.
+        let import = parse_entry("invalid", "p4ss\notpauth://totp/invalid\ntotp: not base32!\n");
+        assert_eq!(import.items, vec![item("invalid", "p4ss", None), item("invalid / Notes", "otpauth://totp/invalid", None)]);
+        assert_eq!(import.unmapped, vec![unmapped("invalid", "totp")]);
+        assert_eq!(parse_entry("empty", ""), Import::default());
+    }
+
+    #[test]
+    fn parse_pass_dir() {
+        let import = parse_dir(Path::new("src/import/fixtures/pass")).unwrap();
+        let github_otp = Otp::from_uri("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        let mut email_otp = Otp::totp("alice", &base32::decode("JBSWY3DPEHPK3PXP").unwrap());
+        email_otp.issuer = Some(String::from("email"));
+        assert_eq!(
+            import.items,
+            vec![
+                item("Internet/Shopping/shop.example.com", "ünïcødé ✓", None),
+                item("Internet/email", "Zq8&Rt5^Hn2*Wd6%", Some(email_otp)),
+                item("Internet/email / Notes", "Recovery codes:\n1f2e-3d4c 5b6a-7980", None),
+                item("github.com", "kT9#vLq2$wXz7!mP", Some(github_otp)),
+                item("github.com / security answer", "Cerberus", None),
+            ]
+        );
+        assert_eq!(
+            import.unmapped,
+            vec![unmapped("Internet/email", "user"), unmapped("github.com", "login"), unmapped("github.com", "url")]
+        );
+        assert!(parse_dir(Path::new("src/import/fixtures/missing"))
+            .unwrap_err()
+            .starts_with("cannot read src/import/fixtures/missing"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_pass_dir_with_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let path = std::env::temp_dir().join(format!("cybele-pass-{}", std::process::id()));
+        fs::create_dir_all(path.join("Internet")).unwrap();
+        fs::write(path.join("Internet/email"), "Zq8&Rt5^Hn2*Wd6%\n").unwrap();
+        // Links to files are read, but links to directories (here creating a cycle) are skipped.
+        symlink(path.join("Internet/email"), path.join("mail")).unwrap();
+        symlink(&path, path.join("Internet/loop")).unwrap();
+        symlink("..", path.join("Internet/parent")).unwrap();
+        let import = parse_dir(&path).unwrap();
+        assert_eq!(import.items, vec![item("Internet/email", "Zq8&Rt5^Hn2*Wd6%", None), item("mail", "Zq8&Rt5^Hn2*Wd6%", None)]);
+        fs::remove_dir_all(path).unwrap();
+    }
+}
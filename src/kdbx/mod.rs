@@ -0,0 +1,823 @@
+use std::io::{Read, Write};
+
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes256;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::crypto::{aes_cbc, hmac};
+use crate::hash::sha256::{self, Sha256};
+use crate::hash::sha512;
+use crate::import::{self, Import};
+use crate::otp::{Otp, OtpKind};
+use crate::vault::{Timestamps, Vault};
+use crate::{keys, Kdf};
+
+pub use self::variant::Variant;
+
+mod variant;
+mod xml;
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const MAJOR_VERSION: u16 = 4;
+
+// Outer header fields.
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION_FLAGS: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+// Inner header fields.
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+const BINARY: u8 = 3;
+const CHACHA20_STREAM: u32 = 3;
+
+// Key derivation limits, to avoid spending an unreasonable amount of resources on crafted databases. They are well above
+// the defaults of KeePassXC, which targets about one second on the computer creating the database.
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+const MAX_ARGON2_MEMORY: u64 = 1 << 30;
+const MAX_ARGON2_ITERATIONS: u64 = 256;
+const MAX_ARGON2_PARALLELISM: u32 = 64;
+
+const AES256_UUID: [u8; 16] = [0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff];
+const CHACHA20_UUID: [u8; 16] = [0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5, 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a];
+const ARGON2D_UUID: [u8; 16] = [0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c];
+const ARGON2ID_UUID: [u8; 16] = [0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73, 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6];
+const AES_KDF_UUID: [u8; 16] = [0xc9, 0xd9, 0xf3, 0x9a, 0x62, 0x8a, 0x44, 0x60, 0xbf, 0x74, 0x0d, 0x08, 0xc1, 0x8a, 0x4f, 0xea];
+
+const BLOCK_SIZE: usize = 1 << 20;
+
+/// Cipher used to encrypt the content of KeePass databases.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    Aes256,
+    ChaCha20,
+}
+
+/// KeePass database (KDBX 4), restricted to the data that can be represented in vaults.
+/// Specification can be found here: https://keepass.info/help/kb/kdbx_4.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Database {
+    pub name: String,
+    pub root: Group,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Group {
+    pub uuid: [u8; 16],
+    pub name: String,
+    pub groups: Vec<Group>,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub uuid: [u8; 16],
+    /// Standard fields (Title, UserName, Password, URL and Notes) and custom fields.
+    pub fields: Vec<Field>,
+    /// Names of the files attached to the entry: their content isn't read.
+    pub attachments: Vec<String>,
+    pub timestamps: Option<Timestamps>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub key: String,
+    pub value: String,
+    /// Protected fields are encrypted with the inner stream, and usually contain secrets.
+    pub protected: bool,
+}
+
+impl Entry {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|f| f.key == key).map(|f| f.value.as_str())
+    }
+}
+
+/// Stream cipher used to encrypt protected values, whose keystream is shared by all values of a database.
+pub(crate) struct InnerStream(ChaCha20);
+
+impl InnerStream {
+    fn new(key: &[u8]) -> InnerStream {
+        let hash = sha512::hash(key);
+        InnerStream(ChaCha20::new(hash[..32].into(), hash[32..44].into()))
+    }
+
+    pub(crate) fn apply(&mut self, data: &mut [u8]) {
+        self.0.apply_keystream(data)
+    }
+}
+
+pub(crate) fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+    if data.len() < n {
+        return Err(String::from("unexpected end of KeePass data"));
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into().unwrap()))
+}
+
+// Header fields are encoded as [u8: id][u32: size][size*u8: data].
+fn take_field<'a>(data: &mut &'a [u8]) -> Result<(u8, &'a [u8]), String> {
+    let id = take(data, 1)?[0];
+    let size = take_u32(data)? as usize;
+    Ok((id, take(data, size)?))
+}
+
+fn write_field(w: &mut Vec<u8>, id: u8, data: &[u8]) {
+    w.push(id);
+    w.extend((data.len() as u32).to_le_bytes());
+    w.extend(data);
+}
+
+// Each block (and the header) is authenticated with its own key.
+fn block_key(hmac_base: &[u8; 64], index: u64) -> [u8; 64] {
+    sha512::hash(&[&index.to_le_bytes()[..], hmac_base].concat())
+}
+
+fn block_mac(hmac_base: &[u8; 64], index: u64, block: &[u8]) -> [u8; 32] {
+    let message = [&index.to_le_bytes()[..], &(block.len() as i32).to_le_bytes(), block].concat();
+    hmac::authenticate::<Sha256>(&block_key(hmac_base, index), &message)
+}
+
+fn transform_key(password: &str, parameters: &[(String, Variant)]) -> Result<[u8; 32], String> {
+    // Key files are not supported: the composite key only contains the password.
+    let composite_key = sha256::hash(&sha256::hash(password.as_bytes()));
+    let parameter = |name: &str| parameters.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+    let missing = |name: &str| format!("missing KDF parameter: {}", name);
+    let uuid = match parameter("$UUID") {
+        Some(Variant::Bytes(uuid)) => uuid.as_slice(),
+        _ => return Err(missing("$UUID")),
+    };
+    let salt = match parameter("S") {
+        Some(Variant::Bytes(salt)) => salt,
+        _ => return Err(missing("S")),
+    };
+    if uuid == AES_KDF_UUID {
+        let rounds = match parameter("R") {
+            Some(Variant::UInt64(rounds)) => *rounds,
+            _ => return Err(missing("R")),
+        };
+        if !(1..=MAX_AES_KDF_ROUNDS).contains(&rounds) {
+            return Err(format!("invalid AES-KDF rounds: {}", rounds));
+        }
+        let cipher = Aes256::new_from_slice(salt).map_err(|_| "invalid AES-KDF seed")?;
+        let mut key = composite_key;
+        for block in key.chunks_mut(16) {
+            (0..rounds).for_each(|_| cipher.encrypt_block(block.into()));
+        }
+        return Ok(sha256::hash(&key));
+    }
+    let (iterations, memory, parallelism, version) = match (parameter("I"), parameter("M"), parameter("P"), parameter("V")) {
+        (Some(Variant::UInt64(i)), Some(Variant::UInt64(m)), Some(Variant::UInt32(p)), Some(Variant::UInt32(v))) => (*i, *m, *p, *v),
+        _ => return Err(missing("I, M, P or V")),
+    };
+    if version != 0x13 {
+        return Err(format!("unsupported Argon2 version: {:#04x}", version));
+    }
+    if !(1..=MAX_ARGON2_ITERATIONS).contains(&iterations) {
+        return Err(format!("invalid Argon2 iterations: {}", iterations));
+    }
+    if !(1..=MAX_ARGON2_PARALLELISM).contains(&parallelism) {
+        return Err(format!("invalid Argon2 parallelism: {}", parallelism));
+    }
+    // Argon2 needs at least 8 KiB per lane.
+    if !(8 * 1024 * parallelism as u64..=MAX_ARGON2_MEMORY).contains(&memory) || memory % 1024 != 0 {
+        return Err(format!("invalid Argon2 memory: {} bytes", memory));
+    }
+    let (iterations, memory_kib) = (iterations as u32, (memory / 1024) as u32);
+    let kdf = match uuid {
+        uuid if uuid == ARGON2D_UUID => Kdf::Argon2d {
+            memory_kib,
+            iterations,
+            parallelism,
+        },
+        uuid if uuid == ARGON2ID_UUID => Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        },
+        _ => return Err(String::from("unsupported KeePass key derivation function")),
+    };
+    keys::derive_master_key_from_bytes(kdf, &composite_key, salt).ok_or_else(|| String::from("cannot derive KeePass key"))
+}
+
+impl Database {
+    /// Decrypt a KDBX 4 database protected by a password (key files are not supported).
+    pub fn read(data: &[u8], password: &str) -> Result<Database, String> {
+        let mut r = data;
+        // [u32: signature1][u32: signature2][u16: minor_version][u16: major_version]
+        if take_u32(&mut r)? != SIGNATURE_1 || take_u32(&mut r)? != SIGNATURE_2 {
+            return Err(String::from("not a KeePass database"));
+        }
+        take(&mut r, 2)?;
+        let major_version = u16::from_le_bytes(take(&mut r, 2)?.try_into().unwrap());
+        if major_version != MAJOR_VERSION {
+            return Err(format!("unsupported KeePass database version: {}", major_version));
+        }
+        let mut cipher = None;
+        let mut compressed = false;
+        let mut seed: &[u8] = &[];
+        let mut iv: &[u8] = &[];
+        let mut kdf_parameters = Vec::new();
+        loop {
+            match take_field(&mut r)? {
+                (END_OF_HEADER, _) => break,
+                (CIPHER_ID, uuid) if uuid == AES256_UUID => cipher = Some(Cipher::Aes256),
+                (CIPHER_ID, uuid) if uuid == CHACHA20_UUID => cipher = Some(Cipher::ChaCha20),
+                (CIPHER_ID, _) => return Err(String::from("unsupported KeePass cipher")),
+                (COMPRESSION_FLAGS, flags) => match flags {
+                    [0, 0, 0, 0] => compressed = false,
+                    [1, 0, 0, 0] => compressed = true,
+                    _ => return Err(String::from("unsupported KeePass compression")),
+                },
+                (MASTER_SEED, data) => seed = data,
+                (ENCRYPTION_IV, data) => iv = data,
+                (KDF_PARAMETERS, data) => kdf_parameters = variant::parse(data)?,
+                // Public custom data and unknown fields are ignored.
+                _ => (),
+            }
+        }
+        let header = &data[..data.len() - r.len()];
+        // [32*u8: sha256(header)][32*u8: hmac(header)]
+        if take(&mut r, 32)? != sha256::hash(header) {
+            return Err(String::from("corrupted KeePass header"));
+        }
+        let header_mac = take(&mut r, 32)?;
+        let cipher = cipher.ok_or("missing KeePass cipher")?;
+        if seed.len() != 32 {
+            return Err(String::from("invalid KeePass master seed"));
+        }
+        let transformed_key = transform_key(password, &kdf_parameters)?;
+        let hmac_base = sha512::hash(&[seed, &transformed_key, &[1]].concat());
+        if !hmac::verify::<Sha256>(&block_key(&hmac_base, u64::MAX), header, header_mac) {
+            return Err(String::from("invalid password for KeePass database"));
+        }
+        // [32*u8: hmac][i32: size][size*u8: data], until an empty block.
+        let mut encrypted = Vec::with_capacity(r.len());
+        for index in 0.. {
+            let mac = take(&mut r, 32)?;
+            let size = i32::from_le_bytes(take(&mut r, 4)?.try_into().unwrap());
+            let block = take(&mut r, usize::try_from(size).map_err(|_| "invalid KeePass block size")?)?;
+            if !hmac::verify::<Sha256>(&block_key(&hmac_base, index), &[&index.to_le_bytes()[..], &size.to_le_bytes(), block].concat(), mac) {
+                return Err(String::from("corrupted KeePass block"));
+            }
+            if block.is_empty() {
+                break;
+            }
+            encrypted.extend(block);
+        }
+        let encryption_key = sha256::hash(&[seed, &transformed_key].concat());
+        let mut decrypted = match cipher {
+            Cipher::Aes256 => {
+                let iv: &[u8; 16] = iv.try_into().map_err(|_| "invalid KeePass IV")?;
+                aes_cbc::decrypt(&encryption_key, iv, &encrypted).ok_or("cannot decrypt KeePass database")?
+            }
+            Cipher::ChaCha20 => {
+                let iv: &[u8; 12] = iv.try_into().map_err(|_| "invalid KeePass IV")?;
+                ChaCha20::new(&encryption_key.into(), iv.into()).apply_keystream(&mut encrypted);
+                encrypted
+            }
+        };
+        if compressed {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(decrypted.as_slice())
+                .read_to_end(&mut decompressed)
+                .map_err(|e| format!("cannot decompress KeePass database: {}", e))?;
+            decrypted = decompressed;
+        }
+        let mut r = decrypted.as_slice();
+        let mut stream = None;
+        let mut stream_id = None;
+        loop {
+            match take_field(&mut r)? {
+                (END_OF_HEADER, _) => break,
+                (INNER_STREAM_ID, id) => stream_id = Some(u32::from_le_bytes(id.try_into().map_err(|_| "invalid KeePass inner stream")?)),
+                (INNER_STREAM_KEY, key) => stream = Some(InnerStream::new(key)),
+                // Attachments content isn't imported.
+                (BINARY, _) => (),
+                _ => (),
+            }
+        }
+        if stream.is_some() && stream_id != Some(CHACHA20_STREAM) {
+            return Err(String::from("unsupported KeePass inner stream"));
+        }
+        let xml = std::str::from_utf8(r).map_err(|_| "invalid KeePass XML encoding")?;
+        xml::parse(xml, stream.as_mut())
+    }
+
+    /// Encrypt the database, using Argon2 to derive the encryption key from the password.
+    pub fn write(&self, password: &str, cipher: Cipher, kdf: Kdf) -> Result<Vec<u8>, String> {
+        let mut csprng = OsRng {};
+        let mut seed = [0u8; 32];
+        csprng.fill_bytes(&mut seed);
+        let mut salt = [0u8; 32];
+        csprng.fill_bytes(&mut salt);
+        let mut inner_key = [0u8; 64];
+        csprng.fill_bytes(&mut inner_key);
+        let mut iv = match cipher {
+            Cipher::Aes256 => vec![0u8; 16],
+            Cipher::ChaCha20 => vec![0u8; 12],
+        };
+        csprng.fill_bytes(&mut iv);
+        let (uuid, memory_kib, iterations, parallelism) = match kdf {
+            Kdf::Argon2d {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => (ARGON2D_UUID, memory_kib, iterations, parallelism),
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => (ARGON2ID_UUID, memory_kib, iterations, parallelism),
+            Kdf::Pbkdf2HmacSha256 { .. } => return Err(String::from("KeePass databases cannot use PBKDF2")),
+        };
+        let kdf_parameters = vec![
+            (String::from("$UUID"), Variant::Bytes(uuid.to_vec())),
+            (String::from("S"), Variant::Bytes(salt.to_vec())),
+            (String::from("P"), Variant::UInt32(parallelism)),
+            (String::from("M"), Variant::UInt64(memory_kib as u64 * 1024)),
+            (String::from("I"), Variant::UInt64(iterations as u64)),
+            (String::from("V"), Variant::UInt32(0x13)),
+        ];
+        let transformed_key = transform_key(password, &kdf_parameters)?;
+        let mut header = Vec::new();
+        header.extend(SIGNATURE_1.to_le_bytes());
+        header.extend(SIGNATURE_2.to_le_bytes());
+        header.extend(1u16.to_le_bytes());
+        header.extend(MAJOR_VERSION.to_le_bytes());
+        let cipher_uuid = match cipher {
+            Cipher::Aes256 => AES256_UUID,
+            Cipher::ChaCha20 => CHACHA20_UUID,
+        };
+        write_field(&mut header, CIPHER_ID, &cipher_uuid);
+        write_field(&mut header, COMPRESSION_FLAGS, &1u32.to_le_bytes());
+        write_field(&mut header, MASTER_SEED, &seed);
+        write_field(&mut header, ENCRYPTION_IV, &iv);
+        write_field(&mut header, KDF_PARAMETERS, &variant::serialize(&kdf_parameters));
+        write_field(&mut header, END_OF_HEADER, b"\r\n\r\n");
+        // We build the inner content, which is then compressed and encrypted.
+        let mut inner = Vec::new();
+        write_field(&mut inner, INNER_STREAM_ID, &CHACHA20_STREAM.to_le_bytes());
+        write_field(&mut inner, INNER_STREAM_KEY, &inner_key);
+        write_field(&mut inner, END_OF_HEADER, &[]);
+        inner.extend(xml::write(self, Some(&mut InnerStream::new(&inner_key))).as_bytes());
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&inner).map_err(|e| format!("cannot compress KeePass database: {}", e))?;
+        let compressed = encoder.finish().map_err(|e| format!("cannot compress KeePass database: {}", e))?;
+        let encryption_key = sha256::hash(&[&seed[..], &transformed_key].concat());
+        let encrypted = match cipher {
+            Cipher::Aes256 => aes_cbc::encrypt(&encryption_key, iv.as_slice().try_into().unwrap(), &compressed),
+            Cipher::ChaCha20 => {
+                let mut encrypted = compressed;
+                ChaCha20::new(&encryption_key.into(), iv.as_slice().into()).apply_keystream(&mut encrypted);
+                encrypted
+            }
+        };
+        // We authenticate the header and each block of encrypted data.
+        let hmac_base = sha512::hash(&[&seed[..], &transformed_key, &[1]].concat());
+        let mut w = header.clone();
+        w.extend(sha256::hash(&header));
+        w.extend(hmac::authenticate::<Sha256>(&block_key(&hmac_base, u64::MAX), &header));
+        for (index, block) in encrypted.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..])).enumerate() {
+            w.extend(block_mac(&hmac_base, index as u64, block));
+            w.extend((block.len() as i32).to_le_bytes());
+            w.extend(block);
+        }
+        Ok(w)
+    }
+
+    /// Parse an unencrypted XML export of KeePass 2.
+    pub fn from_xml(xml: &str) -> Result<Database, String> {
+        xml::parse(xml, None)
+    }
+
+    /// Export the database as unencrypted KeePass 2 XML: protected values are written as plaintext.
+    pub fn to_xml(&self) -> String {
+        xml::write(self, None)
+    }
+
+    /// Create a database containing the vault items that can be decrypted with the given password.
+    /// Item names containing slashes (e.g. "Internet/Email") are mapped to groups.
+    pub fn from_vault(vault: &Vault, password: &str, name: &str) -> Database {
+        let mut root = Group {
+            uuid: random_uuid(),
+            name: String::from("Root"),
+            ..Group::default()
+        };
+        for item_name in vault.list() {
+            let Some(value) = vault.get(&item_name, password) else {
+                continue;
+            };
+            let (path, title) = import::split_name(&item_name);
+            let mut group = &mut root;
+            for group_name in path {
+                let index = match group.groups.iter().position(|g| g.name == group_name) {
+                    Some(index) => index,
+                    None => {
+                        group.groups.push(Group {
+                            uuid: random_uuid(),
+                            name: String::from(group_name),
+                            ..Group::default()
+                        });
+                        group.groups.len() - 1
+                    }
+                };
+                group = &mut group.groups[index];
+            }
+            let mut entry = Entry {
+                uuid: random_uuid(),
+                timestamps: vault.timestamps(&item_name),
+                ..Entry::default()
+            };
+            entry.fields.push(Field {
+                key: String::from("Title"),
+                value: String::from(title),
+                protected: false,
+            });
+            entry.fields.push(Field {
+                key: String::from("Password"),
+                value,
+                protected: true,
+            });
+            if let Some(otp) = vault.get_otp(&item_name, password) {
+                entry.fields.push(Field {
+                    key: String::from("otp"),
+                    value: otp.to_uri(),
+                    protected: true,
+                });
+            }
+            group.entries.push(entry);
+        }
+        Database { name: String::from(name), root }
+    }
+
+    /// Map entries to items named after their group (e.g. "Internet/Email"), ignoring the root group.
+    /// Passwords and one-time passwords are mapped to the entry's item. Notes and protected custom fields are mapped
+    /// to items named "<entry> / <field>". Other fields are reported.
+    pub fn to_import(&self) -> Import {
+        let mut import = Import::default();
+        import_group(&mut import, &self.root, "");
+        import
+    }
+}
+
+fn random_uuid() -> [u8; 16] {
+    let mut uuid = [0u8; 16];
+    OsRng {}.fill_bytes(&mut uuid);
+    uuid
+}
+
+fn import_group(import: &mut Import, group: &Group, prefix: &str) {
+    for entry in &group.entries {
+        let title = entry.get("Title").filter(|t| !t.is_empty()).unwrap_or("Untitled");
+        let name = format!("{}{}", prefix, title);
+        let account = entry.get("UserName").filter(|u| !u.is_empty()).unwrap_or(title);
+        let mut mapped = vec!["Title", "Password"];
+        // KeePassXC stores one-time passwords as otpauth URIs, and older plugins as a seed with settings.
+        let otp = match (entry.get("otp"), entry.get("TOTP Seed")) {
+            (Some(uri), _) => {
+                mapped.push("otp");
+                Otp::from_uri(uri)
+            }
+            (None, Some(seed)) => {
+                mapped.extend(["TOTP Seed", "TOTP Settings"]);
+                import::parse_totp(seed, account, title).and_then(|otp| apply_totp_settings(otp, entry.get("TOTP Settings")))
+            }
+            (None, None) => None,
+        };
+        if otp.is_none() && mapped.len() > 2 {
+            import.add_unmapped(&name, mapped[2]);
+        }
+        let password = entry.get("Password").unwrap_or("");
+        if !password.is_empty() || otp.is_some() {
+            import.add_item(&name, password, otp);
+        }
+        for field in entry.fields.iter().filter(|f| !mapped.contains(&f.key.as_str()) && !f.value.is_empty()) {
+            match field.protected || field.key == "Notes" {
+                true => import.add_item(&format!("{} / {}", name, field.key), &field.value, None),
+                false => import.add_unmapped(&name, &field.key),
+            }
+        }
+        for attachment in &entry.attachments {
+            import.add_unmapped(&name, &format!("attachments.{}", attachment));
+        }
+    }
+    for subgroup in &group.groups {
+        import_group(import, subgroup, &format!("{}{}/", prefix, subgroup.name));
+    }
+}
+
+// Settings are "<period>;<digits>", where digits may be "S" for Steam codes (which aren't supported).
+fn apply_totp_settings(mut otp: Otp, settings: Option<&str>) -> Option<Otp> {
+    if let Some(settings) = settings {
+        let (period, digits) = settings.split_once(';')?;
+        otp.kind = OtpKind::Totp {
+            period: period.parse().ok().filter(|p| *p > 0)?,
+        };
+        otp.digits = digits.parse().ok().filter(|d| (6..=8).contains(d))?;
+    }
+    Some(otp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::base32;
+    use crate::import::{ImportedItem, UnmappedField};
+    use crate::Version;
+
+    const PASSWORD: &str = "fixture p4ssword";
+
+    // Argon2 parameters that are fast enough for tests.
+    const TEST_KDF: Kdf = Kdf::Argon2d {
+        memory_kib: 1024,
+        iterations: 2,
+        parallelism: 2,
+    };
+
+    fn field(key: &str, value: &str, protected: bool) -> Field {
+        Field {
+            key: String::from(key),
+            value: String::from(value),
+            protected,
+        }
+    }
+
+    fn timestamps(created_at: u64, modified_at: u64) -> Option<Timestamps> {
+        Some(Timestamps { created_at, modified_at })
+    }
+
+    fn fixture_database() -> Database {
+        let github = Entry {
+            uuid: [2u8; 16],
+            fields: vec![
+                field("Notes", "Recovery codes: 1f2e-3d4c 5b6a-7980", false),
+                field("Password", "kT9#vLq2$wXz7!mP", true),
+                field("Title", "GitHub", false),
+                field("URL", "https://github.com/login", false),
+                field("UserName", "alice@example.com", false),
+                field("otp", "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&period=30&digits=6&issuer=GitHub", true),
+                field("Security answer", "Cerberus <&>", true),
+            ],
+            attachments: vec![],
+            timestamps: timestamps(1_700_000_000, 1_710_000_000),
+        };
+        let wifi = Entry {
+            uuid: [3u8; 16],
+            fields: vec![
+                field("Notes", "", false),
+                field("Password", "", true),
+                field("Title", "Wifi", false),
+                field("URL", "", false),
+                field("UserName", "", false),
+                field("Network key", "c0rr3ct-h0rs3 ", true),
+            ],
+            attachments: vec![String::from("router.txt")],
+            timestamps: timestamps(1_700_000_100, 1_700_000_100),
+        };
+        let email = Entry {
+            uuid: [5u8; 16],
+            fields: vec![
+                field("Notes", "", false),
+                field("Password", "Zq8&Rt5^Hn2*Wd6%", true),
+                field("Title", "Email", false),
+                field("URL", "", false),
+                field("UserName", "alice", false),
+                field("TOTP Seed", "JBSW Y3DP EHPK 3PXP", true),
+                field("TOTP Settings", "30;6", false),
+            ],
+            attachments: vec![],
+            timestamps: timestamps(1_700_000_200, 1_700_000_300),
+        };
+        let shop = Entry {
+            uuid: [7u8; 16],
+            fields: vec![field("Password", "ünïcødé ✓", true), field("Title", "Shop", false), field("UserName", "bob", false)],
+            attachments: vec![],
+            timestamps: timestamps(1_700_000_400, 1_700_000_400),
+        };
+        let shopping = Group {
+            uuid: [6u8; 16],
+            name: String::from("Shopping"),
+            groups: vec![],
+            entries: vec![shop],
+        };
+        let internet = Group {
+            uuid: [4u8; 16],
+            name: String::from("Internet"),
+            groups: vec![shopping],
+            entries: vec![email],
+        };
+        let empty = Group {
+            uuid: [8u8; 16],
+            name: String::from("Empty"),
+            ..Group::default()
+        };
+        Database {
+            name: String::from("Fixture & tests"),
+            root: Group {
+                uuid: [1u8; 16],
+                name: String::from("Root"),
+                groups: vec![internet, empty],
+                entries: vec![github, wifi],
+            },
+        }
+    }
+
+    // These fixtures were not saved by KeePassXC: they were written by an independent implementation of the KDBX 4
+    // format, so they don't prove that databases saved by KeePassXC can be read.
+    #[test]
+    fn read_fixtures() {
+        let fixtures: [&[u8]; 3] = [
+            include_bytes!("fixtures/kdbx4_argon2d_chacha20.kdbx"),
+            include_bytes!("fixtures/kdbx4_argon2id_aes.kdbx"),
+            include_bytes!("fixtures/kdbx4_aeskdf_aes_uncompressed.kdbx"),
+        ];
+        for fixture in fixtures {
+            assert_eq!(Database::read(fixture, PASSWORD).unwrap(), fixture_database());
+        }
+    }
+
+    #[test]
+    fn write_read_database() {
+        let mut database = fixture_database();
+        // Attachments are not written.
+        database.root.entries[1].attachments.clear();
+        for cipher in [Cipher::Aes256, Cipher::ChaCha20] {
+            let written = database.write(PASSWORD, cipher, TEST_KDF).unwrap();
+            assert_eq!(Database::read(&written, PASSWORD).unwrap(), database);
+            assert_eq!(Database::read(&written, "wrong password"), Err(String::from("invalid password for KeePass database")));
+        }
+        let kdf = Kdf::Argon2id {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let written = database.write(PASSWORD, Cipher::ChaCha20, kdf).unwrap();
+        assert_eq!(Database::read(&written, PASSWORD).unwrap(), database);
+        let kdf = Kdf::Pbkdf2HmacSha256 { iterations: 1000 };
+        assert_eq!(database.write(PASSWORD, Cipher::Aes256, kdf), Err(String::from("KeePass databases cannot use PBKDF2")));
+    }
+
+    #[test]
+    fn read_failure() {
+        let fixture = include_bytes!("fixtures/kdbx4_argon2d_chacha20.kdbx");
+        assert_eq!(Database::read(fixture, "wrong password"), Err(String::from("invalid password for KeePass database")));
+        assert_eq!(Database::read(&fixture[..10], PASSWORD), Err(String::from("unexpected end of KeePass data")));
+        assert_eq!(Database::read(&[0u8; 12], PASSWORD), Err(String::from("not a KeePass database")));
+        // KDBX 3.1 databases are not supported.
+        let mut kdbx3 = fixture.to_vec();
+        kdbx3[10] = 3;
+        assert_eq!(Database::read(&kdbx3, PASSWORD), Err(String::from("unsupported KeePass database version: 3")));
+        // The header hash starts after the end of header field.
+        let header_len = fixture.windows(9).position(|w| w == b"\x00\x04\x00\x00\x00\r\n\r\n").unwrap() + 9;
+        let mut corrupted = fixture.to_vec();
+        corrupted[header_len - 1] ^= 1;
+        assert_eq!(Database::read(&corrupted, PASSWORD), Err(String::from("corrupted KeePass header")));
+        // The first block starts after the header hash and HMAC, with its own HMAC and size.
+        let mut corrupted = fixture.to_vec();
+        corrupted[header_len + 64 + 36] ^= 1;
+        assert_eq!(Database::read(&corrupted, PASSWORD), Err(String::from("corrupted KeePass block")));
+        // Blocks cannot be truncated or removed.
+        assert_eq!(
+            Database::read(&fixture[..fixture.len() - 36], PASSWORD),
+            Err(String::from("unexpected end of KeePass data"))
+        );
+    }
+
+    #[test]
+    fn expensive_key_derivation() {
+        let parameters = |uuid: [u8; 16], values: &[(&str, Variant)]| {
+            let mut parameters = vec![(String::from("$UUID"), Variant::Bytes(uuid.to_vec())), (String::from("S"), Variant::Bytes(vec![0u8; 32]))];
+            parameters.extend(values.iter().map(|(n, v)| (String::from(*n), v.clone())));
+            parameters
+        };
+        let argon2 = |i: u64, m: u64, p: u32| {
+            parameters(
+                ARGON2ID_UUID,
+                &[
+                    ("I", Variant::UInt64(i)),
+                    ("M", Variant::UInt64(m)),
+                    ("P", Variant::UInt32(p)),
+                    ("V", Variant::UInt32(0x13)),
+                ],
+            )
+        };
+        let test_cases = vec![
+            (
+                parameters(AES_KDF_UUID, &[("R", Variant::UInt64(u64::MAX))]),
+                "invalid AES-KDF rounds: 18446744073709551615",
+            ),
+            (parameters(AES_KDF_UUID, &[("R", Variant::UInt64(0))]), "invalid AES-KDF rounds: 0"),
+            (argon2(1 << 32, 1 << 20, 1), "invalid Argon2 iterations: 4294967296"),
+            (argon2(0, 1 << 20, 1), "invalid Argon2 iterations: 0"),
+            (argon2(2, 1 << 40, 1), "invalid Argon2 memory: 1099511627776 bytes"),
+            (argon2(2, 4096, 1), "invalid Argon2 memory: 4096 bytes"),
+            (argon2(2, (1 << 20) + 1, 1), "invalid Argon2 memory: 1048577 bytes"),
+            (argon2(2, 1 << 20, 0), "invalid Argon2 parallelism: 0"),
+            (argon2(2, 1 << 20, u32::MAX), "invalid Argon2 parallelism: 4294967295"),
+        ];
+        for (parameters, error) in test_cases {
+            assert_eq!(transform_key(PASSWORD, &parameters), Err(String::from(error)));
+        }
+        assert!(transform_key(PASSWORD, &argon2(1, 1 << 20, 2)).is_ok());
+    }
+
+    #[test]
+    fn database_to_import() {
+        let import = fixture_database().to_import();
+        let github_otp = Otp::from_uri("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        let mut email_otp = Otp::totp("alice", &base32::decode("JBSWY3DPEHPK3PXP").unwrap());
+        email_otp.issuer = Some(String::from("Email"));
+        let item = |name: &str, value: &str, otp: Option<Otp>| ImportedItem {
+            name: String::from(name),
+            value: String::from(value),
+            otp,
+            ..ImportedItem::default()
+        };
+        assert_eq!(
+            import.items,
+            vec![
+                item("GitHub", "kT9#vLq2$wXz7!mP", Some(github_otp)),
+                item("GitHub / Notes", "Recovery codes: 1f2e-3d4c 5b6a-7980", None),
+                item("GitHub / Security answer", "Cerberus <&>", None),
+                item("Wifi / Network key", "c0rr3ct-h0rs3 ", None),
+                item("Internet/Email", "Zq8&Rt5^Hn2*Wd6%", Some(email_otp)),
+                item("Internet/Shopping/Shop", "ünïcødé ✓", None),
+            ]
+        );
+        let unmapped = |item: &str, field: &str| UnmappedField {
+            item: String::from(item),
+            field: String::from(field),
+        };
+        assert_eq!(
+            import.unmapped,
+            vec![
+                unmapped("GitHub", "URL"),
+                unmapped("GitHub", "UserName"),
+                unmapped("Wifi", "attachments.router.txt"),
+                unmapped("Internet/Email", "UserName"),
+                unmapped("Internet/Shopping/Shop", "UserName"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_totp_settings() {
+        let mut database = fixture_database();
+        database.root.groups[0].entries[0].fields[6].value = String::from("30;S");
+        let import = database.to_import();
+        assert_eq!(import.items[4].otp, None);
+        assert!(import.unmapped.contains(&UnmappedField {
+            item: String::from("Internet/Email"),
+            field: String::from("TOTP Seed"),
+        }));
+    }
+
+    #[test]
+    fn vault_to_database() {
+        let mut vault = Vault::create(None);
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "p4ssw0rd").unwrap();
+        vault.add("Internet/Email", "Zq8&Rt5^Hn2*Wd6%", "p4ssw0rd").unwrap();
+        vault.add("Internet/Shopping/Shop", "ünïcødé ✓", "p4ssw0rd").unwrap();
+        vault.add("Internet/Bank", "0th3r p4ssw0rd", "other password").unwrap();
+        let otp = Otp::totp("alice", b"12345678901234567890");
+        vault.set_otp("Internet/Email", &otp, "p4ssw0rd").unwrap();
+        let database = Database::from_vault(&vault, "p4ssw0rd", "Export");
+        assert_eq!(database.name, "Export");
+        assert_eq!(database.root.entries.len(), 1);
+        assert_eq!(database.root.entries[0].get("Title"), Some("GitHub"));
+        assert_eq!(database.root.entries[0].timestamps, vault.timestamps("GitHub"));
+        let internet = &database.root.groups[0];
+        assert_eq!(internet.name, "Internet");
+        assert_eq!(internet.entries.len(), 1);
+        assert_eq!(internet.entries[0].get("otp"), Some(otp.to_uri().as_str()));
+        assert_eq!(internet.groups[0].name, "Shopping");
+        // Items are identical after a round-trip through KeePass.
+        let written = database.write("p4ssw0rd", Cipher::ChaCha20, TEST_KDF).unwrap();
+        let import = Database::read(&written, "p4ssw0rd").unwrap().to_import();
+        assert!(import.unmapped.is_empty());
+        let mut imported = Vault::create(None);
+        imported.version = Version::Test;
+        imported.import(&import, "p4ssw0rd").unwrap();
+        assert_eq!(imported.list(), vec!["GitHub", "Internet/Email", "Internet/Shopping/Shop"]);
+        assert_eq!(imported.get("Internet/Shopping/Shop", "p4ssw0rd").unwrap(), "ünïcødé ✓");
+        assert_eq!(imported.get_otp("Internet/Email", "p4ssw0rd").unwrap(), otp);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::kdbx::take;
+
+// Only the major version is checked: minor versions are backwards-compatible.
+const VERSION: u16 = 0x0100;
+const VERSION_MAJOR_MASK: u16 = 0xff00;
+
+/// Typed value of a variant dictionary, which KeePass uses to store the KDF parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Variant {
+    UInt32(u32),
+    UInt64(u64),
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl Variant {
+    fn type_byte(&self) -> u8 {
+        match self {
+            Variant::UInt32(_) => 0x04,
+            Variant::UInt64(_) => 0x05,
+            Variant::Bool(_) => 0x08,
+            Variant::Int32(_) => 0x0c,
+            Variant::Int64(_) => 0x0d,
+            Variant::String(_) => 0x18,
+            Variant::Bytes(_) => 0x42,
+        }
+    }
+}
+
+pub(crate) fn parse(data: &[u8]) -> Result<Vec<(String, Variant)>, String> {
+    let mut r = data;
+    // [u16: version]
+    let version = u16::from_le_bytes(take(&mut r, 2)?.try_into().unwrap());
+    if version & VERSION_MAJOR_MASK > VERSION & VERSION_MAJOR_MASK {
+        return Err(format!("unsupported variant dictionary version: {:#06x}", version));
+    }
+    let mut entries = Vec::new();
+    loop {
+        // [u8: type]
+        let value_type = take(&mut r, 1)?[0];
+        if value_type == 0 {
+            break;
+        }
+        // [i32: name_len][name_len*u8: name][i32: value_len][value_len*u8: value]
+        let name_len = i32::from_le_bytes(take(&mut r, 4)?.try_into().unwrap());
+        let name = take(&mut r, usize::try_from(name_len).map_err(|_| "invalid variant dictionary name length")?)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| "invalid variant dictionary name")?;
+        let value_len = i32::from_le_bytes(take(&mut r, 4)?.try_into().unwrap());
+        let value = take(&mut r, usize::try_from(value_len).map_err(|_| "invalid variant dictionary value length")?)?;
+        let invalid = || format!("invalid variant dictionary value: {}", name);
+        let value = match value_type {
+            0x04 => Variant::UInt32(u32::from_le_bytes(value.try_into().map_err(|_| invalid())?)),
+            0x05 => Variant::UInt64(u64::from_le_bytes(value.try_into().map_err(|_| invalid())?)),
+            0x08 => match value {
+                [b] => Variant::Bool(*b != 0),
+                _ => return Err(invalid()),
+            },
+            0x0c => Variant::Int32(i32::from_le_bytes(value.try_into().map_err(|_| invalid())?)),
+            0x0d => Variant::Int64(i64::from_le_bytes(value.try_into().map_err(|_| invalid())?)),
+            0x18 => Variant::String(String::from_utf8(value.to_vec()).map_err(|_| invalid())?),
+            0x42 => Variant::Bytes(value.to_vec()),
+            _ => return Err(format!("unknown variant dictionary type: {:#04x}", value_type)),
+        };
+        entries.push((name, value));
+    }
+    if !r.is_empty() {
+        return Err(String::from("unexpected data after variant dictionary"));
+    }
+    Ok(entries)
+}
+
+pub(crate) fn serialize(entries: &[(String, Variant)]) -> Vec<u8> {
+    let mut w = VERSION.to_le_bytes().to_vec();
+    for (name, value) in entries {
+        let value_bytes = match value {
+            Variant::UInt32(v) => v.to_le_bytes().to_vec(),
+            Variant::UInt64(v) => v.to_le_bytes().to_vec(),
+            Variant::Bool(v) => vec![*v as u8],
+            Variant::Int32(v) => v.to_le_bytes().to_vec(),
+            Variant::Int64(v) => v.to_le_bytes().to_vec(),
+            Variant::String(v) => v.as_bytes().to_vec(),
+            Variant::Bytes(v) => v.clone(),
+        };
+        w.push(value.type_byte());
+        w.extend((name.len() as i32).to_le_bytes());
+        w.extend(name.as_bytes());
+        w.extend((value_bytes.len() as i32).to_le_bytes());
+        w.extend(value_bytes);
+    }
+    w.push(0);
+    w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hex;
+
+    #[test]
+    fn serialize_parse() {
+        let entries = vec![
+            (String::from("$UUID"), Variant::Bytes(vec![0xef, 0x63])),
+            (String::from("I"), Variant::UInt64(2)),
+            (String::from("P"), Variant::UInt32(4)),
+            (String::from("B"), Variant::Bool(true)),
+            (String::from("i"), Variant::Int32(-1)),
+            (String::from("l"), Variant::Int64(-2)),
+            (String::from("s"), Variant::String(String::from("value"))),
+        ];
+        let serialized = serialize(&entries);
+        assert_eq!(
+            hex::encode(&serialized),
+            "00014205000000245555494402000000ef63050100000049080000000200000000000000040100000050040000000400000008010000004201000000010c010000006904000000ffffffff0d010000006c08000000feffffffffffffff1801000000730500000076616c756500"
+        );
+        assert_eq!(parse(&serialized).unwrap(), entries);
+        assert_eq!(parse(&[0x00, 0x01, 0x00]).unwrap(), vec![]);
+        // Minor versions are ignored.
+        assert_eq!(parse(&[0xff, 0x01, 0x00]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parse_failure() {
+        let test_cases = vec![
+            ("", "unexpected end of KeePass data"),
+            ("0002", "unsupported variant dictionary version: 0x0200"),
+            ("0001", "unexpected end of KeePass data"),
+            ("000104010000004905000000", "unexpected end of KeePass data"),
+            ("00010401000000490500000001020304050000", "invalid variant dictionary value: I"),
+            ("0001080100000042020000000101", "invalid variant dictionary value: B"),
+            ("00011801000000730100000080", "invalid variant dictionary value: s"),
+            ("0001ff01000000730100000080", "unknown variant dictionary type: 0xff"),
+            ("000104ffffffff", "invalid variant dictionary name length"),
+            ("00010000", "unexpected data after variant dictionary"),
+        ];
+        for (data, error) in test_cases {
+            assert_eq!(parse(&hex::decode(data).unwrap()), Err(String::from(error)), "{}", data);
+        }
+    }
+}
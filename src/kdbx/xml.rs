@@ -0,0 +1,314 @@
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::base64;
+use crate::kdbx::{Database, Entry, Field, Group, InnerStream};
+use crate::vault::Timestamps;
+
+// KeePass times are stored as the number of seconds since 0001-01-01T00:00:00Z.
+const UNIX_EPOCH_SECONDS: i64 = 62_135_596_800;
+
+/// Parse the XML document contained in KeePass databases, or exported by KeePass as plaintext.
+/// Protected values are decrypted with the inner stream, in document order (including history entries, which are
+/// discarded). Plaintext exports don't have an inner stream: their protected values are marked as `ProtectInMemory`.
+pub(crate) fn parse(xml: &str, mut stream: Option<&mut InnerStream>) -> Result<Database, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut parser = Parser::default();
+    loop {
+        match reader.read_event().map_err(|e| format!("invalid KeePass XML: {}", e))? {
+            Event::Start(e) => parser.start(&e)?,
+            Event::Empty(e) => {
+                parser.start(&e)?;
+                parser.end(stream.as_deref_mut())?;
+            }
+            Event::End(_) => parser.end(stream.as_deref_mut())?,
+            Event::Text(e) => parser.text.push_str(&e.unescape().map_err(|e| format!("invalid KeePass XML: {}", e))?),
+            Event::CData(e) => parser.text.push_str(std::str::from_utf8(&e).map_err(|e| format!("invalid KeePass XML: {}", e))?),
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(Database {
+        name: parser.name,
+        root: parser.root.ok_or("missing root group in KeePass XML")?,
+    })
+}
+
+#[derive(Default)]
+struct Parser {
+    path: Vec<String>,
+    text: String,
+    protected: bool,
+    encrypted: bool,
+    key: String,
+    name: String,
+    root: Option<Group>,
+    groups: Vec<Group>,
+    entries: Vec<(Entry, Option<u64>, Option<u64>)>,
+}
+
+impl Parser {
+    fn start(&mut self, e: &BytesStart) -> Result<(), String> {
+        let name = String::from_utf8(e.name().as_ref().to_vec()).map_err(|_| "invalid KeePass XML element")?;
+        self.protected = false;
+        self.encrypted = false;
+        for attribute in e.attributes() {
+            let attribute = attribute.map_err(|e| format!("invalid KeePass XML: {}", e))?;
+            match attribute.key.as_ref() {
+                b"Protected" => self.encrypted = attribute.value.as_ref() == b"True",
+                b"ProtectInMemory" => self.protected = attribute.value.as_ref() == b"True",
+                _ => (),
+            }
+        }
+        self.protected |= self.encrypted;
+        let parent = self.path.last().map(|p| p.as_str());
+        match (name.as_str(), parent) {
+            ("Group", Some("Root" | "Group")) => self.groups.push(Group::default()),
+            ("Entry", Some("Group" | "History")) => self.entries.push((Entry::default(), None, None)),
+            _ => (),
+        }
+        self.path.push(name);
+        self.text.clear();
+        Ok(())
+    }
+
+    fn end(&mut self, stream: Option<&mut InnerStream>) -> Result<(), String> {
+        let name = self.path.pop().unwrap_or_default();
+        let text = std::mem::take(&mut self.text);
+        let parent = self.path.last().map(|p| p.as_str());
+        let grandparent = self.path.len().checked_sub(2).map(|i| self.path[i].as_str());
+        match (name.as_str(), parent) {
+            ("Group", Some("Root" | "Group")) => {
+                let group = self.groups.pop().ok_or("invalid KeePass XML group")?;
+                match self.groups.last_mut() {
+                    Some(parent) => parent.groups.push(group),
+                    None if self.root.is_none() => self.root = Some(group),
+                    None => return Err(String::from("multiple root groups in KeePass XML")),
+                }
+            }
+            ("Entry", Some("Group" | "History")) => {
+                let (mut entry, created_at, modified_at) = self.entries.pop().ok_or("invalid KeePass XML entry")?;
+                if let (Some(created_at), Some(modified_at)) = (created_at, modified_at) {
+                    entry.timestamps = Some(Timestamps { created_at, modified_at });
+                }
+                // We only keep the current version of entries.
+                if parent == Some("Group") {
+                    self.groups.last_mut().ok_or("invalid KeePass XML entry")?.entries.push(entry);
+                }
+            }
+            ("UUID", Some("Group")) => self.groups.last_mut().ok_or("invalid KeePass XML group")?.uuid = parse_uuid(&text)?,
+            ("UUID", Some("Entry")) => self.entries.last_mut().ok_or("invalid KeePass XML entry")?.0.uuid = parse_uuid(&text)?,
+            ("Name", Some("Group")) => self.groups.last_mut().ok_or("invalid KeePass XML group")?.name = text,
+            ("DatabaseName", Some("Meta")) => self.name = text,
+            ("Key", Some("String" | "Binary")) => self.key = text,
+            ("Value", Some("String")) if grandparent == Some("Entry") => {
+                let value = match self.encrypted {
+                    true => {
+                        let stream = stream.ok_or("unexpected protected value in KeePass XML")?;
+                        let mut value = base64::decode(&text)?;
+                        stream.apply(&mut value);
+                        String::from_utf8(value).map_err(|_| "invalid protected value in KeePass XML")?
+                    }
+                    false => text,
+                };
+                let (entry, _, _) = self.entries.last_mut().ok_or("invalid KeePass XML entry")?;
+                entry.fields.push(Field {
+                    key: std::mem::take(&mut self.key),
+                    value,
+                    protected: self.protected,
+                });
+            }
+            ("Value", Some("Binary")) if grandparent == Some("Entry") => {
+                let (entry, _, _) = self.entries.last_mut().ok_or("invalid KeePass XML entry")?;
+                entry.attachments.push(std::mem::take(&mut self.key));
+            }
+            ("CreationTime", Some("Times")) if grandparent == Some("Entry") => {
+                self.entries.last_mut().ok_or("invalid KeePass XML entry")?.1 = Some(parse_time(&text)?);
+            }
+            ("LastModificationTime", Some("Times")) if grandparent == Some("Entry") => {
+                self.entries.last_mut().ok_or("invalid KeePass XML entry")?.2 = Some(parse_time(&text)?);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+fn parse_uuid(text: &str) -> Result<[u8; 16], String> {
+    base64::decode(text)?.try_into().map_err(|_| String::from("invalid KeePass UUID"))
+}
+
+// Times before the unix epoch are clamped.
+fn parse_time(text: &str) -> Result<u64, String> {
+    let seconds: [u8; 8] = base64::decode(text)?.try_into().map_err(|_| "invalid KeePass time")?;
+    Ok(i64::from_le_bytes(seconds).saturating_sub(UNIX_EPOCH_SECONDS).max(0) as u64)
+}
+
+fn format_time(timestamp: u64) -> String {
+    base64::encode((timestamp as i64 + UNIX_EPOCH_SECONDS).to_le_bytes())
+}
+
+/// Write the XML document of a KeePass database, encrypting protected values with the inner stream.
+/// Without inner stream, protected values are written as plaintext.
+pub(crate) fn write(database: &Database, mut stream: Option<&mut InnerStream>) -> String {
+    let mut w = Writer {
+        xml: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n"),
+        depth: 0,
+    };
+    w.open("KeePassFile");
+    w.open("Meta");
+    w.leaf("Generator", "cybele");
+    w.leaf("DatabaseName", &database.name);
+    w.open("MemoryProtection");
+    w.leaf("ProtectTitle", "False");
+    w.leaf("ProtectUserName", "False");
+    w.leaf("ProtectPassword", "True");
+    w.leaf("ProtectURL", "False");
+    w.leaf("ProtectNotes", "False");
+    w.close("MemoryProtection");
+    w.close("Meta");
+    w.open("Root");
+    write_group(&mut w, &database.root, &mut stream);
+    w.leaf("DeletedObjects", "");
+    w.close("Root");
+    w.close("KeePassFile");
+    w.xml
+}
+
+fn write_group(w: &mut Writer, group: &Group, stream: &mut Option<&mut InnerStream>) {
+    w.open("Group");
+    w.leaf("UUID", &base64::encode(group.uuid));
+    w.leaf("Name", &group.name);
+    for entry in &group.entries {
+        w.open("Entry");
+        w.leaf("UUID", &base64::encode(entry.uuid));
+        if let Some(timestamps) = entry.timestamps {
+            w.open("Times");
+            w.leaf("CreationTime", &format_time(timestamps.created_at));
+            w.leaf("LastModificationTime", &format_time(timestamps.modified_at));
+            w.leaf("LastAccessTime", &format_time(timestamps.modified_at));
+            w.leaf("Expires", "False");
+            w.close("Times");
+        }
+        for field in &entry.fields {
+            w.open("String");
+            w.leaf("Key", &field.key);
+            match (field.protected, stream.as_deref_mut()) {
+                (true, Some(stream)) => {
+                    let mut value = field.value.as_bytes().to_vec();
+                    stream.apply(&mut value);
+                    w.line(&format!("<Value Protected=\"True\">{}</Value>", base64::encode(value)));
+                }
+                (true, None) => w.line(&format!("<Value ProtectInMemory=\"True\">{}</Value>", escape(&field.value))),
+                (false, _) => w.leaf("Value", &field.value),
+            }
+            w.close("String");
+        }
+        w.close("Entry");
+    }
+    group.groups.iter().for_each(|g| write_group(w, g, stream));
+    w.close("Group");
+}
+
+struct Writer {
+    xml: String,
+    depth: usize,
+}
+
+impl Writer {
+    fn line(&mut self, line: &str) {
+        self.xml.extend(std::iter::repeat_n('\t', self.depth));
+        self.xml.push_str(line);
+        self.xml.push('\n');
+    }
+
+    fn open(&mut self, name: &str) {
+        self.line(&format!("<{}>", name));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{}>", name));
+    }
+
+    fn leaf(&mut self, name: &str, text: &str) {
+        match text {
+            "" => self.line(&format!("<{}/>", name)),
+            text => self.line(&format!("<{}>{}</{}>", name, escape(text), name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keepass_times() {
+        assert_eq!(parse_time("AAAAAAAAAAA=").unwrap(), 0);
+        assert_eq!(format_time(0), "APeRdw4AAAA=");
+        assert_eq!(parse_time("APeRdw4AAAA=").unwrap(), 0);
+        assert_eq!(parse_time(&format_time(1_700_000_000)).unwrap(), 1_700_000_000);
+        assert_eq!(parse_time("AAAA"), Err(String::from("invalid KeePass time")));
+    }
+
+    #[test]
+    fn parse_unprotected_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <KeePassFile>
+                <Meta><DatabaseName>Test &amp; co</DatabaseName></Meta>
+                <Root>
+                    <Group>
+                        <UUID>AQEBAQEBAQEBAQEBAQEBAQ==</UUID>
+                        <Name>Root</Name>
+                        <Entry>
+                            <UUID>AgICAgICAgICAgICAgICAg==</UUID>
+                            <String><Key>Title</Key><Value>Test</Value></String>
+                            <String><Key>Password</Key><Value ProtectInMemory="True"><![CDATA[ p<4>ss ]]></Value></String>
+                            <String><Key>Notes</Key><Value/></String>
+                        </Entry>
+                        <Group><UUID>AwMDAwMDAwMDAwMDAwMDAw==</UUID><Name>Sub</Name></Group>
+                    </Group>
+                </Root>
+            </KeePassFile>"#;
+        let database = parse(xml, None).unwrap();
+        assert_eq!(database.name, "Test & co");
+        assert_eq!(database.root.uuid, [1u8; 16]);
+        assert_eq!(database.root.name, "Root");
+        assert_eq!(database.root.groups.len(), 1);
+        assert_eq!(database.root.groups[0].name, "Sub");
+        let entry = &database.root.entries[0];
+        assert_eq!(entry.uuid, [2u8; 16]);
+        assert_eq!(entry.get("Title"), Some("Test"));
+        assert_eq!(entry.get("Password"), Some(" p<4>ss "));
+        assert!(!entry.fields[0].protected);
+        assert!(entry.fields[1].protected);
+        assert_eq!(entry.get("Notes"), Some(""));
+        assert_eq!(entry.timestamps, None);
+    }
+
+    #[test]
+    fn parse_invalid_xml() {
+        let test_cases = vec![
+            ("<KeePassFile><Root></Root></KeePassFile>", "missing root group in KeePass XML"),
+            (
+                "<KeePassFile><Root><Group></Root></KeePassFile>",
+                "invalid KeePass XML: ill-formed document: expected `</Group>`, but `</Root>` was found",
+            ),
+            (
+                "<KeePassFile><Root><Group></Group><Group></Group></Root></KeePassFile>",
+                "multiple root groups in KeePass XML",
+            ),
+            (
+                "<KeePassFile><Root><Group><Entry><String><Key>Password</Key><Value Protected=\"True\">AAAA</Value></String></Entry></Group></Root></KeePassFile>",
+                "unexpected protected value in KeePass XML",
+            ),
+            ("<KeePassFile><Root><Group><UUID>AAAA</UUID></Group></Root></KeePassFile>", "invalid KeePass UUID"),
+        ];
+        for (xml, error) in test_cases {
+            assert_eq!(parse(xml, None), Err(String::from(error)));
+        }
+    }
+}
@@ -3,13 +3,23 @@
 extern crate rand;
 
 use self::crypto::cipher;
+use self::crypto::gf256;
 use self::crypto::keys;
-pub use self::crypto::keys::Purpose;
+pub use self::crypto::keys::{Kdf, Purpose};
 pub use self::version::Version;
 
+pub mod armor;
+pub mod backup;
+pub mod base32;
+pub mod base64;
+pub mod base64url;
 mod crypto;
 pub mod hash;
 pub mod hex;
+pub mod import;
+pub mod kdbx;
+pub mod otp;
 pub mod password;
+pub mod recovery;
 pub mod vault;
 mod version;
@@ -0,0 +1,356 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::base32;
+use crate::crypto::hmac;
+use crate::hash::sha1::Sha1;
+use crate::hash::sha256::Sha256;
+use crate::hash::sha512::Sha512;
+
+/// Hash function used by the HMAC of one-time password generators.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Sha1 => hmac::authenticate::<Sha1>(key, message).to_vec(),
+            Algorithm::Sha256 => hmac::authenticate::<Sha256>(key, message).to_vec(),
+            Algorithm::Sha512 => hmac::authenticate::<Sha512>(key, message).to_vec(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Algorithm> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" => Some(Algorithm::Sha1),
+            "SHA256" => Some(Algorithm::Sha256),
+            "SHA512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// Compute an HMAC-based one-time password.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc4226
+pub fn hotp(secret: &[u8], counter: u64, digits: u32, algorithm: Algorithm) -> Option<String> {
+    if !(6..=8).contains(&digits) {
+        eprintln!("One-time passwords must contain between 6 and 8 digits");
+        return None;
+    }
+    let mac = algorithm.authenticate(secret, &counter.to_be_bytes());
+    // Dynamic truncation: the last 4 bits select the offset of the 31-bit value we extract from the mac.
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([mac[offset] & 0x7f, mac[offset + 1], mac[offset + 2], mac[offset + 3]]);
+    let code = binary % 10u32.pow(digits);
+    Some(format!("{:0width$}", code, width = digits as usize))
+}
+
+/// Compute a time-based one-time password, where `timestamp` is expressed in seconds since the unix epoch.
+/// Specification can be found here: https://www.rfc-editor.org/rfc/rfc6238
+pub fn totp(secret: &[u8], timestamp: u64, period: u64, digits: u32, algorithm: Algorithm) -> Option<String> {
+    if period == 0 {
+        eprintln!("TOTP period cannot be 0");
+        return None;
+    }
+    hotp(secret, timestamp / period, digits, algorithm)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OtpKind {
+    Hotp { counter: u64 },
+    Totp { period: u64 },
+}
+
+/// One-time password generator, usually shared by websites as an `otpauth://` URI (often displayed as a QR code).
+/// The URI format is described here: https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Otp {
+    pub kind: OtpKind,
+    pub issuer: Option<String>,
+    pub account: String,
+    pub secret: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OtpCode {
+    pub code: String,
+    /// Number of seconds during which the code is valid (not applicable to counter-based codes).
+    pub remaining_seconds: Option<u64>,
+}
+
+impl Otp {
+    /// Create a TOTP generator with the default parameters used by most websites.
+    pub fn totp(account: &str, secret: &[u8]) -> Otp {
+        Otp {
+            kind: OtpKind::Totp { period: 30 },
+            issuer: None,
+            account: String::from(account),
+            secret: secret.to_vec(),
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+        }
+    }
+
+    pub fn generate(&self, timestamp: u64) -> Option<OtpCode> {
+        match self.kind {
+            OtpKind::Hotp { counter } => {
+                let code = hotp(&self.secret, counter, self.digits, self.algorithm)?;
+                Some(OtpCode { code, remaining_seconds: None })
+            }
+            OtpKind::Totp { period } => {
+                let code = totp(&self.secret, timestamp, period, self.digits, self.algorithm)?;
+                Some(OtpCode {
+                    code,
+                    remaining_seconds: Some(period - timestamp % period),
+                })
+            }
+        }
+    }
+
+    pub fn generate_now(&self) -> Option<OtpCode> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        self.generate(timestamp)
+    }
+
+    pub fn to_uri(&self) -> String {
+        let label = match &self.issuer {
+            Some(issuer) => format!("{}:{}", percent_encode(issuer), percent_encode(&self.account)),
+            None => percent_encode(&self.account),
+        };
+        let mut uri = match self.kind {
+            OtpKind::Hotp { .. } => format!("otpauth://hotp/{}?secret={}", label, base32::encode_unpadded(&self.secret)),
+            OtpKind::Totp { .. } => format!("otpauth://totp/{}?secret={}", label, base32::encode_unpadded(&self.secret)),
+        };
+        if let Some(issuer) = &self.issuer {
+            uri.push_str(&format!("&issuer={}", percent_encode(issuer)));
+        }
+        uri.push_str(&format!("&algorithm={}&digits={}", self.algorithm.name(), self.digits));
+        match self.kind {
+            OtpKind::Hotp { counter } => uri.push_str(&format!("&counter={}", counter)),
+            OtpKind::Totp { period } => uri.push_str(&format!("&period={}", period)),
+        }
+        uri
+    }
+
+    pub fn from_uri(uri: &str) -> Option<Otp> {
+        let (kind, rest) = if let Some(rest) = uri.strip_prefix("otpauth://totp/") {
+            ("totp", rest)
+        } else if let Some(rest) = uri.strip_prefix("otpauth://hotp/") {
+            ("hotp", rest)
+        } else {
+            eprintln!("Invalid otpauth URI scheme or type");
+            return None;
+        };
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        // The label is either "account" or "issuer:account", and the colon may be percent-encoded.
+        let label = percent_decode(label)?;
+        let (label_issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(String::from(issuer)), String::from(account.trim_start())),
+            None => (None, label),
+        };
+        let mut secret: Option<Vec<u8>> = None;
+        let mut issuer: Option<String> = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = 6u32;
+        let mut counter: Option<u64> = None;
+        let mut period = 30u64;
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=')?;
+            let value = percent_decode(value)?;
+            match key {
+                "secret" => secret = Some(base32::decode(&value).map_err(|e| eprintln!("Invalid OTP secret: {}", e)).ok()?),
+                "issuer" => issuer = Some(value),
+                "algorithm" => algorithm = Algorithm::from_name(&value)?,
+                "digits" => digits = value.parse().ok()?,
+                "counter" => counter = Some(value.parse().ok()?),
+                "period" => period = value.parse().ok()?,
+                // Unknown parameters (such as "image") are ignored.
+                _ => (),
+            }
+        }
+        let kind = match kind {
+            "hotp" => OtpKind::Hotp { counter: counter? },
+            _ => OtpKind::Totp { period },
+        };
+        let otp = Otp {
+            kind,
+            issuer: issuer.or(label_issuer),
+            account,
+            secret: secret?,
+            algorithm,
+            digits,
+        };
+        if otp.secret.is_empty() || !(6..=8).contains(&otp.digits) || otp.kind == (OtpKind::Totp { period: 0 }) {
+            eprintln!("Invalid otpauth URI parameters");
+            return None;
+        }
+        Some(otp)
+    }
+}
+
+fn percent_encode(data: &str) -> String {
+    let mut encoded = String::with_capacity(data.len());
+    for &b in data.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(data: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut r = data.bytes();
+    while let Some(b) = r.next() {
+        match b {
+            b'%' => {
+                let hex = [r.next()?, r.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotp_test_vectors() {
+        // Test vectors from RFC 4226.
+        let secret = b"12345678901234567890";
+        let expected = ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489"];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(secret, counter as u64, 6, Algorithm::Sha1).unwrap(), *code);
+        }
+        assert_eq!(hotp(secret, 0, 5, Algorithm::Sha1), None);
+        assert_eq!(hotp(secret, 0, 9, Algorithm::Sha1), None);
+    }
+
+    #[test]
+    fn totp_test_vectors() {
+        // Test vectors from RFC 6238.
+        let secret_sha1 = b"12345678901234567890";
+        let secret_sha256 = b"12345678901234567890123456789012";
+        let secret_sha512 = b"1234567890123456789012345678901234567890123456789012345678901234";
+        let test_cases = vec![
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (timestamp, code_sha1, code_sha256, code_sha512) in test_cases {
+            assert_eq!(totp(secret_sha1, timestamp, 30, 8, Algorithm::Sha1).unwrap(), code_sha1);
+            assert_eq!(totp(secret_sha256, timestamp, 30, 8, Algorithm::Sha256).unwrap(), code_sha256);
+            assert_eq!(totp(secret_sha512, timestamp, 30, 8, Algorithm::Sha512).unwrap(), code_sha512);
+        }
+    }
+
+    #[test]
+    fn generate_codes() {
+        let otp = Otp::totp("alice@example.com", b"Hello!\xde\xad\xbe\xef");
+        let code = otp.generate(1700000000).unwrap();
+        assert_eq!(code.code, "324550");
+        assert_eq!(code.remaining_seconds, Some(10));
+        let code = otp.generate(1699999980).unwrap();
+        assert_eq!(code.code, "324550");
+        assert_eq!(code.remaining_seconds, Some(30));
+        let otp = Otp {
+            kind: OtpKind::Hotp { counter: 3 },
+            ..Otp::totp("alice", b"12345678901234567890")
+        };
+        let code = otp.generate(1700000000).unwrap();
+        assert_eq!(code.code, "969429");
+        assert_eq!(code.remaining_seconds, None);
+    }
+
+    #[test]
+    fn parse_otpauth_uri() {
+        let otp = Otp::from_uri("otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA1&digits=6&period=30").unwrap();
+        assert_eq!(otp.kind, OtpKind::Totp { period: 30 });
+        assert_eq!(otp.issuer, Some(String::from("ACME Co")));
+        assert_eq!(otp.account, "john.doe@email.com");
+        assert_eq!(otp.secret, base32::decode("HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ").unwrap());
+        assert_eq!(otp.algorithm, Algorithm::Sha1);
+        assert_eq!(otp.digits, 6);
+
+        let otp = Otp::from_uri("otpauth://hotp/Example%3Aalice?secret=jbswy3dpehpk3pxp&algorithm=sha512&digits=8&counter=42").unwrap();
+        assert_eq!(otp.kind, OtpKind::Hotp { counter: 42 });
+        assert_eq!(otp.issuer, Some(String::from("Example")));
+        assert_eq!(otp.account, "alice");
+        assert_eq!(otp.secret, b"Hello!\xde\xad\xbe\xef");
+        assert_eq!(otp.algorithm, Algorithm::Sha512);
+        assert_eq!(otp.digits, 8);
+
+        let otp = Otp::from_uri("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=60&image=https%3A%2F%2Fexample.com%2Flogo.png").unwrap();
+        assert_eq!(otp.kind, OtpKind::Totp { period: 60 });
+        assert_eq!(otp.issuer, None);
+        assert_eq!(otp.account, "alice");
+    }
+
+    #[test]
+    fn invalid_otpauth_uri() {
+        let test_cases = vec![
+            // invalid scheme
+            "https://totp/alice?secret=JBSWY3DPEHPK3PXP",
+            // unknown type
+            "otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP",
+            // missing secret
+            "otpauth://totp/alice?digits=6",
+            // invalid secret
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PX1",
+            // missing counter
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP",
+            // invalid digits
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=4",
+            // invalid period
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0",
+            // unknown algorithm
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+            // invalid percent-encoding
+            "otpauth://totp/alice%2?secret=JBSWY3DPEHPK3PXP",
+        ];
+        for t in test_cases {
+            assert_eq!(Otp::from_uri(t), None);
+        }
+    }
+
+    #[test]
+    fn otpauth_uri_round_trip() {
+        let otp = Otp {
+            kind: OtpKind::Hotp { counter: 7 },
+            issuer: Some(String::from("Cybele & Co")),
+            account: String::from("bob:builder"),
+            secret: b"12345678901234567890".to_vec(),
+            algorithm: Algorithm::Sha256,
+            digits: 7,
+        };
+        let uri = otp.to_uri();
+        assert_eq!(
+            uri,
+            "otpauth://hotp/Cybele%20%26%20Co:bob%3Abuilder?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Cybele%20%26%20Co&algorithm=SHA256&digits=7&counter=7"
+        );
+        assert_eq!(Otp::from_uri(&uri).unwrap(), otp);
+        let otp = Otp::totp("alice", b"Hello!\xde\xad\xbe\xef");
+        assert_eq!(otp.to_uri(), "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30");
+        assert_eq!(Otp::from_uri(&otp.to_uri()).unwrap(), otp);
+    }
+}
@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::hash::sha1;
+use crate::hex;
+
+// Binary index: [8*u8: magic] followed by sorted entries [20*u8: sha1][u32: count].
+const INDEX_MAGIC: &[u8; 8] = b"CYBHIBP1";
+const INDEX_ENTRY_SIZE: u64 = 24;
+// Lines of text files are "<40 hex chars>:<count>", optionally followed by "\r".
+const MAX_LINE_SIZE: u64 = 64;
+
+/// A local copy of the Pwned Passwords database (https://haveibeenpwned.com/Passwords), which can be:
+///  - a text file containing sorted "SHA1:COUNT" lines
+///  - a directory of range files, named after the first 5 hex chars of the hashes and containing "SUFFIX:COUNT" lines
+///  - a binary index built from a text file with `BreachDatabase::build_index`
+#[derive(Debug)]
+pub enum BreachDatabase {
+    Text { file: File, len: u64 },
+    Ranges { directory: PathBuf },
+    Index { file: File, entries: u64 },
+}
+
+impl BreachDatabase {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<BreachDatabase, String> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Ok(BreachDatabase::Ranges { directory: path.to_path_buf() });
+        }
+        let mut file = File::open(path).map_err(|e| format!("cannot open breach database: {}", e))?;
+        let len = file.metadata().map_err(|e| format!("cannot read breach database: {}", e))?.len();
+        let mut magic = [0u8; 8];
+        if len >= 8 && file.read_exact(&mut magic).is_ok() && &magic == INDEX_MAGIC {
+            if !(len - 8).is_multiple_of(INDEX_ENTRY_SIZE) {
+                return Err(String::from("invalid breach database index size"));
+            }
+            return Ok(BreachDatabase::Index {
+                file,
+                entries: (len - 8) / INDEX_ENTRY_SIZE,
+            });
+        }
+        Ok(BreachDatabase::Text { file, len })
+    }
+
+    /// Convert a sorted text file to a binary index, which is about half the size and faster to search.
+    /// Returns the number of entries.
+    pub fn build_index<P: AsRef<Path>, Q: AsRef<Path>>(text_path: P, index_path: Q) -> Result<u64, String> {
+        let input = File::open(text_path).map_err(|e| format!("cannot open breach database: {}", e))?;
+        let output = File::create(index_path).map_err(|e| format!("cannot create breach database index: {}", e))?;
+        let mut w = BufWriter::new(output);
+        w.write_all(INDEX_MAGIC).map_err(|e| e.to_string())?;
+        let mut previous: Option<[u8; 20]> = None;
+        let mut entries = 0;
+        for line in BufReader::new(input).lines() {
+            let line = line.map_err(|e| format!("cannot read breach database: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (hash, count) = parse_line(&line, 40).ok_or_else(|| format!("invalid breach database line: {}", line))?;
+            let hash: [u8; 20] = hex::decode_array(&hash).map_err(|e| format!("invalid breach database line: {}", e))?;
+            if previous.is_some_and(|p| p >= hash) {
+                return Err(String::from("breach database must be sorted by hash"));
+            }
+            previous = Some(hash);
+            w.write_all(&hash).map_err(|e| e.to_string())?;
+            w.write_all(&(count.min(u32::MAX as u64) as u32).to_be_bytes()).map_err(|e| e.to_string())?;
+            entries += 1;
+        }
+        w.flush().map_err(|e| e.to_string())?;
+        Ok(entries)
+    }
+
+    // Returns the number of times the given SHA-1 hash appears in breaches (0 if it doesn't).
+    fn lookup(&mut self, hash: &[u8; 20]) -> Result<u64, String> {
+        let hex_hash = hex::encode_upper(hash);
+        match self {
+            BreachDatabase::Text { file, len } => lookup_text(file, *len, &hex_hash),
+            BreachDatabase::Ranges { directory } => {
+                let path = directory.join(format!("{}.txt", &hex_hash[..5]));
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+                    Err(e) => return Err(format!("cannot open breach database: {}", e)),
+                };
+                // Range files are small (a few thousand lines), so we read them sequentially.
+                for line in BufReader::new(file).lines() {
+                    let line = line.map_err(|e| format!("cannot read breach database: {}", e))?;
+                    match parse_line(&line, 35) {
+                        Some((suffix, count)) if suffix == hex_hash[5..] => return Ok(count),
+                        _ => (),
+                    }
+                }
+                Ok(0)
+            }
+            BreachDatabase::Index { file, entries } => {
+                let (mut low, mut high) = (0, *entries);
+                let mut entry = [0u8; INDEX_ENTRY_SIZE as usize];
+                while low < high {
+                    let middle = low + (high - low) / 2;
+                    file.seek(SeekFrom::Start(8 + middle * INDEX_ENTRY_SIZE)).map_err(|e| e.to_string())?;
+                    file.read_exact(&mut entry).map_err(|e| format!("cannot read breach database: {}", e))?;
+                    match entry[..20].cmp(hash) {
+                        std::cmp::Ordering::Equal => return Ok(u32::from_be_bytes([entry[20], entry[21], entry[22], entry[23]]) as u64),
+                        std::cmp::Ordering::Less => low = middle + 1,
+                        std::cmp::Ordering::Greater => high = middle,
+                    }
+                }
+                Ok(0)
+            }
+        }
+    }
+}
+
+// Parse a "HASH:COUNT" line, where the hash has the given number of hex chars. Hashes are returned in uppercase.
+fn parse_line(line: &str, hash_len: usize) -> Option<(String, u64)> {
+    let (hash, count) = line.trim_end().split_once(':')?;
+    if hash.len() != hash_len || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((hash.to_uppercase(), count.trim().parse().ok()?))
+}
+
+// Binary search in a sorted text file: we seek to the middle of the searched range, and read the next full line.
+fn lookup_text(file: &mut File, len: u64, hash: &str) -> Result<u64, String> {
+    // Invariant: every line starting before `low` is smaller than the hash, and lines starting at or after `high` are greater.
+    let (mut low, mut high) = (0u64, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        // We read the first line that starts at or after `middle`.
+        let (line_start, line) = read_line_after(file, middle, len)?;
+        if line_start >= high {
+            // There is no line starting in [middle, high): we scan [low, middle) sequentially, which is short.
+            let mut position = low;
+            while position < middle {
+                let (start, line) = read_line_after(file, position, len)?;
+                if start >= middle {
+                    break;
+                }
+                if let Some(count) = compare_line(&line, hash)?.1 {
+                    return Ok(count);
+                }
+                position = start + line.len() as u64 + 1;
+            }
+            return Ok(0);
+        }
+        match compare_line(&line, hash)? {
+            (std::cmp::Ordering::Equal, Some(count)) => return Ok(count),
+            (std::cmp::Ordering::Less, _) => low = line_start + line.len() as u64 + 1,
+            _ => high = line_start,
+        }
+    }
+    Ok(0)
+}
+
+// Returns the position and content of the first non-blank line that starts at or after `position` (without the
+// trailing "\n"). Blank lines are skipped, as in `BreachDatabase::build_index`.
+fn read_line_after(file: &mut File, mut position: u64, len: u64) -> Result<(u64, String), String> {
+    loop {
+        let start = if position == 0 { 0 } else { position - 1 };
+        file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+        let mut buffer = vec![0u8; (2 * MAX_LINE_SIZE).min(len - start) as usize];
+        file.read_exact(&mut buffer).map_err(|e| format!("cannot read breach database: {}", e))?;
+        // If we didn't start at the beginning of the file, the line starts after the first "\n".
+        let offset = if position == 0 {
+            0
+        } else {
+            match buffer.iter().position(|&b| b == b'\n') {
+                Some(i) => i + 1,
+                None => return Ok((len, String::new())),
+            }
+        };
+        let line_start = start + offset as u64;
+        if line_start >= len {
+            return Ok((len, String::new()));
+        }
+        let line_end = buffer[offset..].iter().position(|&b| b == b'\n').map(|i| offset + i).unwrap_or(buffer.len());
+        let line = String::from_utf8(buffer[offset..line_end].to_vec()).map_err(|_| String::from("invalid breach database line"))?;
+        if !line.trim().is_empty() {
+            return Ok((line_start, line));
+        }
+        position = start + line_end as u64 + 1;
+    }
+}
+
+fn compare_line(line: &str, hash: &str) -> Result<(std::cmp::Ordering, Option<u64>), String> {
+    let (line_hash, count) = parse_line(line, 40).ok_or_else(|| format!("invalid breach database line: {}", line))?;
+    let ordering = line_hash.as_str().cmp(hash);
+    Ok((ordering, if ordering.is_eq() { Some(count) } else { None }))
+}
+
+/// Returns how many times the password appears in known data breaches (0 if it doesn't).
+/// The password never leaves the device: only its SHA-1 hash is looked up in the local database.
+pub fn check_breached(password: &str, db: &mut BreachDatabase) -> Result<u64, String> {
+    db.lookup(&sha1::hash(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    // Creates a unique temporary path for the given test.
+    fn temp_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("cybele-breach-{}-{}", std::process::id(), name));
+        path
+    }
+
+    fn sha1_hex(password: &str) -> String {
+        hex::encode_upper(sha1::hash(password.as_bytes()))
+    }
+
+    // Creates a sorted database containing the given passwords, and random hashes around them.
+    fn create_text_database(name: &str, passwords: &[(&str, u64)], random_entries: usize) -> PathBuf {
+        let mut lines: Vec<String> = passwords.iter().map(|(p, count)| format!("{}:{}", sha1_hex(p), count)).collect();
+        lines.extend((0..random_entries).map(|i| format!("{}:{}", sha1_hex(&format!("random password {}", i)), i + 1)));
+        lines.sort();
+        let path = temp_path(name);
+        fs::write(&path, lines.join("\r\n") + "\r\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn text_database() {
+        let breached = [("password", 9_545_824), ("123456", 37_359_195), ("P@ssw0rd", 101_125)];
+        for random_entries in [0, 1, 2, 10, 1000] {
+            let path = create_text_database(&format!("text-{}", random_entries), &breached, random_entries);
+            let mut db = BreachDatabase::open(&path).unwrap();
+            for (password, count) in breached {
+                assert_eq!(check_breached(password, &mut db), Ok(count), "{}", password);
+            }
+            for i in 0..random_entries.min(100) {
+                assert_eq!(check_breached(&format!("random password {}", i), &mut db), Ok(i as u64 + 1));
+            }
+            assert_eq!(check_breached("correct horse battery staple 42", &mut db), Ok(0));
+            assert_eq!(check_breached("", &mut db), Ok(0));
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn text_database_with_blank_lines() {
+        let breached = [("password", 9_545_824), ("123456", 37_359_195)];
+        for random_entries in [0, 1, 10, 1000] {
+            let path = create_text_database(&format!("blank-{}", random_entries), &breached, random_entries);
+            let text = fs::read_to_string(&path).unwrap();
+            // Blank lines (e.g. added by an editor) before, between and after the entries are ignored.
+            let lines: Vec<&str> = text.lines().collect();
+            let (head, tail) = lines.split_at(lines.len() / 2);
+            fs::write(&path, format!("\r\n{}\r\n\r\n\n{}\r\n\r\n   \n\n", head.join("\r\n"), tail.join("\r\n"))).unwrap();
+            let mut db = BreachDatabase::open(&path).unwrap();
+            for (password, count) in breached {
+                assert_eq!(check_breached(password, &mut db), Ok(count), "{}", password);
+            }
+            for i in 0..random_entries.min(100) {
+                assert_eq!(check_breached(&format!("random password {}", i), &mut db), Ok(i as u64 + 1));
+            }
+            assert_eq!(check_breached("correct horse battery staple 42", &mut db), Ok(0));
+            assert_eq!(check_breached("", &mut db), Ok(0));
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn binary_index() {
+        let breached = [("password", 9_545_824), ("qwerty", 10_556_095)];
+        let text_path = create_text_database("index-text", &breached, 500);
+        let index_path = temp_path("index-bin");
+        assert_eq!(BreachDatabase::build_index(&text_path, &index_path), Ok(502));
+        assert_eq!(fs::metadata(&index_path).unwrap().len(), 8 + 502 * 24);
+        let mut db = BreachDatabase::open(&index_path).unwrap();
+        assert!(matches!(db, BreachDatabase::Index { entries: 502, .. }));
+        assert_eq!(check_breached("password", &mut db), Ok(9_545_824));
+        assert_eq!(check_breached("qwerty", &mut db), Ok(10_556_095));
+        assert_eq!(check_breached("random password 42", &mut db), Ok(43));
+        assert_eq!(check_breached("not breached", &mut db), Ok(0));
+        fs::remove_file(text_path).unwrap();
+        fs::remove_file(index_path).unwrap();
+    }
+
+    #[test]
+    fn range_files() {
+        let directory = temp_path("ranges");
+        fs::create_dir_all(&directory).unwrap();
+        let hash = sha1_hex("password");
+        fs::write(
+            directory.join(format!("{}.txt", &hash[..5])),
+            format!("003D68EB55068C33ACE09247EE4C639306B:3\r\n{}:9545824\r\n", &hash[5..]),
+        )
+        .unwrap();
+        let mut db = BreachDatabase::open(&directory).unwrap();
+        assert_eq!(check_breached("password", &mut db), Ok(9_545_824));
+        assert_eq!(check_breached("not breached", &mut db), Ok(0));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn invalid_databases() {
+        assert!(BreachDatabase::open(temp_path("missing")).is_err());
+        let path = temp_path("invalid-text");
+        fs::write(&path, "not a hash:12\n").unwrap();
+        let mut db = BreachDatabase::open(&path).unwrap();
+        assert!(check_breached("password", &mut db).is_err());
+        let unsorted = temp_path("unsorted");
+        fs::write(&unsorted, format!("{}:1\n{}:2\n", sha1_hex("123456"), sha1_hex("password"))).unwrap();
+        assert_eq!(
+            BreachDatabase::build_index(&unsorted, temp_path("unsorted-index")),
+            Err(String::from("breach database must be sorted by hash"))
+        );
+        let truncated = temp_path("truncated-index");
+        fs::write(&truncated, b"CYBHIBP1\x00\x01").unwrap();
+        assert_eq!(BreachDatabase::open(&truncated).unwrap_err(), "invalid breach database index size");
+        for p in [path, unsorted, truncated, temp_path("unsorted-index")] {
+            fs::remove_file(p).unwrap();
+        }
+    }
+}
@@ -2,6 +2,21 @@ use rand::distributions::Uniform;
 use rand::rngs::OsRng;
 use rand::Rng;
 
+pub use self::breach::{check_breached, BreachDatabase};
+pub use self::passphrase::{generate_passphrase, Capitalization, PassphraseOptions};
+pub use self::policy::{CharClass, PasswordPolicy};
+pub use self::pronounceable::{generate_pronounceable, PronounceableOptions};
+pub use self::site::derive_site_password;
+pub use self::strength::{estimate_strength, Feedback, Match, Pattern, Strength};
+
+mod breach;
+pub(crate) mod passphrase;
+mod policy;
+mod pronounceable;
+mod rules;
+mod site;
+mod strength;
+
 // Note that we avoid characters that may be confused with other characters: I, l, O, 0
 // We generally want more letters and numbers than special characters, which is why we duplicate them.
 #[rustfmt::skip]
@@ -15,7 +30,15 @@ const PASSWORD_CHARS: [char; 141] = [
     '-', '_', '+', '=', '<', '>', '.', '!', '?', ':', ';', '~', '@', '#', '$', '%', '^', '&', '*', '(', ')', '[', ']', '{', '}'
 ];
 
-pub fn generate_password(password_len: usize) -> String {
+/// A generated password with its Shannon entropy (in bits). For password policies with consecutive chars or required
+/// chars rules, the entropy is an upper bound (see `PasswordPolicy::entropy`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedPassword {
+    pub password: String,
+    pub entropy: f64,
+}
+
+pub fn generate_password(password_len: usize) -> GeneratedPassword {
     let mut csprng = OsRng {};
     let between = Uniform::from(0..PASSWORD_CHARS.len());
     let mut password = String::with_capacity(password_len);
@@ -23,7 +46,25 @@ pub fn generate_password(password_len: usize) -> String {
         let i: usize = csprng.sample(between);
         password.push(PASSWORD_CHARS[i]);
     }
-    password
+    GeneratedPassword {
+        password,
+        entropy: password_len as f64 * char_entropy(&PASSWORD_CHARS),
+    }
+}
+
+// Shannon entropy (in bits) of a char uniformly selected from the given list.
+// Duplicated chars are more likely to be selected, so the entropy is lower than log2(distinct chars count).
+fn char_entropy(chars: &[char]) -> f64 {
+    let mut distinct = chars.to_vec();
+    distinct.sort();
+    distinct.dedup();
+    distinct
+        .iter()
+        .map(|c| {
+            let p = chars.iter().filter(|&x| x == c).count() as f64 / chars.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -33,9 +74,23 @@ mod tests {
     #[test]
     fn generate_passwords() {
         let p1 = generate_password(16);
-        assert_eq!(p1.len(), 16);
+        assert_eq!(p1.password.len(), 16);
         let p2 = generate_password(16);
-        assert_eq!(p2.len(), 16);
-        assert_ne!(p1, p2);
+        assert_eq!(p2.password.len(), 16);
+        assert_ne!(p1.password, p2.password);
+        assert!((p1.entropy - 101.0697010710048).abs() < 1e-9);
+        assert_eq!(generate_password(0).entropy, 0.0);
+    }
+
+    #[test]
+    fn default_alphabet_entropy() {
+        // 58 chars appear twice and 25 symbols appear once in the 141 chars of the alphabet.
+        let expected = 58.0 * (2.0 / 141.0) * (141.0f64 / 2.0).log2() + 25.0 * (1.0 / 141.0) * 141.0f64.log2();
+        assert!((char_entropy(&PASSWORD_CHARS) - expected).abs() < 1e-12);
+        assert!((char_entropy(&PASSWORD_CHARS) - 6.3168563169378).abs() < 1e-12);
+        // This is slightly less than a uniform selection among the 83 distinct chars.
+        assert!(char_entropy(&PASSWORD_CHARS) < 83f64.log2());
+        assert_eq!(char_entropy(&['a', 'b', 'c', 'd']), 2.0);
+        assert_eq!(char_entropy(&['a', 'a']), 0.0);
     }
 }
@@ -0,0 +1,228 @@
+use rand::distributions::Uniform;
+use rand::rngs::OsRng;
+use rand::Rng;
+
+use crate::password::GeneratedPassword;
+
+// The default wordlist is the BIP-39 English wordlist (CC0 license), not the EFF large wordlist: 2048 distinct
+// lowercase words (11 bits per word), uniquely identified by their first 4 letters. Some words are prefixes of others
+// (e.g. "act" and "action"), which matters when words aren't separated. Other wordlists (e.g. the EFF one, with its
+// dice rolls) can be used with `PassphraseOptions::wordlist`.
+// Specification can be found here: https://github.com/bitcoin/bips/blob/master/bip-0039/english.txt
+pub(crate) const WORDLIST: &str = include_str!("wordlist.txt");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Capitalization {
+    /// All words are lowercase.
+    Lowercase,
+    /// The first letter of every word is uppercase.
+    Capitalize,
+    /// The first letter of every word is randomly lowercase or uppercase (one bit of entropy per word).
+    Random,
+}
+
+/// Options used to generate passphrases made of random words.
+/// By default, words are lowercase, separated by '-', without any digit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassphraseOptions {
+    word_count: usize,
+    separator: String,
+    capitalization: Capitalization,
+    digit: bool,
+    words: Option<Vec<String>>,
+}
+
+impl PassphraseOptions {
+    pub fn new(word_count: usize) -> PassphraseOptions {
+        PassphraseOptions {
+            word_count,
+            separator: String::from("-"),
+            capitalization: Capitalization::Lowercase,
+            digit: false,
+            words: None,
+        }
+    }
+
+    /// Without a separator, words that start with another word of the list are never chosen (e.g. "action" when the
+    /// list contains "act"), since "act" + "ionic" couldn't be told apart from "action" + "ic".
+    pub fn separator(mut self, separator: &str) -> PassphraseOptions {
+        self.separator = String::from(separator);
+        self
+    }
+
+    pub fn capitalization(mut self, capitalization: Capitalization) -> PassphraseOptions {
+        self.capitalization = capitalization;
+        self
+    }
+
+    /// Append a random digit to a random word.
+    pub fn digit(mut self, digit: bool) -> PassphraseOptions {
+        self.digit = digit;
+        self
+    }
+
+    /// Use a custom wordlist: one word per line, optionally preceded by dice rolls (e.g. "11111\tabacus").
+    /// Duplicated words are removed, since they would reduce the entropy.
+    pub fn wordlist(mut self, wordlist: &str) -> PassphraseOptions {
+        let mut words: Vec<String> = parse_wordlist(wordlist).map(String::from).collect();
+        words.sort();
+        words.dedup();
+        self.words = Some(words);
+        self
+    }
+
+    /// Entropy (in bits) of the passphrases generated with these options.
+    pub fn entropy(&self) -> f64 {
+        let words_count = self.words().len();
+        if words_count == 0 || self.word_count == 0 {
+            return 0.0;
+        }
+        let mut entropy = self.word_count as f64 * (words_count as f64).log2();
+        if self.capitalization == Capitalization::Random {
+            entropy += self.word_count as f64;
+        }
+        if self.digit {
+            entropy += 10f64.log2() + (self.word_count as f64).log2();
+        }
+        entropy
+    }
+
+    // Words that can be chosen with these options.
+    fn words(&self) -> Vec<&str> {
+        let mut words: Vec<&str> = match &self.words {
+            Some(words) => words.iter().map(|w| w.as_str()).collect(),
+            None => parse_wordlist(WORDLIST).collect(),
+        };
+        if self.separator.is_empty() {
+            // Sorted words that start with another word come right after it (or after other words starting with it),
+            // so comparing with the last kept word is enough to make the list prefix-free.
+            words.sort();
+            let mut kept: Vec<&str> = Vec::with_capacity(words.len());
+            for word in words {
+                if !kept.last().is_some_and(|k| word.starts_with(k)) {
+                    kept.push(word);
+                }
+            }
+            words = kept;
+        }
+        words
+    }
+}
+
+pub(crate) fn parse_wordlist(wordlist: &str) -> impl Iterator<Item = &str> {
+    wordlist.lines().filter_map(|line| line.split_whitespace().last())
+}
+
+/// Generate a passphrase, using the same source of randomness as `generate_password`.
+/// Returns None if there are no words to choose from.
+pub fn generate_passphrase(options: &PassphraseOptions) -> Option<GeneratedPassword> {
+    let words = options.words();
+    if words.is_empty() {
+        eprintln!("cannot generate a passphrase from an empty wordlist");
+        return None;
+    }
+    let mut csprng = OsRng {};
+    let between = Uniform::from(0..words.len());
+    let mut chosen: Vec<String> = Vec::with_capacity(options.word_count);
+    for _ in 0..options.word_count {
+        let word = words[csprng.sample(between)];
+        let uppercase = match options.capitalization {
+            Capitalization::Lowercase => false,
+            Capitalization::Capitalize => true,
+            Capitalization::Random => csprng.gen_bool(0.5),
+        };
+        chosen.push(if uppercase { capitalize(word) } else { String::from(word) });
+    }
+    if options.digit && !chosen.is_empty() {
+        let i = csprng.gen_range(0..chosen.len());
+        let digit = csprng.gen_range(0..10u32);
+        chosen[i].push(char::from_digit(digit, 10).unwrap_or('0'));
+    }
+    Some(GeneratedPassword {
+        password: chosen.join(&options.separator),
+        entropy: options.entropy(),
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_wordlist() {
+        let words: Vec<&str> = parse_wordlist(WORDLIST).collect();
+        assert_eq!(words.len(), 2048);
+        assert_eq!(words[0], "abandon");
+        assert_eq!(words[2047], "zoo");
+        let mut distinct = words.clone();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 2048);
+        assert!(words.iter().all(|w| w.chars().all(|c| c.is_ascii_lowercase())));
+    }
+
+    #[test]
+    fn generate_passphrases() {
+        let options = PassphraseOptions::new(6);
+        let p1 = generate_passphrase(&options).unwrap();
+        let p2 = generate_passphrase(&options).unwrap();
+        assert_ne!(p1.password, p2.password);
+        assert_eq!(p1.entropy, 66.0);
+        let words: Vec<&str> = p1.password.split('-').collect();
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| WORDLIST.lines().any(|l| l == *w)));
+    }
+
+    #[test]
+    fn passphrase_options() {
+        let options = PassphraseOptions::new(4).separator(" ").capitalization(Capitalization::Capitalize).digit(true);
+        let p = generate_passphrase(&options).unwrap();
+        let words: Vec<&str> = p.password.split(' ').collect();
+        assert_eq!(words.len(), 4);
+        assert!(words.iter().all(|w| w.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert_eq!(p.password.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+        assert!((p.entropy - (44.0 + 10f64.log2() + 2.0)).abs() < 1e-9);
+
+        let options = PassphraseOptions::new(4).capitalization(Capitalization::Random);
+        assert_eq!(options.entropy(), 48.0);
+        assert_eq!(PassphraseOptions::new(0).entropy(), 0.0);
+        assert_eq!(generate_passphrase(&PassphraseOptions::new(0)).unwrap().password, "");
+    }
+
+    #[test]
+    fn custom_wordlist() {
+        let options = PassphraseOptions::new(3).wordlist("11111\tabacus\n11112\tabdomen\n11113\tabdomen\n11114\tabide\n11115\tabiding\n");
+        assert_eq!(options.entropy(), 3.0 * 2.0);
+        let p = generate_passphrase(&options).unwrap();
+        assert!(p.password.split('-').all(|w| ["abacus", "abdomen", "abide", "abiding"].contains(&w)));
+        assert!(generate_passphrase(&PassphraseOptions::new(3).wordlist("")).is_none());
+    }
+
+    #[test]
+    fn passphrase_without_separator() {
+        let bip39: Vec<&str> = parse_wordlist(WORDLIST).collect();
+        assert!(bip39.contains(&"act") && bip39.contains(&"action"));
+        let options = PassphraseOptions::new(6).separator("");
+        let words = options.words();
+        assert_eq!(words.len(), 1938);
+        assert!(!words.contains(&"action") && words.contains(&"act"));
+        assert!(words.iter().all(|w| words.iter().all(|p| p == w || !w.starts_with(p))));
+        assert!((options.entropy() - 6.0 * 1938f64.log2()).abs() < 1e-9);
+        let p = generate_passphrase(&options).unwrap();
+        assert!(p.password.chars().all(|c| c.is_ascii_lowercase()));
+        let options = PassphraseOptions::new(3).separator("").wordlist("act\naction\nactor\nadd\naddress\nzoo\n");
+        assert_eq!(options.words(), vec!["act", "add", "zoo"]);
+        assert!((options.entropy() - 3.0 * 3f64.log2()).abs() < 1e-9);
+        let options = PassphraseOptions::new(3).separator("").wordlist("abacus\nabide\nabdomen\nab\n");
+        assert_eq!(options.words(), vec!["ab"]);
+        assert_eq!(options.entropy(), 0.0);
+        assert_eq!(generate_passphrase(&options.separator("-")).unwrap().entropy, 6.0);
+    }
+}
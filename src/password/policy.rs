@@ -0,0 +1,467 @@
+use rand::distributions::Uniform;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::password::{GeneratedPassword, PASSWORD_CHARS};
+
+// Maximum number of random passwords we draw before building one constructively, when the consecutive chars or required
+// chars rules aren't satisfied, and maximum number of constructions before giving up.
+const MAX_ATTEMPTS: usize = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharClass {
+    const ALL: [CharClass; 4] = [CharClass::Lowercase, CharClass::Uppercase, CharClass::Digit, CharClass::Symbol];
+
+    pub fn of(c: char) -> CharClass {
+        if c.is_lowercase() {
+            CharClass::Lowercase
+        } else if c.is_uppercase() {
+            CharClass::Uppercase
+        } else if c.is_ascii_digit() {
+            CharClass::Digit
+        } else {
+            CharClass::Symbol
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            CharClass::Lowercase => 0,
+            CharClass::Uppercase => 1,
+            CharClass::Digit => 2,
+            CharClass::Symbol => 3,
+        }
+    }
+}
+
+/// Requirements that generated passwords must satisfy, for websites that reject some passwords.
+/// By default, passwords use the (distinct) chars of our default alphabet, without any other requirement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    length: usize,
+    alphabet: Vec<char>,
+    excluded: Vec<char>,
+    min: [usize; 4],
+    max: [usize; 4],
+    max_consecutive: Option<usize>,
+    required: Vec<Vec<char>>,
+}
+
+impl PasswordPolicy {
+    pub fn new(length: usize) -> PasswordPolicy {
+        let mut alphabet = PASSWORD_CHARS.to_vec();
+        alphabet.sort();
+        alphabet.dedup();
+        PasswordPolicy {
+            length,
+            alphabet,
+            excluded: Vec::new(),
+            min: [0; 4],
+            max: [usize::MAX; 4],
+            max_consecutive: None,
+            required: Vec::new(),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Require at least `count` chars of the given class.
+    pub fn min(mut self, class: CharClass, count: usize) -> PasswordPolicy {
+        self.min[class.index()] = count;
+        self
+    }
+
+    /// Allow at most `count` chars of the given class.
+    pub fn max(mut self, class: CharClass, count: usize) -> PasswordPolicy {
+        self.max[class.index()] = count;
+        self
+    }
+
+    /// Replace the symbols of the alphabet with the given subset.
+    pub fn symbols(mut self, symbols: &str) -> PasswordPolicy {
+        self.alphabet.retain(|&c| CharClass::of(c) != CharClass::Symbol);
+        self.alphabet.extend(symbols.chars().filter(|&c| CharClass::of(c) == CharClass::Symbol));
+        self.alphabet.sort();
+        self.alphabet.dedup();
+        self
+    }
+
+    /// Use a custom alphabet: chars are assigned to a class based on their type.
+    pub fn alphabet(mut self, alphabet: &str) -> PasswordPolicy {
+        self.alphabet = alphabet.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+        self.alphabet.sort();
+        self.alphabet.dedup();
+        self
+    }
+
+    /// Never use the given chars.
+    pub fn exclude(mut self, chars: &str) -> PasswordPolicy {
+        self.excluded.extend(chars.chars());
+        self
+    }
+
+    /// Reject passwords containing more than `count` identical consecutive chars.
+    pub fn max_consecutive(mut self, count: usize) -> PasswordPolicy {
+        self.max_consecutive = Some(count);
+        self
+    }
+
+    /// Require at least one of the given chars (which may belong to different classes).
+    pub fn require_one_of(mut self, chars: &str) -> PasswordPolicy {
+        self.required.push(chars.chars().collect());
+        self
+    }
+
+    /// Chars that may appear in passwords, sorted.
+    pub(crate) fn allowed_chars(&self) -> Vec<char> {
+        self.alphabet.iter().filter(|c| !self.excluded.contains(c)).copied().collect()
+    }
+
+    fn chars(&self, class: CharClass) -> Vec<char> {
+        self.alphabet
+            .iter()
+            .filter(|&&c| CharClass::of(c) == class && !self.excluded.contains(&c))
+            .copied()
+            .collect()
+    }
+
+    pub fn is_satisfied_by(&self, password: &str) -> bool {
+        let chars: Vec<char> = password.chars().collect();
+        if chars.len() != self.length || chars.iter().any(|c| !self.alphabet.contains(c) || self.excluded.contains(c)) {
+            return false;
+        }
+        let counts_valid = CharClass::ALL.iter().all(|&class| {
+            let count = chars.iter().filter(|&&c| CharClass::of(c) == class).count();
+            self.min[class.index()] <= count && count <= self.max[class.index()]
+        });
+        let consecutive_valid = match self.max_consecutive {
+            Some(max) => chars.chunk_by(|a, b| a == b).all(|run| run.len() <= max),
+            None => true,
+        };
+        let required_valid = self.required.iter().all(|set| chars.iter().any(|c| set.contains(c)));
+        counts_valid && consecutive_valid && required_valid
+    }
+
+    /// Generate a password that satisfies the policy, or None if it cannot be satisfied.
+    /// Passwords are drawn uniformly among the passwords with a valid composition until one satisfies the consecutive
+    /// chars and required chars rules, so every valid password has the same probability of being generated. Policies
+    /// that are rarely satisfied (e.g. a 2-char alphabet with `max_consecutive(1)`) would need too many draws: passwords
+    /// are then built by placing the required chars first and avoiding repetitions, which is no longer uniform.
+    pub fn generate(&self) -> Option<GeneratedPassword> {
+        let mut csprng = OsRng {};
+        let sets: Vec<Vec<char>> = CharClass::ALL.iter().map(|&class| self.chars(class)).collect();
+        let compositions = self.compositions(&sets);
+        let required_allowed = self.required.iter().all(|set| set.iter().any(|c| self.alphabet.contains(c) && !self.excluded.contains(c)));
+        if compositions.is_empty() || !required_allowed {
+            eprintln!("Password policy cannot be satisfied");
+            return None;
+        }
+        let total_weight: f64 = compositions.iter().map(|(_, w)| w).sum();
+        // We first select how many chars of each class the password contains, proportionally to the number of passwords
+        // that have this composition (up to floating point precision).
+        let shuffled_classes = |csprng: &mut OsRng| {
+            let mut r = csprng.gen::<f64>() * total_weight;
+            let counts = compositions.iter().find(|(_, w)| {
+                r -= w;
+                r < 0.0
+            });
+            let counts = counts.unwrap_or(&compositions[compositions.len() - 1]).0;
+            // We then randomly place the classes in the password.
+            let mut classes: Vec<usize> = (0..4).flat_map(|i| std::iter::repeat_n(i, counts[i])).collect();
+            classes.shuffle(csprng);
+            classes
+        };
+        for _ in 0..MAX_ATTEMPTS {
+            let classes = shuffled_classes(&mut csprng);
+            let password: String = classes.iter().map(|&i| sets[i][csprng.sample(Uniform::from(0..sets[i].len()))]).collect();
+            // Passwords that don't satisfy the consecutive chars or required chars rules are discarded, which doesn't introduce any bias.
+            if self.is_satisfied_by(&password) {
+                return Some(GeneratedPassword {
+                    password,
+                    entropy: self.entropy(),
+                });
+            }
+        }
+        for _ in 0..MAX_ATTEMPTS {
+            let classes = shuffled_classes(&mut csprng);
+            match self.build(&sets, &classes, &mut csprng) {
+                Some(password) if self.is_satisfied_by(&password) => {
+                    return Some(GeneratedPassword {
+                        password,
+                        entropy: self.entropy(),
+                    })
+                }
+                _ => (),
+            }
+        }
+        eprintln!("Password policy consecutive chars or required chars rules cannot be satisfied");
+        None
+    }
+
+    // Builds a password with the given class at each position: a char of each required set is placed first, and the other
+    // chars are selected among the chars that don't exceed the consecutive chars limit. Returns None if the classes don't
+    // leave room for the required chars, or if a position has no valid char.
+    fn build(&self, sets: &[Vec<char>], classes: &[usize], csprng: &mut OsRng) -> Option<String> {
+        let mut chars: Vec<Option<char>> = vec![None; classes.len()];
+        for set in &self.required {
+            // A char placed for a previous set may belong to this set too.
+            if chars.iter().flatten().any(|c| set.contains(c)) {
+                continue;
+            }
+            let positions: Vec<usize> = (0..classes.len())
+                .filter(|&p| chars[p].is_none() && sets[classes[p]].iter().any(|c| set.contains(c)))
+                .collect();
+            let &position = positions.choose(csprng)?;
+            let candidates: Vec<char> = sets[classes[position]].iter().filter(|c| set.contains(c)).copied().collect();
+            chars[position] = Some(*candidates.choose(csprng)?);
+        }
+        for position in 0..classes.len() {
+            if chars[position].is_some() {
+                continue;
+            }
+            let candidates: Vec<char> = sets[classes[position]].iter().filter(|&&c| self.fits(&chars, position, c)).copied().collect();
+            chars[position] = Some(*candidates.choose(csprng)?);
+        }
+        Some(chars.into_iter().flatten().collect())
+    }
+
+    // Whether placing `c` at `position` keeps the run of identical chars containing it within the consecutive chars limit.
+    fn fits(&self, chars: &[Option<char>], position: usize, c: char) -> bool {
+        let Some(max) = self.max_consecutive else { return true };
+        let before = chars[..position].iter().rev().take_while(|&&o| o == Some(c)).count();
+        let after = chars[position + 1..].iter().take_while(|&&o| o == Some(c)).count();
+        before + 1 + after <= max
+    }
+
+    // Returns every valid number of chars of each class, with the log of the number of passwords with that composition:
+    // length! / (n_0! * n_1! * n_2! * n_3!) * |class_0|^n_0 * |class_1|^n_1 * |class_2|^n_2 * |class_3|^n_3
+    fn ln_compositions(&self, sets: &[Vec<char>]) -> Vec<([usize; 4], f64)> {
+        let bounds: Vec<(usize, usize)> = (0..4)
+            .map(|i| {
+                let max = if sets[i].is_empty() { 0 } else { self.max[i].min(self.length) };
+                (self.min[i], max)
+            })
+            .collect();
+        // We compute weights in log-space to avoid overflows.
+        let ln_factorial: Vec<f64> = (0..=self.length)
+            .scan(0.0, |acc, n| {
+                if n > 0 {
+                    *acc += (n as f64).ln();
+                }
+                Some(*acc)
+            })
+            .collect();
+        let ln_sizes: Vec<f64> = sets.iter().map(|s| if s.is_empty() { 0.0 } else { (s.len() as f64).ln() }).collect();
+        let mut compositions: Vec<([usize; 4], f64)> = Vec::new();
+        for n0 in bounds[0].0..=bounds[0].1 {
+            for n1 in bounds[1].0..=bounds[1].1.min(self.length.saturating_sub(n0)) {
+                for n2 in bounds[2].0..=bounds[2].1.min(self.length.saturating_sub(n0 + n1)) {
+                    let Some(n3) = self.length.checked_sub(n0 + n1 + n2) else { continue };
+                    if n3 < bounds[3].0 || n3 > bounds[3].1 {
+                        continue;
+                    }
+                    let counts = [n0, n1, n2, n3];
+                    let ln_weight = ln_factorial[self.length] + (0..4).map(|i| counts[i] as f64 * ln_sizes[i] - ln_factorial[counts[i]]).sum::<f64>();
+                    compositions.push((counts, ln_weight));
+                }
+            }
+        }
+        compositions
+    }
+
+    fn compositions(&self, sets: &[Vec<char>]) -> Vec<([usize; 4], f64)> {
+        let compositions = self.ln_compositions(sets);
+        let max_ln_weight = compositions.iter().map(|(_, w)| *w).fold(f64::NEG_INFINITY, f64::max);
+        compositions.iter().map(|(counts, w)| (*counts, (w - max_ln_weight).exp())).collect()
+    }
+
+    /// Entropy (in bits) of the generated passwords: since they are uniformly distributed, this is log2 of the number of
+    /// passwords with a valid composition. The consecutive chars and required chars rules aren't taken into account, so
+    /// it's only an upper bound when they are set.
+    pub fn entropy(&self) -> f64 {
+        let sets: Vec<Vec<char>> = CharClass::ALL.iter().map(|&class| self.chars(class)).collect();
+        let compositions = self.ln_compositions(&sets);
+        if compositions.is_empty() {
+            return 0.0;
+        }
+        let max_ln_weight = compositions.iter().map(|(_, w)| *w).fold(f64::NEG_INFINITY, f64::max);
+        let ln_total = max_ln_weight + compositions.iter().map(|(_, w)| (w - max_ln_weight).exp()).sum::<f64>().ln();
+        ln_total / std::f64::consts::LN_2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy() {
+        let policy = PasswordPolicy::new(16);
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert_eq!(password.len(), 16);
+            assert!(policy.is_satisfied_by(&password));
+            assert!(password.chars().all(|c| PASSWORD_CHARS.contains(&c)));
+        }
+    }
+
+    #[test]
+    fn required_classes() {
+        let policy = PasswordPolicy::new(8)
+            .min(CharClass::Lowercase, 1)
+            .min(CharClass::Uppercase, 1)
+            .min(CharClass::Digit, 2)
+            .min(CharClass::Symbol, 1)
+            .max(CharClass::Symbol, 2);
+        for _ in 0..500 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password));
+            assert!(password.chars().any(|c| c.is_ascii_lowercase()));
+            assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(password.chars().filter(|c| c.is_ascii_digit()).count() >= 2);
+            let symbols = password.chars().filter(|c| !c.is_ascii_alphanumeric()).count();
+            assert!((1..=2).contains(&symbols));
+        }
+    }
+
+    #[test]
+    fn strict_policies() {
+        // Policies that would almost never be satisfied by randomly generated passwords.
+        let policy = PasswordPolicy::new(10).min(CharClass::Digit, 9).min(CharClass::Symbol, 1);
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password));
+            assert_eq!(password.chars().filter(|c| c.is_ascii_digit()).count(), 9);
+        }
+        let policy = PasswordPolicy::new(6).max(CharClass::Lowercase, 0).max(CharClass::Uppercase, 0).max(CharClass::Symbol, 0);
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(password.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn excluded_chars_and_symbols() {
+        let policy = PasswordPolicy::new(20).symbols("-_!<>&").exclude("<>&abc").min(CharClass::Symbol, 3);
+        for _ in 0..500 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password));
+            assert!(password.chars().all(|c| c.is_ascii_alphanumeric() || "-_!".contains(c)));
+            assert!(!password.contains(['a', 'b', 'c']));
+            assert!(password.chars().filter(|&c| "-_!".contains(c)).count() >= 3);
+        }
+    }
+
+    #[test]
+    fn custom_alphabet() {
+        let policy = PasswordPolicy::new(12).alphabet("abcABC123#").min(CharClass::Uppercase, 2).max_consecutive(1);
+        for _ in 0..500 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password));
+            assert!(password.chars().all(|c| "abcABC123#".contains(c)));
+            assert!(password.chars().filter(|c| c.is_ascii_uppercase()).count() >= 2);
+            let chars: Vec<char> = password.chars().collect();
+            assert!(chars.windows(2).all(|w| w[0] != w[1]));
+        }
+        // With a single char, there is no way to avoid repetitions.
+        assert_eq!(PasswordPolicy::new(3).alphabet("a").max_consecutive(2).generate(), None);
+        assert_eq!(PasswordPolicy::new(3).alphabet("a").max_consecutive(3).generate().unwrap().password, "aaa");
+    }
+
+    #[test]
+    fn required_chars() {
+        let policy = PasswordPolicy::new(12).require_one_of("-_").require_one_of("9#");
+        for _ in 0..500 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password));
+            assert!(password.contains(['-', '_']));
+            assert!(password.contains(['9', '#']));
+        }
+        assert!(!policy.is_satisfied_by("abcdefghijk9"));
+        assert_eq!(PasswordPolicy::new(12).require_one_of("-_").exclude("-_").generate(), None);
+        assert_eq!(PasswordPolicy::new(12).require_one_of("").generate(), None);
+    }
+
+    #[test]
+    fn unsatisfiable_policies() {
+        let test_cases = vec![
+            // too many required chars
+            PasswordPolicy::new(4).min(CharClass::Digit, 3).min(CharClass::Symbol, 2),
+            // not enough allowed chars
+            PasswordPolicy::new(8)
+                .max(CharClass::Lowercase, 2)
+                .max(CharClass::Uppercase, 2)
+                .max(CharClass::Digit, 2)
+                .max(CharClass::Symbol, 1),
+            // all chars of a required class are excluded
+            PasswordPolicy::new(8).min(CharClass::Digit, 1).exclude("123456789"),
+            // required symbols are not part of the alphabet
+            PasswordPolicy::new(8).alphabet("abcdef").min(CharClass::Symbol, 1),
+        ];
+        for t in test_cases {
+            assert_eq!(t.generate(), None);
+        }
+    }
+
+    #[test]
+    fn rarely_satisfied_policies() {
+        // Only "abab..." and "baba..." are valid, out of 2^32 passwords.
+        let policy = PasswordPolicy::new(32).alphabet("ab").max_consecutive(1);
+        for _ in 0..10 {
+            let password = policy.generate().unwrap().password;
+            assert!(password == "ab".repeat(16) || password == "ba".repeat(16), "{}", password);
+        }
+        // Random passwords would contain the 6 required symbols once every 10^11 draws or so.
+        let policy = PasswordPolicy::new(8).max_consecutive(2);
+        let policy = ["!", "#", "$", "%", "^", "&"].iter().fold(policy, |p, c| p.require_one_of(c));
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(policy.is_satisfied_by(&password), "{}", password);
+        }
+        let policy = PasswordPolicy::new(5).alphabet("a1").min(CharClass::Digit, 2).max_consecutive(1).require_one_of("a");
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(password == "1a1a1" || password == "a1a1a", "{}", password);
+        }
+    }
+
+    #[test]
+    fn uniform_distribution() {
+        // With a 2-char alphabet and one required digit, the 3 valid passwords should be equally likely.
+        let policy = PasswordPolicy::new(2).alphabet("a1").min(CharClass::Digit, 1);
+        let mut counts = [0usize; 3];
+        for _ in 0..3000 {
+            match policy.generate().unwrap().password.as_str() {
+                "a1" => counts[0] += 1,
+                "1a" => counts[1] += 1,
+                "11" => counts[2] += 1,
+                p => panic!("unexpected password: {}", p),
+            }
+        }
+        assert!(counts.iter().all(|&c| 800 < c && c < 1200), "{:?}", counts);
+        assert!((policy.generate().unwrap().entropy - 3f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn policy_entropy() {
+        // Without any requirement, chars are uniformly selected among the 83 distinct chars of the default alphabet.
+        assert!((PasswordPolicy::new(16).entropy() - 16.0 * 83f64.log2()).abs() < 1e-9);
+        // 4-digit PINs.
+        let policy = PasswordPolicy::new(4).alphabet("0123456789");
+        assert!((policy.entropy() - 10000f64.log2()).abs() < 1e-9);
+        // Requiring a digit removes the 9^4 passwords that only contain letters.
+        let policy = PasswordPolicy::new(4).alphabet("abcdefghi0").min(CharClass::Digit, 1);
+        assert!((policy.entropy() - (10000.0f64 - 6561.0).log2()).abs() < 1e-9);
+        assert_eq!(PasswordPolicy::new(4).min(CharClass::Digit, 5).entropy(), 0.0);
+    }
+}
@@ -0,0 +1,184 @@
+use rand::distributions::Uniform;
+use rand::rngs::OsRng;
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+
+use crate::password::GeneratedPassword;
+
+// Syllables are made of a consonant cluster followed by a vowel cluster. Clusters only contain consonants or vowels,
+// so a password can be split back into its clusters: every password is generated in exactly one way.
+// Like the default alphabet, we avoid chars that may be confused with other chars (l, 0 and 1).
+#[rustfmt::skip]
+const CONSONANTS: [&str; 32] = [
+    "b", "c", "d", "f", "g", "h", "j", "k", "m", "n", "p", "r", "s", "t", "v", "w",
+    "z", "br", "ch", "cr", "dr", "fr", "gr", "kr", "ph", "pr", "sh", "sk", "sp", "st", "th", "tr",
+];
+const VOWELS: [&str; 8] = ["a", "e", "i", "o", "u", "ai", "ea", "oo"];
+const DIGITS: &str = "23456789";
+const SYMBOLS: &str = "-_.!?@#$%&*+=";
+
+/// Options used to generate pronounceable passwords, which are easier to read and type (e.g. on a TV).
+/// Digits and symbols are inserted between syllables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PronounceableOptions {
+    syllables: usize,
+    digits: usize,
+    symbols: usize,
+    capitalize: bool,
+}
+
+impl PronounceableOptions {
+    pub fn new(syllables: usize) -> PronounceableOptions {
+        PronounceableOptions {
+            syllables,
+            digits: 0,
+            symbols: 0,
+            capitalize: false,
+        }
+    }
+
+    pub fn digits(mut self, count: usize) -> PronounceableOptions {
+        self.digits = count;
+        self
+    }
+
+    pub fn symbols(mut self, count: usize) -> PronounceableOptions {
+        self.symbols = count;
+        self
+    }
+
+    /// Capitalize the first letter (for websites that require an uppercase letter), which doesn't add any entropy.
+    pub fn capitalize(mut self, capitalize: bool) -> PronounceableOptions {
+        self.capitalize = capitalize;
+        self
+    }
+
+    /// Entropy (in bits) of the passwords generated with these options.
+    pub fn entropy(&self) -> f64 {
+        if self.syllables == 0 {
+            return 0.0;
+        }
+        let extras = self.digits + self.symbols;
+        let syllables = self.syllables as f64 * ((CONSONANTS.len() * VOWELS.len()) as f64).log2();
+        // Positions of the extra chars (the password always starts with a syllable), then positions of the digits.
+        let positions = n_choose_k_log2(self.syllables + extras - 1, extras) + n_choose_k_log2(extras, self.digits);
+        let chars = self.digits as f64 * (DIGITS.len() as f64).log2() + self.symbols as f64 * (SYMBOLS.len() as f64).log2();
+        syllables + positions + chars
+    }
+}
+
+fn n_choose_k_log2(n: usize, k: usize) -> f64 {
+    (1..=k).map(|i| ((n - k + i) as f64 / i as f64).log2()).sum()
+}
+
+/// Generate a pronounceable password, using the same source of randomness as `generate_password`.
+pub fn generate_pronounceable(options: &PronounceableOptions) -> GeneratedPassword {
+    if options.syllables == 0 {
+        return GeneratedPassword {
+            password: String::new(),
+            entropy: 0.0,
+        };
+    }
+    let mut csprng = OsRng {};
+    let consonants = Uniform::from(0..CONSONANTS.len());
+    let vowels = Uniform::from(0..VOWELS.len());
+    let mut syllables: Vec<String> = (0..options.syllables)
+        .map(|_| format!("{}{}", CONSONANTS[csprng.sample(consonants)], VOWELS[csprng.sample(vowels)]))
+        .collect();
+    if options.capitalize {
+        syllables[0] = syllables[0][..1].to_uppercase() + &syllables[0][1..];
+    }
+    // We select which chars are digits or symbols, then where they are inserted.
+    let mut extras: Vec<&str> = [DIGITS].repeat(options.digits);
+    extras.extend([SYMBOLS].repeat(options.symbols));
+    extras.shuffle(&mut csprng);
+    let tokens_len = options.syllables + extras.len();
+    let mut is_extra = vec![false; tokens_len];
+    for i in index::sample(&mut csprng, tokens_len - 1, extras.len()) {
+        is_extra[i + 1] = true;
+    }
+    let mut syllables = syllables.into_iter();
+    let mut extras = extras.into_iter();
+    let mut password = String::new();
+    for extra in is_extra {
+        if extra {
+            let chars: Vec<char> = extras.next().unwrap_or_default().chars().collect();
+            password.push(chars[csprng.gen_range(0..chars.len())]);
+        } else {
+            password.push_str(&syllables.next().unwrap_or_default());
+        }
+    }
+    GeneratedPassword {
+        password,
+        entropy: options.entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Split a password back into its clusters and extra chars.
+    fn split(password: &str) -> Vec<String> {
+        let kind = |c: char| {
+            if "aeiou".contains(c.to_ascii_lowercase()) {
+                0
+            } else if c.is_ascii_alphabetic() {
+                1
+            } else {
+                2
+            }
+        };
+        let chars: Vec<char> = password.chars().collect();
+        chars.chunk_by(|&a, &b| kind(a) == kind(b) && kind(a) != 2).map(|c| c.iter().collect()).collect()
+    }
+
+    #[test]
+    fn generate_pronounceable_passwords() {
+        let options = PronounceableOptions::new(6);
+        let p1 = generate_pronounceable(&options);
+        let p2 = generate_pronounceable(&options);
+        assert_ne!(p1.password, p2.password);
+        assert_eq!(p1.entropy, 48.0);
+        let clusters = split(&p1.password);
+        assert_eq!(clusters.len(), 12);
+        for pair in clusters.chunks(2) {
+            assert!(CONSONANTS.contains(&pair[0].as_str()), "{}", p1.password);
+            assert!(VOWELS.contains(&pair[1].as_str()), "{}", p1.password);
+        }
+        assert_eq!(generate_pronounceable(&PronounceableOptions::new(0)).password, "");
+    }
+
+    #[test]
+    fn digits_and_symbols() {
+        let options = PronounceableOptions::new(4).digits(2).symbols(1).capitalize(true);
+        for _ in 0..100 {
+            let p = generate_pronounceable(&options);
+            assert!(p.password.starts_with(|c: char| c.is_ascii_uppercase()), "{}", p.password);
+            assert_eq!(p.password.chars().filter(|c| DIGITS.contains(*c)).count(), 2);
+            assert_eq!(p.password.chars().filter(|c| SYMBOLS.contains(*c)).count(), 1);
+            let clusters = split(&p.password.to_lowercase());
+            let letters: Vec<&String> = clusters.iter().filter(|c| c.chars().all(|c| c.is_ascii_alphabetic())).collect();
+            assert_eq!(letters.len(), 8);
+        }
+        // 4 syllables (8 bits each), C(6, 3) positions for the extra chars, C(3, 2) positions for the digits.
+        let expected = 32.0 + 20f64.log2() + 3f64.log2() + 2.0 * 3.0 + 13f64.log2();
+        assert!((options.entropy() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn extra_chars_positions() {
+        // With 2 syllables and 1 digit, the digit is in the middle or at the end, with the same probability.
+        let options = PronounceableOptions::new(2).digits(1);
+        let mut middle = 0;
+        for _ in 0..1000 {
+            let p = generate_pronounceable(&options);
+            assert!(!p.password.starts_with(|c: char| c.is_ascii_digit()));
+            if !p.password.ends_with(|c: char| c.is_ascii_digit()) {
+                middle += 1;
+            }
+        }
+        assert!(400 < middle && middle < 600, "{}", middle);
+        assert!((options.entropy() - (16.0 + 1.0 + 3.0)).abs() < 1e-9);
+    }
+}
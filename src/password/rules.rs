@@ -0,0 +1,249 @@
+use crate::password::{CharClass, PasswordPolicy, PASSWORD_CHARS};
+
+// Length of generated passwords, unless the rules require shorter or longer passwords.
+const DEFAULT_LENGTH: usize = 20;
+
+const SPECIAL: &str = "-~!@#$%^&*_+=`|(){}[:;\"'<>,.? ]";
+
+impl PasswordPolicy {
+    /// Parse password requirements written in the passwordrules syntax, e.g. "minlength: 12; required: upper; allowed: [-_]".
+    /// Chars that are allowed or required by a named class (e.g. "upper") exclude confusable chars, like the default alphabet.
+    /// Specification can be found here: https://developer.apple.com/password-rules/
+    pub fn from_rules(rules: &str) -> Result<PasswordPolicy, String> {
+        let mut required: Vec<Vec<char>> = Vec::new();
+        let mut allowed: Vec<char> = Vec::new();
+        let mut min_length: Option<usize> = None;
+        let mut max_length: Option<usize> = None;
+        let mut max_consecutive: Option<usize> = None;
+        for rule in split_rules(rules)? {
+            let rule = rule.trim();
+            if rule.is_empty() {
+                continue;
+            }
+            let (name, value) = rule.split_once(':').ok_or_else(|| format!("invalid password rule: {}", rule))?;
+            let parse_number = || value.trim().parse::<usize>().map_err(|_| format!("invalid number in password rule: {}", rule));
+            match name.trim().to_lowercase().as_str() {
+                "required" => {
+                    let chars = parse_classes(value)?;
+                    allowed.extend(&chars);
+                    required.push(chars);
+                }
+                "allowed" => allowed.extend(parse_classes(value)?),
+                "minlength" => min_length = Some(min_length.unwrap_or(0).max(parse_number()?)),
+                "maxlength" => max_length = Some(max_length.unwrap_or(usize::MAX).min(parse_number()?)),
+                "max-consecutive" => max_consecutive = Some(max_consecutive.unwrap_or(usize::MAX).min(parse_number()?)),
+                // Unknown rules must be ignored.
+                _ => {}
+            }
+        }
+        if allowed.is_empty() {
+            allowed = ascii_printable();
+        }
+        allowed.retain(|c| !c.is_whitespace() && !c.is_control());
+        allowed.sort();
+        allowed.dedup();
+        let (min_length, max_length) = (min_length.unwrap_or(0), max_length.unwrap_or(usize::MAX));
+        if min_length > max_length {
+            return Err(format!("invalid password rules: minlength {} is greater than maxlength {}", min_length, max_length));
+        }
+        let mut policy = PasswordPolicy::new(DEFAULT_LENGTH.clamp(min_length, max_length)).alphabet(&allowed.iter().collect::<String>());
+        if let Some(count) = max_consecutive {
+            policy = policy.max_consecutive(count);
+        }
+        for set in required {
+            // Requiring a whole class doesn't need rejection sampling.
+            let set: Vec<char> = allowed.iter().filter(|c| set.contains(c)).copied().collect();
+            let class = CharClass::of(set.first().copied().unwrap_or(' '));
+            let class_chars: Vec<char> = allowed.iter().filter(|&&c| CharClass::of(c) == class).copied().collect();
+            if !set.is_empty() && set == class_chars {
+                policy = policy.min(class, 1);
+            } else {
+                policy = policy.require_one_of(&set.iter().collect::<String>());
+            }
+        }
+        Ok(policy)
+    }
+}
+
+// Rules are separated by ';', which may also appear in custom classes (e.g. "allowed: [;:]").
+fn split_rules(rules: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = rules.chars().collect();
+    let mut split = vec![String::new()];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '[' => {
+                let end = custom_class_end(&chars, i)?;
+                split.last_mut().unwrap().extend(&chars[i..=end]);
+                i = end;
+            }
+            ';' => split.push(String::new()),
+            c => split.last_mut().unwrap().push(c),
+        }
+        i += 1;
+    }
+    Ok(split)
+}
+
+// Returns the index of the ']' that closes the custom class starting at `start`. A ']' char in a custom class must be
+// its last char (e.g. "[-]]").
+fn custom_class_end(chars: &[char], start: usize) -> Result<usize, String> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == ']' && chars.get(i + 1) != Some(&']') {
+            return Ok(i);
+        }
+        i += 1;
+    }
+    Err(format!("unterminated custom character class: {}", chars[start..].iter().collect::<String>()))
+}
+
+// Parse a comma separated list of classes (e.g. "upper, digit, [-_]") and returns the chars they contain.
+fn parse_classes(value: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = value.chars().collect();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() || chars[i] == ',' {
+            i += 1;
+        } else if chars[i] == '[' {
+            let end = custom_class_end(&chars, i)?;
+            result.extend(&chars[i + 1..end]);
+            i = end + 1;
+        } else {
+            let name: String = chars[i..].iter().take_while(|&&c| !c.is_whitespace() && c != ',').collect();
+            i += name.chars().count();
+            result.extend(match name.to_lowercase().as_str() {
+                "upper" => filter_confusables('A'..='Z'),
+                "lower" => filter_confusables('a'..='z'),
+                "digit" => filter_confusables('0'..='9'),
+                "special" => SPECIAL.chars().collect(),
+                // We don't generate non-ascii chars.
+                "ascii-printable" | "unicode" => ascii_printable(),
+                _ => return Err(format!("unknown character class: {}", name)),
+            });
+        }
+    }
+    Ok(result)
+}
+
+fn filter_confusables(chars: impl Iterator<Item = char>) -> Vec<char> {
+    chars.filter(|c| PASSWORD_CHARS.contains(c)).collect()
+}
+
+fn ascii_printable() -> Vec<char> {
+    let mut chars = filter_confusables(('a'..='z').chain('A'..='Z').chain('0'..='9'));
+    chars.extend((' '..='~').filter(|c| !c.is_ascii_alphanumeric()));
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rules() {
+        let policy = PasswordPolicy::from_rules("required: upper; required: digit; allowed: [-_]; max-consecutive: 2; minlength: 12").unwrap();
+        assert_eq!(policy.length(), 20);
+        let alphabet: String = filter_confusables(('A'..='Z').chain('0'..='9')).into_iter().chain("-_".chars()).collect();
+        let expected = PasswordPolicy::new(20)
+            .alphabet(&alphabet)
+            .max_consecutive(2)
+            .min(CharClass::Uppercase, 1)
+            .min(CharClass::Digit, 1);
+        assert_eq!(policy, expected);
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(password.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_'));
+            assert!(password.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(password.chars().any(|c| c.is_ascii_digit()));
+        }
+        // Without any allowed or required chars, every ascii printable char is allowed.
+        let policy = PasswordPolicy::from_rules("maxlength: 8").unwrap();
+        assert_eq!(policy, PasswordPolicy::new(8).alphabet(&ascii_printable().iter().collect::<String>()));
+        assert_eq!(PasswordPolicy::from_rules("").unwrap().length(), 20);
+        assert_eq!(PasswordPolicy::from_rules("minlength: 32; minlength: 40").unwrap().length(), 40);
+        assert_eq!(PasswordPolicy::from_rules("MinLength: 4; maxlength: 10; maxlength: 6;").unwrap().length(), 6);
+        // Unknown rules are ignored.
+        assert_eq!(PasswordPolicy::from_rules("foo: bar; maxlength: 8"), PasswordPolicy::from_rules("maxlength: 8"));
+    }
+
+    #[test]
+    fn custom_classes() {
+        assert_eq!(split_rules("allowed: [;:]; minlength: 8").unwrap(), vec!["allowed: [;:]", " minlength: 8"]);
+        assert_eq!(parse_classes("[-]]").unwrap(), vec!['-', ']']);
+        assert_eq!(parse_classes("[abc], [-], digit").unwrap(), "abc-123456789".chars().collect::<Vec<char>>());
+        assert_eq!(parse_classes(" [ ]").unwrap(), vec![' ']);
+        // Requiring some symbols only.
+        let policy = PasswordPolicy::from_rules("required: lower; required: [!?]").unwrap();
+        for _ in 0..100 {
+            let password = policy.generate().unwrap().password;
+            assert!(password.chars().all(|c| c.is_ascii_lowercase() || c == '!' || c == '?'));
+            assert!(password.contains(['!', '?']));
+        }
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let test_cases = vec![
+            ("minlength: abc", "invalid number in password rule: minlength: abc"),
+            ("required: foo", "unknown character class: foo"),
+            ("allowed: [abc", "unterminated custom character class: [abc"),
+            ("minlength: 20; maxlength: 10", "invalid password rules: minlength 20 is greater than maxlength 10"),
+            ("required upper", "invalid password rule: required upper"),
+        ];
+        for (rules, error) in test_cases {
+            assert_eq!(PasswordPolicy::from_rules(rules), Err(String::from(error)));
+        }
+    }
+
+    #[test]
+    fn real_world_rules() {
+        // Rules published by websites, with the lengths they allow.
+        let test_cases = vec![
+            (
+                "minlength: 8; maxlength: 63; required: lower; required: upper; required: digit; allowed: ascii-printable;",
+                8,
+                63,
+            ),
+            (
+                "minlength: 8; maxlength: 20; max-consecutive: 3; required: lower; required: upper; required: digit; allowed: [-@#*()+={}/?~;,._];",
+                8,
+                20,
+            ),
+            ("minlength: 8; maxlength: 16; required: lower, upper; allowed: digit, special;", 8, 16),
+            ("minlength: 8; maxlength: 20; required: lower, upper; required: digit; allowed: [!#$%&*@^];", 8, 20),
+            ("minlength: 8; allowed: lower, upper, digit, [-!\"#$%&'()*+,./:;<=>?@[^_{|}~]];", 8, usize::MAX),
+            (
+                "minlength: 8; maxlength: 20; required: lower, upper; required: digit, [-!\"#$%&'()*+,./:;=?@[\\^_`{|}~];",
+                8,
+                20,
+            ),
+            (
+                "minlength: 8; maxlength: 12; max-consecutive: 2; required: lower; required: upper; required: digit; allowed: [-!\"#$%&'()*+,./:;<=>?@[^_`{|}~];",
+                8,
+                12,
+            ),
+            ("minlength: 6; maxlength: 6; required: digit;", 6, 6),
+            (
+                "minlength: 12; required: lower; required: upper; required: digit; required: special; max-consecutive: 1;",
+                12,
+                usize::MAX,
+            ),
+            (
+                "minlength: 8; maxlength: 32; max-consecutive: 2; required: lower; required: upper; required: digit; required: [!@#$%^&*];",
+                8,
+                32,
+            ),
+        ];
+        for (rules, min_length, max_length) in test_cases {
+            let policy = PasswordPolicy::from_rules(rules).unwrap();
+            assert!(min_length <= policy.length() && policy.length() <= max_length, "{}", rules);
+            for _ in 0..100 {
+                let password = policy.generate().unwrap().password;
+                assert!(policy.is_satisfied_by(&password), "{}: {}", rules, password);
+                assert!(password.chars().all(|c| c.is_ascii_graphic()), "{}: {}", rules, password);
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::crypto::hmac;
+use crate::crypto::keys::{derive_key, Purpose};
+use crate::hash::sha256::Sha256;
+use crate::hash::HashFunction;
+use crate::password::{GeneratedPassword, PasswordPolicy};
+use crate::Version;
+
+// Maximum number of candidate passwords we try before giving up, when the policy is hard to satisfy.
+const MAX_ATTEMPTS: usize = 10_000;
+
+/// Derive the password of a website from a master password, without storing anything (e.g. for emergency access).
+/// The same inputs always produce the same password: the counter should be incremented to rotate the password.
+/// Site names are case-insensitive. The entropy reported is the one of the policy, but the password can't be harder to
+/// guess than the master password.
+pub fn derive_site_password(master_password: &str, site: &str, login: &str, counter: u32, policy: &PasswordPolicy) -> Option<GeneratedPassword> {
+    // Argon2 parameters are frozen for each version, so derived passwords never change.
+    derive_site_password_with(Version::V2, master_password, site, login, counter, policy)
+}
+
+fn derive_site_password_with(version: Version, master_password: &str, site: &str, login: &str, counter: u32, policy: &PasswordPolicy) -> Option<GeneratedPassword> {
+    // Argon2 salts are limited in size, so we hash the (length-prefixed) inputs.
+    let site = site.to_lowercase();
+    let mut salt = Sha256::new();
+    salt.update(&(site.len() as u32).to_be_bytes());
+    salt.update(site.as_bytes());
+    salt.update(&(login.len() as u32).to_be_bytes());
+    salt.update(login.as_bytes());
+    salt.update(&counter.to_be_bytes());
+    let key = derive_key(version, master_password, &salt.finalize(), Purpose::Site)?;
+    let alphabet = policy.allowed_chars();
+    if alphabet.is_empty() {
+        eprintln!("Password policy doesn't allow any char");
+        return None;
+    }
+    // We draw chars uniformly from the allowed chars, and discard candidates that don't satisfy the policy. This is
+    // slower than what the policy does with random passwords, but doesn't depend on floating point arithmetic.
+    let mut stream = KeyStream::new(key);
+    for _ in 0..MAX_ATTEMPTS {
+        let password: String = (0..policy.length()).map(|_| alphabet[stream.uniform(alphabet.len() as u32) as usize]).collect();
+        if policy.is_satisfied_by(&password) {
+            return Some(GeneratedPassword {
+                password,
+                entropy: policy.entropy(),
+            });
+        }
+    }
+    eprintln!("Password policy is too strict to derive site passwords");
+    None
+}
+
+// Deterministic stream of pseudo-random bytes: block i is HMAC-SHA256(key, i), with i encoded as a big-endian u64.
+struct KeyStream {
+    key: [u8; 32],
+    block_index: u64,
+    block: [u8; 32],
+    position: usize,
+}
+
+impl KeyStream {
+    fn new(key: [u8; 32]) -> KeyStream {
+        KeyStream {
+            key,
+            block_index: 0,
+            block: [0u8; 32],
+            position: 32,
+        }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        for b in bytes.iter_mut() {
+            if self.position == self.block.len() {
+                self.block = hmac::authenticate::<Sha256>(&self.key, &self.block_index.to_be_bytes());
+                self.block_index += 1;
+                self.position = 0;
+            }
+            *b = self.block[self.position];
+            self.position += 1;
+        }
+        u32::from_be_bytes(bytes)
+    }
+
+    // Returns a uniformly distributed value in [0, n): we discard values that would introduce a modulo bias.
+    fn uniform(&mut self, n: u32) -> u32 {
+        let limit = u32::MAX - (u32::MAX % n + 1) % n;
+        loop {
+            let x = self.next_u32();
+            if x <= limit {
+                return x % n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::password::CharClass;
+
+    #[test]
+    fn key_stream() {
+        let mut stream = KeyStream::new([42u8; 32]);
+        let first_block = hmac::authenticate::<Sha256>(&[42u8; 32], &0u64.to_be_bytes());
+        let second_block = hmac::authenticate::<Sha256>(&[42u8; 32], &1u64.to_be_bytes());
+        let bytes: Vec<u8> = (0..16).flat_map(|_| stream.next_u32().to_be_bytes()).collect();
+        assert_eq!(bytes[..32], first_block);
+        assert_eq!(bytes[32..], second_block);
+        // Every value is valid when n is a power of 2.
+        let mut stream = KeyStream::new([42u8; 32]);
+        assert_eq!(stream.uniform(256), u32::from_be_bytes(first_block[..4].try_into().unwrap()) % 256);
+        assert_eq!(stream.uniform(1), 0);
+        let mut counts = [0usize; 3];
+        (0..3000).for_each(|_| counts[stream.uniform(3) as usize] += 1);
+        assert!(counts.iter().all(|&c| 800 < c && c < 1200), "{:?}", counts);
+    }
+
+    #[test]
+    fn derive_site_passwords() {
+        let policy = PasswordPolicy::new(16)
+            .min(CharClass::Lowercase, 1)
+            .min(CharClass::Uppercase, 1)
+            .min(CharClass::Digit, 1)
+            .min(CharClass::Symbol, 1);
+        let derive = |master: &str, site: &str, login: &str, counter: u32| derive_site_password_with(Version::Test, master, site, login, counter, &policy).unwrap().password;
+        let password = derive("correct horse battery staple", "example.com", "alice@example.com", 1);
+        assert!(policy.is_satisfied_by(&password));
+        assert_eq!(password, derive("correct horse battery staple", "Example.COM", "alice@example.com", 1));
+        assert_ne!(password, derive("correct horse battery staple", "example.com", "alice@example.com", 2));
+        assert_ne!(password, derive("correct horse battery staple", "example.org", "alice@example.com", 1));
+        assert_ne!(password, derive("correct horse battery staple", "example.com", "bob@example.com", 1));
+        assert_ne!(password, derive("correct horse battery stapler", "example.com", "alice@example.com", 1));
+        // Inputs are length-prefixed, so they can't be shifted between fields.
+        assert_ne!(derive("master", "example.com", "alice", 1), derive("master", "example.coma", "lice", 1));
+    }
+
+    #[test]
+    fn frozen_test_vectors() {
+        // These passwords must never change: users rely on them to log into websites.
+        // They have been cross-checked with an independent implementation (using Python's cryptography library).
+        let policy = PasswordPolicy::new(16)
+            .min(CharClass::Lowercase, 1)
+            .min(CharClass::Uppercase, 1)
+            .min(CharClass::Digit, 1)
+            .min(CharClass::Symbol, 1);
+        let pin = PasswordPolicy::new(6).alphabet("0123456789");
+        let test_cases = vec![
+            (
+                Version::Test,
+                "correct horse battery staple",
+                "example.com",
+                "alice@example.com",
+                1,
+                &policy,
+                "eX5r&$?)HcMpKox5",
+            ),
+            (
+                Version::Test,
+                "correct horse battery staple",
+                "example.com",
+                "alice@example.com",
+                2,
+                &policy,
+                "rp4~Ze:N]9]DVCbC",
+            ),
+            (Version::Test, "tH1s m4Y b3 a str0ng#r p4sS0rD", "cybele.io", "bob", 42, &policy, "x1J:sJiN6c:X#A+P"),
+            (Version::Test, "master", "Bank.com", "alice", 7, &pin, "237459"),
+        ];
+        for (version, master, site, login, counter, policy, expected) in test_cases {
+            let derived = derive_site_password_with(version, master, site, login, counter, policy).unwrap();
+            assert_eq!(derived.password, expected);
+        }
+        // With the Argon2 parameters used by the public function.
+        let derived = derive_site_password("correct horse battery staple", "example.com", "alice@example.com", 1, &policy).unwrap();
+        assert_eq!(derived.password, "Z#~5<oq-P!A47xvy");
+    }
+
+    #[test]
+    fn strict_policies() {
+        let pin = PasswordPolicy::new(6).alphabet("0123456789");
+        let derived = derive_site_password_with(Version::Test, "master", "bank.com", "alice", 1, &pin).unwrap();
+        assert!(derived.password.chars().all(|c| c.is_ascii_digit()));
+        assert!((derived.entropy - 1_000_000f64.log2()).abs() < 1e-9);
+        assert_eq!(
+            derive_site_password_with(Version::Test, "master", "bank.com", "alice", 1, &PasswordPolicy::new(6).alphabet("")),
+            None
+        );
+        assert_eq!(
+            derive_site_password_with(Version::Test, "master", "bank.com", "alice", 1, &PasswordPolicy::new(6).min(CharClass::Digit, 7)),
+            None
+        );
+    }
+}
@@ -0,0 +1,1062 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::password::passphrase::{parse_wordlist, WORDLIST};
+
+// This is a simplified version of zxcvbn: we find every pattern that the password contains, and estimate the number
+// of guesses an attacker needs with the sequence of non-overlapping patterns that minimizes it.
+// Specification can be found here: https://www.usenix.org/conference/usenixsecurity16/technical-sessions/presentation/wheeler
+
+// Dictionaries are ranked by frequency (the most common entries come first). They only contain the few hundred most
+// common passwords and English words, so rarer words are estimated as random chars, which overestimates the number of
+// guesses of passwords made of them.
+const PASSWORDS: &str = include_str!("dictionaries/passwords.txt");
+const ENGLISH: &str = include_str!("dictionaries/english.txt");
+
+// Finding the most guessable sequence takes cubic time, so longer passwords are split into segments that are analyzed
+// separately. Long repeats and sequences are found in the whole password first, and are kept in one segment.
+const MAX_LENGTH: usize = 64;
+// Longer dictionary words would only slow down the search.
+const MAX_WORD_LENGTH: usize = 32;
+const MAX_L33T_VARIANTS: usize = 256;
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+const L33T_TABLE: [(char, &str); 12] = [
+    ('a', "4@"),
+    ('b', "8"),
+    ('c', "({[<"),
+    ('e', "3"),
+    ('g', "69"),
+    ('i', "1!|"),
+    ('l', "1|7"),
+    ('o', "0"),
+    ('s', "$5"),
+    ('t', "+7"),
+    ('x', "%"),
+    ('z', "2"),
+];
+
+// Keys are listed with their shifted variant. Rows of a (slanted) qwerty keyboard are shifted by half a key.
+const QWERTY: [&str; 4] = [
+    "`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) -_ =+",
+    "qQ wW eE rR tT yY uU iI oO pP [{ ]} \\|",
+    "aA sS dD fF gG hH jJ kK lL ;: '\"",
+    "zZ xX cC vV bB nN mM ,< .> /?",
+];
+// Keys of an (aligned) keypad: keys that span several cells are repeated.
+const KEYPAD: [&str; 5] = [". / * -", "7 8 9 +", "4 5 6 +", "1 2 3 .", "0 0 . ."];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Dictionary {
+        dictionary: &'static str,
+        word: String,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+    },
+    /// Adjacent keys on a keyboard (e.g. "qwerty" or "zxcvfr").
+    Spatial {
+        keyboard: &'static str,
+        turns: usize,
+        shifted: usize,
+    },
+    /// A token repeated several times (e.g. "aaaa" or "abcabc").
+    Repeat {
+        base: String,
+        count: usize,
+    },
+    /// Chars with a constant difference between them (e.g. "abcd" or "9753").
+    Sequence {
+        ascending: bool,
+    },
+    Date {
+        year: i32,
+        month: u32,
+        day: u32,
+        separator: Option<char>,
+    },
+    Year {
+        year: i32,
+    },
+    /// Chars that don't match any pattern.
+    Bruteforce,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    /// Index of the first char of the token in the password.
+    pub start: usize,
+    pub token: String,
+    pub pattern: Pattern,
+    pub guesses: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Feedback {
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    /// From 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    /// Estimated number of guesses needed to find the password.
+    pub guesses: f64,
+    /// Patterns used to compute the estimate, in the order they appear in the password.
+    pub matches: Vec<Match>,
+    pub feedback: Feedback,
+}
+
+/// Estimate how hard it is to guess a password chosen by a user.
+/// User inputs (e.g. the vault or item name) are used as an additional dictionary.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let user_inputs: Vec<String> = user_inputs.iter().map(|s| s.to_lowercase()).filter(|s| !s.is_empty()).collect();
+    let user_dictionary: HashMap<&str, usize> = user_inputs.iter().enumerate().map(|(i, s)| (s.as_str(), i + 1)).rev().collect();
+    let matches = if chars.len() <= MAX_LENGTH {
+        most_guessable_sequence(&chars, &find_matches(&chars, &user_dictionary))
+    } else {
+        long_password_matches(&chars, &user_dictionary)
+    };
+    let guesses = sequence_guesses(&matches);
+    let score = score(guesses);
+    let feedback = feedback(score, &matches);
+    Strength {
+        score,
+        guesses,
+        matches,
+        feedback,
+    }
+}
+
+// Splits a password longer than MAX_LENGTH into long runs (repeats or sequences longer than half a segment) and
+// segments of at most MAX_LENGTH chars between them, and returns the concatenation of their most guessable sequences.
+fn long_password_matches(chars: &[char], user_dictionary: &HashMap<&str, usize>) -> Vec<Match> {
+    let mut runs: Vec<Match> = Vec::new();
+    let mut candidates = long_repeats(chars, user_dictionary);
+    sequence_matches(chars, &mut candidates);
+    candidates.retain(|m| m.token.chars().count() > MAX_LENGTH / 2);
+    // Longer runs are kept first.
+    candidates.sort_by_key(|m| std::cmp::Reverse(m.token.chars().count()));
+    for m in candidates {
+        let end = m.start + m.token.chars().count();
+        if runs.iter().all(|r| end <= r.start || r.start + r.token.chars().count() <= m.start) {
+            runs.push(m);
+        }
+    }
+    runs.sort_by_key(|m| m.start);
+    let mut matches = Vec::new();
+    let analyze = |matches: &mut Vec<Match>, start: usize, end: usize| {
+        for segment_start in (start..end).step_by(MAX_LENGTH) {
+            let segment = &chars[segment_start..end.min(segment_start + MAX_LENGTH)];
+            let sequence = most_guessable_sequence(segment, &find_matches(segment, user_dictionary));
+            matches.extend(sequence.into_iter().map(|m| Match {
+                start: segment_start + m.start,
+                ..m
+            }));
+        }
+    };
+    let mut position = 0;
+    for run in runs {
+        analyze(&mut matches, position, run.start);
+        position = run.start + run.token.chars().count();
+        matches.push(run);
+    }
+    analyze(&mut matches, position, chars.len());
+    // No match covers the whole password, so every match is a submatch.
+    for m in &mut matches {
+        let min_guesses = if m.token.chars().count() == 1 {
+            MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+        } else {
+            MIN_SUBMATCH_GUESSES_MULTI_CHAR
+        };
+        m.guesses = m.guesses.max(min_guesses);
+    }
+    matches
+}
+
+// Returns the maximal repeats of bases of at most MAX_LENGTH chars in a password of any length, in linear time.
+fn long_repeats(chars: &[char], user_dictionary: &HashMap<&str, usize>) -> Vec<Match> {
+    // Runs (start, end, base length), where chars[start..end] is made of at least 2 repetitions of its base.
+    let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+    for base_len in 1..=MAX_LENGTH.min(chars.len() / 2) {
+        let mut i = 0;
+        while i + base_len < chars.len() {
+            let mut j = i;
+            while j + base_len < chars.len() && chars[j] == chars[j + base_len] {
+                j += 1;
+            }
+            // chars[i..j + base_len] repeats chars[i..i + base_len]: we only keep whole repetitions.
+            let count = (j - i + base_len) / base_len;
+            let end = i + count * base_len;
+            // Repetitions of a shorter base were already found (e.g. "abab" when looking for "abababab").
+            if count >= 2 && !runs.iter().any(|&(s, e, _)| s <= i && end <= e) {
+                runs.push((i, end, base_len));
+            }
+            i = j + 1;
+        }
+    }
+    runs.into_iter()
+        .map(|(start, end, base_len)| {
+            let base = &chars[start..start + base_len];
+            let count = (end - start) / base_len;
+            let guesses = sequence_guesses(&most_guessable_sequence(base, &find_matches(base, user_dictionary)));
+            Match {
+                start,
+                token: chars[start..end].iter().collect(),
+                pattern: Pattern::Repeat {
+                    base: base.iter().collect(),
+                    count,
+                },
+                guesses: guesses * count as f64,
+            }
+        })
+        .collect()
+}
+
+fn score(guesses: f64) -> u8 {
+    // We add a small delta to the thresholds, to avoid ambiguity around exact powers of 10.
+    let delta = 5.0;
+    if guesses < 1e3 + delta {
+        0
+    } else if guesses < 1e6 + delta {
+        1
+    } else if guesses < 1e8 + delta {
+        2
+    } else if guesses < 1e10 + delta {
+        3
+    } else {
+        4
+    }
+}
+
+fn find_matches(chars: &[char], user_dictionary: &HashMap<&str, usize>) -> Vec<Match> {
+    let mut matches = Vec::new();
+    dictionary_matches(chars, user_dictionary, &mut matches);
+    spatial_matches(chars, &mut matches);
+    repeat_matches(chars, user_dictionary, &mut matches);
+    sequence_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    matches
+}
+
+struct Dictionary {
+    name: &'static str,
+    ranks: HashMap<&'static str, usize>,
+}
+
+fn dictionaries() -> &'static [Dictionary] {
+    static DICTIONARIES: OnceLock<Vec<Dictionary>> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let ranked = |name: &'static str, words: &'static str| Dictionary {
+            name,
+            // If a word appears several times, we keep its best rank.
+            ranks: words
+                .lines()
+                .filter(|w| !w.is_empty())
+                .enumerate()
+                .map(|(i, w)| (w, i + 1))
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect(),
+        };
+        // The words of the passphrase wordlist are all equally likely.
+        let wordlist_len = parse_wordlist(WORDLIST).count();
+        vec![
+            ranked("passwords", PASSWORDS),
+            ranked("english", ENGLISH),
+            Dictionary {
+                name: "wordlist",
+                ranks: parse_wordlist(WORDLIST).map(|w| (w, wordlist_len)).collect(),
+            },
+        ]
+    })
+}
+
+fn dictionary_matches(chars: &[char], user_dictionary: &HashMap<&str, usize>, matches: &mut Vec<Match>) {
+    let lowercase: Vec<char> = chars.iter().map(|&c| to_lowercase(c)).collect();
+    for i in 0..chars.len() {
+        for j in i + 1..=chars.len().min(i + MAX_WORD_LENGTH) {
+            let token: String = chars[i..j].iter().collect();
+            let word: String = lowercase[i..j].iter().collect();
+            let reversed: String = lowercase[i..j].iter().rev().collect();
+            let mut variants = vec![(word.clone(), false, false)];
+            if reversed != word {
+                variants.push((reversed, true, false));
+            }
+            variants.extend(unl33t(&lowercase[i..j]).into_iter().map(|w| (w, false, true)));
+            for (candidate, reversed, l33t) in variants {
+                let ranks = dictionaries().iter().map(|d| (d.name, d.ranks.get(candidate.as_str())));
+                let ranks = ranks.chain(std::iter::once(("user_inputs", user_dictionary.get(candidate.as_str()))));
+                for (dictionary, rank) in ranks {
+                    let Some(&rank) = rank else { continue };
+                    let mut guesses = rank as f64 * uppercase_variations(&chars[i..j]);
+                    if reversed {
+                        guesses *= 2.0;
+                    }
+                    if l33t {
+                        guesses *= l33t_variations(&lowercase[i..j], &candidate);
+                    }
+                    matches.push(Match {
+                        start: i,
+                        token: token.clone(),
+                        pattern: Pattern::Dictionary {
+                            dictionary,
+                            word: candidate.clone(),
+                            rank,
+                            reversed,
+                            l33t,
+                        },
+                        guesses,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn to_lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// Returns every word that the token may represent with l33t substitutions (at least one char is substituted).
+fn unl33t(token: &[char]) -> Vec<String> {
+    if token.len() < 2 {
+        return Vec::new();
+    }
+    let mut variants: Vec<(String, bool)> = vec![(String::new(), false)];
+    for &c in token {
+        let letters: Vec<char> = L33T_TABLE.iter().filter(|(_, subs)| subs.contains(c)).map(|&(letter, _)| letter).collect();
+        if letters.is_empty() {
+            variants.iter_mut().for_each(|(v, _)| v.push(c));
+            continue;
+        }
+        if variants.len() * (letters.len() + 1) > MAX_L33T_VARIANTS {
+            return Vec::new();
+        }
+        variants = variants
+            .into_iter()
+            .flat_map(|(v, substituted)| {
+                let mut next = vec![(format!("{}{}", v, c), substituted)];
+                next.extend(letters.iter().map(|&letter| (format!("{}{}", v, letter), true)));
+                next
+            })
+            .collect();
+    }
+    variants.into_iter().filter(|(_, substituted)| *substituted).map(|(v, _)| v).collect()
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k).fold(1.0, |acc, i| acc * (n - k + i) as f64 / i as f64)
+}
+
+// Number of ways to choose which chars are of the first kind, knowing that there are `a` and `b` chars of each kind.
+fn variations(a: usize, b: usize) -> f64 {
+    (1..=a.min(b)).map(|i| n_choose_k(a + b, i)).sum()
+}
+
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    // Capitalizing the first or last letter or every letter is very common.
+    let first_only = token[0].is_uppercase() && upper == 1;
+    let last_only = token[token.len() - 1].is_uppercase() && upper == 1;
+    if first_only || last_only || lower == 0 {
+        return 2.0;
+    }
+    variations(upper, lower)
+}
+
+fn l33t_variations(token: &[char], word: &str) -> f64 {
+    let mut substitutions: Vec<(char, char)> = token.iter().zip(word.chars()).filter(|&(&a, b)| a != b).map(|(&a, b)| (a, b)).collect();
+    substitutions.sort();
+    substitutions.dedup();
+    substitutions
+        .iter()
+        .map(|&(sub, letter)| {
+            let substituted = token.iter().zip(word.chars()).filter(|&(&a, b)| a == sub && b == letter).count();
+            let unsubstituted = token.iter().filter(|&&c| c == letter).count();
+            if unsubstituted == 0 {
+                2.0
+            } else {
+                variations(substituted, unsubstituted)
+            }
+        })
+        .product()
+}
+
+struct Keyboard {
+    name: &'static str,
+    // Position of each key, and whether the char is the shifted variant of the key.
+    keys: HashMap<char, (Vec<(i32, i32)>, bool)>,
+    slanted: bool,
+}
+
+impl Keyboard {
+    fn neighbors(&self, position: (i32, i32)) -> Vec<(i32, i32)> {
+        let (r, c) = position;
+        if self.slanted {
+            vec![(r, c - 1), (r - 1, c), (r - 1, c + 1), (r, c + 1), (r + 1, c), (r + 1, c - 1)]
+        } else {
+            (-1..=1).flat_map(|dr| (-1..=1).map(move |dc| (r + dr, c + dc))).filter(|&p| p != position).collect()
+        }
+    }
+
+    // Returns the direction from a to b, if they are adjacent.
+    fn direction(&self, a: char, b: char) -> Option<(i32, i32)> {
+        let (positions_a, _) = self.keys.get(&a)?;
+        let (positions_b, _) = self.keys.get(&b)?;
+        for &pa in positions_a {
+            if positions_b.contains(&pa) {
+                return None;
+            }
+            if let Some(&pb) = self.neighbors(pa).iter().find(|p| positions_b.contains(p)) {
+                return Some((pb.0 - pa.0, pb.1 - pa.1));
+            }
+        }
+        None
+    }
+
+    fn average_degree(&self) -> f64 {
+        let mut keys: Vec<&Vec<(i32, i32)>> = self.keys.values().map(|(positions, _)| positions).collect();
+        keys.sort();
+        keys.dedup();
+        let degree = |positions: &Vec<(i32, i32)>| {
+            keys.iter()
+                .filter(|&&other| other != positions && positions.iter().any(|&p| self.neighbors(p).iter().any(|n| other.contains(n))))
+                .count()
+        };
+        keys.iter().map(|positions| degree(positions) as f64).sum::<f64>() / keys.len() as f64
+    }
+
+    fn starting_positions(&self) -> f64 {
+        let mut keys: Vec<&Vec<(i32, i32)>> = self.keys.values().map(|(positions, _)| positions).collect();
+        keys.sort();
+        keys.dedup();
+        keys.len() as f64
+    }
+}
+
+fn keyboards() -> &'static [Keyboard] {
+    static KEYBOARDS: OnceLock<Vec<Keyboard>> = OnceLock::new();
+    KEYBOARDS.get_or_init(|| {
+        let mut qwerty: HashMap<char, (Vec<(i32, i32)>, bool)> = HashMap::new();
+        for (r, row) in QWERTY.iter().enumerate() {
+            for (c, key) in row.split(' ').enumerate() {
+                for (shifted, k) in key.chars().enumerate() {
+                    qwerty.insert(k, (vec![(r as i32, c as i32)], shifted == 1));
+                }
+            }
+        }
+        let mut keypad: HashMap<char, (Vec<(i32, i32)>, bool)> = HashMap::new();
+        for (r, row) in KEYPAD.iter().enumerate() {
+            for (c, k) in row.split(' ').filter_map(|k| k.chars().next()).enumerate() {
+                keypad.entry(k).or_insert((Vec::new(), false)).0.push((r as i32, c as i32));
+            }
+        }
+        vec![
+            Keyboard {
+                name: "qwerty",
+                keys: qwerty,
+                slanted: true,
+            },
+            Keyboard {
+                name: "keypad",
+                keys: keypad,
+                slanted: false,
+            },
+        ]
+    })
+}
+
+fn spatial_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for keyboard in keyboards() {
+        let mut i = 0;
+        while i < chars.len() {
+            let mut j = i + 1;
+            let mut turns = 0;
+            let mut last_direction = None;
+            while j < chars.len() {
+                let Some(direction) = keyboard.direction(chars[j - 1], chars[j]) else { break };
+                if last_direction != Some(direction) {
+                    turns += 1;
+                    last_direction = Some(direction);
+                }
+                j += 1;
+            }
+            if j - i >= 3 {
+                let shifted = chars[i..j].iter().filter(|c| keyboard.keys.get(c).is_some_and(|(_, shifted)| *shifted)).count();
+                matches.push(Match {
+                    start: i,
+                    token: chars[i..j].iter().collect(),
+                    pattern: Pattern::Spatial {
+                        keyboard: keyboard.name,
+                        turns,
+                        shifted,
+                    },
+                    guesses: spatial_guesses(keyboard, j - i, turns, shifted),
+                });
+            }
+            i = if j - i >= 3 { j } else { i + 1 };
+        }
+    }
+}
+
+fn spatial_guesses(keyboard: &Keyboard, len: usize, turns: usize, shifted: usize) -> f64 {
+    let starts = keyboard.starting_positions();
+    let degree = keyboard.average_degree();
+    let mut guesses = 0.0;
+    // We consider every pattern of the same length with at most the same number of turns.
+    for i in 2..=len {
+        for j in 1..=turns.min(i - 1) {
+            guesses += n_choose_k(i - 1, j - 1) * starts * degree.powi(j as i32);
+        }
+    }
+    let unshifted = len - shifted;
+    if shifted > 0 {
+        guesses *= if unshifted == 0 { 2.0 } else { variations(shifted, unshifted) };
+    }
+    guesses
+}
+
+fn repeat_matches(chars: &[char], user_dictionary: &HashMap<&str, usize>, matches: &mut Vec<Match>) {
+    let mut base_guesses: HashMap<String, f64> = HashMap::new();
+    for i in 0..chars.len() {
+        for base_len in 1..=(chars.len() - i) / 2 {
+            let base = &chars[i..i + base_len];
+            // We only consider the longest repetition of bases that aren't themselves repetitions.
+            if i >= base_len && chars[i - base_len..i] == *base || (1..base_len).any(|p| base_len.is_multiple_of(p) && base[p..] == base[..base_len - p]) {
+                continue;
+            }
+            let count = 1 + (1..).take_while(|k| chars.get(i + k * base_len..i + (k + 1) * base_len) == Some(base)).count();
+            if count < 2 {
+                continue;
+            }
+            let base: String = base.iter().collect();
+            let guesses = *base_guesses.entry(base.clone()).or_insert_with(|| {
+                let base_chars: Vec<char> = base.chars().collect();
+                sequence_guesses(&most_guessable_sequence(&base_chars, &find_matches(&base_chars, user_dictionary)))
+            });
+            matches.push(Match {
+                start: i,
+                token: chars[i..i + count * base_len].iter().collect(),
+                pattern: Pattern::Repeat { base, count },
+                guesses: guesses * count as f64,
+            });
+        }
+    }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut i = 0;
+    while i + 2 < chars.len() {
+        let delta = chars[i + 1] as i64 - chars[i] as i64;
+        let mut j = i + 1;
+        while j < chars.len() && chars[j] as i64 - chars[j - 1] as i64 == delta {
+            j += 1;
+        }
+        if j - i >= 3 && delta != 0 && delta.abs() <= 5 {
+            // Sequences that start with an obvious char are guessed first.
+            let first = chars[i];
+            let mut base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push(Match {
+                start: i,
+                token: chars[i..j].iter().collect(),
+                pattern: Pattern::Sequence { ascending: delta > 0 },
+                guesses: base * (j - i) as f64,
+            });
+        }
+        i = if j - i >= 3 { j } else { i + 1 };
+    }
+}
+
+fn reference_year() -> i32 {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    1970 + (seconds / 31_556_952) as i32
+}
+
+fn two_digit_year(year: i32) -> i32 {
+    if year > 50 {
+        1900 + year
+    } else {
+        2000 + year
+    }
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let reference_year = reference_year();
+    let year_guesses = |year: i32| ((year - reference_year).abs() as f64).max(MIN_YEAR_SPACE);
+    for i in 0..chars.len() {
+        // Years (e.g. "1987").
+        if let Some(token) = chars.get(i..i + 4) {
+            let token: String = token.iter().collect();
+            if let Ok(year) = token.parse::<i32>() {
+                if (1900..=2099).contains(&year) && token.chars().all(|c| c.is_ascii_digit()) {
+                    matches.push(Match {
+                        start: i,
+                        token,
+                        pattern: Pattern::Year { year },
+                        guesses: year_guesses(year),
+                    });
+                }
+            }
+        }
+        // Dates with or without separators (e.g. "19/04/1987" or "870419").
+        for j in i + 4..=chars.len().min(i + 10) {
+            let token = &chars[i..j];
+            let separators: Vec<char> = token.iter().filter(|c| !c.is_ascii_digit()).copied().collect();
+            let separator = match separators[..] {
+                [] if token.len() <= 8 => None,
+                [a, b] if a == b && " -/\\_.".contains(a) => Some(a),
+                _ => continue,
+            };
+            let groups: Vec<Vec<String>> = match separator {
+                Some(s) => {
+                    let parts: Vec<String> = token.iter().collect::<String>().split(s).map(String::from).collect();
+                    vec![parts]
+                }
+                None => {
+                    let digits: String = token.iter().collect();
+                    let mut splits = Vec::new();
+                    for a in 1..digits.len() {
+                        for b in a + 1..digits.len() {
+                            splits.push(vec![digits[..a].to_string(), digits[a..b].to_string(), digits[b..].to_string()]);
+                        }
+                    }
+                    splits
+                }
+            };
+            let best = groups
+                .iter()
+                .filter_map(|parts| parse_date(parts))
+                .min_by_key(|&(year, _, _)| (year - reference_year).abs());
+            if let Some((year, month, day)) = best {
+                let mut guesses = 365.0 * year_guesses(year);
+                if separator.is_some() {
+                    guesses *= 4.0;
+                }
+                matches.push(Match {
+                    start: i,
+                    token: token.iter().collect(),
+                    pattern: Pattern::Date { year, month, day, separator },
+                    guesses,
+                });
+            }
+        }
+    }
+}
+
+// Returns the year, month and day represented by 3 groups of digits (the year comes first or last).
+fn parse_date(parts: &[String]) -> Option<(i32, u32, u32)> {
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let candidates = [
+        (&parts[0], &parts[1], &parts[2]),
+        (&parts[2], &parts[0], &parts[1]),
+        (&parts[2], &parts[1], &parts[0]),
+        (&parts[0], &parts[2], &parts[1]),
+    ];
+    candidates.iter().find_map(|&(year, a, b)| {
+        if a.len() > 2 || b.len() > 2 {
+            return None;
+        }
+        let year = match year.len() {
+            2 => two_digit_year(year.parse().ok()?),
+            4 => year.parse().ok()?,
+            _ => return None,
+        };
+        let (a, b): (u32, u32) = (a.parse().ok()?, b.parse().ok()?);
+        if !(1000..=2050).contains(&year) {
+            return None;
+        }
+        if (1..=12).contains(&a) && (1..=31).contains(&b) {
+            Some((year, a, b))
+        } else if (1..=12).contains(&b) && (1..=31).contains(&a) {
+            Some((year, b, a))
+        } else {
+            None
+        }
+    })
+}
+
+fn bruteforce_guesses(len: usize) -> f64 {
+    let min_guesses = if len == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR + 1.0
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR + 1.0
+    };
+    BRUTEFORCE_CARDINALITY.powi(len as i32).max(min_guesses).min(f64::MAX)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+fn sequence_guesses(matches: &[Match]) -> f64 {
+    if matches.is_empty() {
+        return 1.0;
+    }
+    let product: f64 = matches.iter().map(|m| m.guesses).product();
+    (factorial(matches.len()) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(matches.len() as i32 - 1)).min(f64::MAX)
+}
+
+// Attackers first try passwords made of few patterns, so we compute the minimal number of guesses over every
+// sequence of non-overlapping matches that covers the password: l! * (guesses of each match) + 10000^(l - 1).
+fn most_guessable_sequence(chars: &[char], matches: &[Match]) -> Vec<Match> {
+    let n = chars.len();
+    let mut candidates: Vec<Match> = matches
+        .iter()
+        .map(|m| {
+            let len = m.token.chars().count();
+            let mut m = m.clone();
+            if len < n {
+                let min_guesses = if len == 1 { MIN_SUBMATCH_GUESSES_SINGLE_CHAR } else { MIN_SUBMATCH_GUESSES_MULTI_CHAR };
+                m.guesses = m.guesses.max(min_guesses);
+            }
+            m
+        })
+        .collect();
+    for i in 0..n {
+        for j in i + 1..=n {
+            candidates.push(Match {
+                start: i,
+                token: chars[i..j].iter().collect(),
+                pattern: Pattern::Bruteforce,
+                guesses: bruteforce_guesses(j - i),
+            });
+        }
+    }
+    let mut by_end: Vec<Vec<usize>> = vec![Vec::new(); n + 1];
+    for (index, m) in candidates.iter().enumerate() {
+        by_end[m.start + m.token.chars().count()].push(index);
+    }
+    // best[j][l] is the (product of guesses, total guesses, match) of the best sequence of l matches covering chars[..j].
+    let mut best: Vec<Vec<Option<(f64, f64, usize)>>> = vec![vec![None; n + 1]; n + 1];
+    for j in 1..=n {
+        for &index in &by_end[j] {
+            let m = &candidates[index];
+            let previous: Vec<(usize, f64)> = if m.start == 0 {
+                vec![(0, 1.0)]
+            } else {
+                best[m.start].iter().enumerate().filter_map(|(l, e)| e.map(|(product, _, _)| (l, product))).collect()
+            };
+            for (l, product) in previous {
+                let product = product * m.guesses;
+                let total = factorial(l + 1) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32);
+                if best[j][l + 1].is_none_or(|(_, t, _)| total < t) {
+                    best[j][l + 1] = Some((product, total, index));
+                }
+            }
+        }
+    }
+    let Some(mut l) = (1..=n)
+        .filter(|&l| best[n][l].is_some())
+        .min_by(|&a, &b| best[n][a].unwrap().1.total_cmp(&best[n][b].unwrap().1))
+    else {
+        return Vec::new();
+    };
+    let mut sequence = Vec::with_capacity(l);
+    let mut j = n;
+    while l > 0 {
+        let (_, _, index) = best[j][l].unwrap();
+        sequence.push(candidates[index].clone());
+        j = candidates[index].start;
+        l -= 1;
+    }
+    sequence.reverse();
+    sequence
+}
+
+fn feedback(score: u8, matches: &[Match]) -> Feedback {
+    if matches.is_empty() {
+        return Feedback {
+            warning: None,
+            suggestions: vec![
+                String::from("Use a few words, avoid common phrases"),
+                String::from("No need for symbols, digits, or uppercase letters"),
+            ],
+        };
+    }
+    if score > 2 {
+        return Feedback::default();
+    }
+    let mut suggestions = vec![String::from("Add another word or two. Uncommon words are better.")];
+    let longest = matches.iter().max_by_key(|m| m.token.chars().count()).unwrap_or(&matches[0]);
+    let sole_match = matches.len() == 1;
+    let warning = match &longest.pattern {
+        Pattern::Dictionary {
+            dictionary, rank, reversed, l33t, ..
+        } => {
+            let chars: Vec<char> = longest.token.chars().collect();
+            if chars[0].is_uppercase() && chars[1..].iter().all(|c| !c.is_uppercase()) {
+                suggestions.push(String::from("Capitalization doesn't help very much"));
+            } else if chars.iter().all(|c| !c.is_lowercase()) && chars.iter().any(|c| c.is_uppercase()) {
+                suggestions.push(String::from("All-uppercase is almost as easy to guess as all-lowercase"));
+            }
+            if *reversed {
+                suggestions.push(String::from("Reversed words aren't much harder to guess"));
+            }
+            if *l33t {
+                suggestions.push(String::from("Predictable substitutions like '@' instead of 'a' don't help very much"));
+            }
+            match *dictionary {
+                "passwords" if sole_match && !l33t && !reversed && *rank <= 10 => Some("This is a top-10 common password"),
+                "passwords" if sole_match && !l33t && !reversed && *rank <= 100 => Some("This is a top-100 common password"),
+                "passwords" if sole_match => Some("This is a very common password"),
+                "passwords" => Some("This is similar to a commonly used password"),
+                "user_inputs" => Some("Passwords based on your personal details are easy to guess"),
+                _ if sole_match => Some("A word by itself is easy to guess"),
+                _ => None,
+            }
+        }
+        Pattern::Spatial { turns, .. } => {
+            suggestions.push(String::from("Use a longer keyboard pattern with more turns"));
+            if *turns == 1 {
+                Some("Straight rows of keys are easy to guess")
+            } else {
+                Some("Short keyboard patterns are easy to guess")
+            }
+        }
+        Pattern::Repeat { base, .. } => {
+            suggestions.push(String::from("Avoid repeated words and characters"));
+            if base.chars().count() == 1 {
+                Some("Repeats like \"aaa\" are easy to guess")
+            } else {
+                Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"")
+            }
+        }
+        Pattern::Sequence { .. } => {
+            suggestions.push(String::from("Avoid sequences"));
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        Pattern::Date { .. } => {
+            suggestions.push(String::from("Avoid dates and years that are associated with you"));
+            Some("Dates are often easy to guess")
+        }
+        Pattern::Year { .. } => {
+            suggestions.push(String::from("Avoid recent years"));
+            suggestions.push(String::from("Avoid years that are associated with you"));
+            Some("Recent years are easy to guess")
+        }
+        Pattern::Bruteforce => None,
+    };
+    Feedback {
+        warning: warning.map(String::from),
+        suggestions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::password::generate_password;
+
+    fn patterns(password: &str) -> Vec<Pattern> {
+        estimate_strength(password, &[]).matches.into_iter().map(|m| m.pattern).collect()
+    }
+
+    #[test]
+    fn common_passwords() {
+        let strength = estimate_strength("password", &[]);
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.guesses, 3.0);
+        assert_eq!(strength.feedback.warning, Some(String::from("This is a top-10 common password")));
+        for password in ["123456", "qwerty", "iloveyou", "Password", "PASSWORD", "drowssap", "letmein1", "trustno1"] {
+            assert_eq!(estimate_strength(password, &[]).score, 0, "{}", password);
+        }
+    }
+
+    #[test]
+    fn l33t_substitutions() {
+        let strength = estimate_strength("P@ssw0rd", &[]);
+        assert!(strength.score <= 1);
+        assert!(matches!(
+            &strength.matches[..],
+            [Match {
+                pattern: Pattern::Dictionary { l33t: true, .. },
+                ..
+            }]
+        ));
+        assert!(strength.feedback.suggestions.iter().any(|s| s.contains("substitutions")));
+        assert_eq!(unl33t(&['p', '4', 's']), vec![String::from("pas")]);
+        assert_eq!(unl33t(&['1', 'o']), vec![String::from("io"), String::from("lo")]);
+        assert_eq!(unl33t(&['a', 'b']), Vec::<String>::new());
+    }
+
+    #[test]
+    fn keyboard_patterns() {
+        assert!(matches!(
+            &patterns("zxcvfdsa")[..],
+            [Pattern::Spatial {
+                keyboard: "qwerty",
+                turns: 3,
+                shifted: 0
+            }]
+        ));
+        assert!(matches!(
+            &patterns("ASDFGHJ")[..],
+            [Pattern::Spatial {
+                keyboard: "qwerty",
+                turns: 1,
+                shifted: 7
+            }]
+        ));
+        assert!(matches!(&patterns("78963214")[..], [Pattern::Spatial { keyboard: "keypad", .. }]));
+        let strength = estimate_strength("1qaz2wsx3edc", &[]);
+        assert!(strength.score <= 2);
+        assert!(strength.matches.iter().all(|m| matches!(m.pattern, Pattern::Spatial { .. } | Pattern::Dictionary { .. })));
+        assert_eq!(
+            estimate_strength("zxcvfdsa", &[]).feedback.warning,
+            Some(String::from("Short keyboard patterns are easy to guess"))
+        );
+    }
+
+    #[test]
+    fn repeats_and_sequences() {
+        let strength = estimate_strength("aaaaaaaaaaaa", &[]);
+        assert_eq!(strength.score, 0);
+        assert!(matches!(
+            &strength.matches[..],
+            [Match {
+                pattern: Pattern::Repeat { count: 12, .. },
+                ..
+            }]
+        ));
+        assert!(matches!(&patterns("monkeymonkeymonkey")[..], [Pattern::Repeat { count: 3, .. }]));
+        assert!(estimate_strength("monkeymonkeymonkey", &[]).score <= 1);
+        assert!(matches!(&patterns("abcdefghijk")[..], [Pattern::Sequence { ascending: true }]));
+        assert!(matches!(&patterns("97531")[..], [Pattern::Sequence { ascending: false }]));
+        assert_eq!(estimate_strength("abcdefghijk", &[]).score, 0);
+    }
+
+    #[test]
+    fn dates() {
+        for (password, year, month, day) in [
+            ("19/04/1987", 1987, 4, 19),
+            ("04.19.1987", 1987, 4, 19),
+            ("1987-04-19", 1987, 4, 19),
+            ("19041987", 1987, 4, 19),
+            ("870419", 1987, 4, 19),
+        ] {
+            let p = patterns(password);
+            assert_eq!(p.len(), 1, "{}", password);
+            match &p[0] {
+                Pattern::Date { year: y, month: m, day: d, .. } => assert_eq!((*y, *m, *d), (year, month, day), "{}", password),
+                p => panic!("unexpected pattern for {}: {:?}", password, p),
+            }
+        }
+        assert!(matches!(&patterns("1987")[..], [Pattern::Year { year: 1987 }]));
+        assert!(estimate_strength("19/04/1987", &[]).score <= 2);
+    }
+
+    #[test]
+    fn user_inputs() {
+        let weak = estimate_strength("Cybele!", &["cybele"]);
+        let strong = estimate_strength("Cybele!", &[]);
+        assert!(weak.guesses < strong.guesses);
+        assert_eq!(weak.score, 1);
+        assert_eq!(weak.feedback.warning, Some(String::from("Passwords based on your personal details are easy to guess")));
+        assert_eq!(estimate_strength("cybele", &["Cybele"]).score, 0);
+    }
+
+    #[test]
+    fn strong_passwords() {
+        assert_eq!(estimate_strength("correcthorsebatterystaple", &[]).score, 4);
+        assert!(estimate_strength("Tr0ub4dour&3", &[]).score >= 3);
+        for _ in 0..10 {
+            let password = generate_password(16).password;
+            let strength = estimate_strength(&password, &[]);
+            assert_eq!(strength.score, 4, "{}", password);
+            assert_eq!(strength.feedback, Feedback::default());
+        }
+        for password_len in [65, 100, 300] {
+            let password = generate_password(password_len).password;
+            assert_eq!(estimate_strength(&password, &[]).score, 4, "{}", password);
+        }
+    }
+
+    #[test]
+    fn long_weak_passwords() {
+        let alphabet = "abcdefghijklmnopqrstuvwxyz";
+        for password in [
+            "a".repeat(65),
+            "a".repeat(80),
+            "x".repeat(1000),
+            "password".repeat(10),
+            "monkey".repeat(15),
+            alphabet.repeat(3),
+        ] {
+            let strength = estimate_strength(&password, &[]);
+            assert!(strength.score <= 1, "{}", password);
+            assert!(
+                matches!(
+                    &strength.matches[..],
+                    [Match {
+                        pattern: Pattern::Repeat { .. },
+                        ..
+                    }]
+                ),
+                "{}",
+                password
+            );
+        }
+        assert!(matches!(&patterns(&"x".repeat(1000))[..], [Pattern::Repeat { count: 1000, .. }]));
+        // Long repeats are found anywhere in the password.
+        let password = format!("{}{}{}", generate_password(50).password, "monkey".repeat(20), generate_password(50).password);
+        let strength = estimate_strength(&password, &[]);
+        assert!(strength.matches.iter().any(|m| m.pattern
+            == Pattern::Repeat {
+                base: String::from("monkey"),
+                count: 20
+            }));
+    }
+
+    #[test]
+    fn empty_password() {
+        let strength = estimate_strength("", &[]);
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.guesses, 1.0);
+        assert!(strength.matches.is_empty());
+        assert_eq!(strength.feedback.suggestions.len(), 2);
+    }
+
+    #[test]
+    fn matches_cover_password() {
+        let long = format!("{}{}{}", "P4ssw0rd_19870419_monkey".repeat(4), "abcdefghijklmnopqrstuvwxyz".repeat(3), "é€🔑".repeat(30));
+        for password in ["password123", "Summer2024!", "qwerty12345zxcvbn", "é€🔑abc", "P4ssw0rd_19870419_monkey", &long] {
+            let strength = estimate_strength(password, &[]);
+            let tokens: String = strength.matches.iter().map(|m| m.token.as_str()).collect();
+            assert_eq!(tokens, password);
+            let mut start = 0;
+            for m in &strength.matches {
+                assert_eq!(m.start, start);
+                start += m.token.chars().count();
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::base32;
+use crate::hash::sha256;
+use crate::password::passphrase::{parse_wordlist, WORDLIST};
+
+pub mod shamir;
+
+/// A random 256-bit key that can open a vault when its password has been lost. It is only shown once, when it is
+/// generated, and can be split among trusted people with `shamir::split`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RecoveryKey {
+    bytes: [u8; 32],
+}
+
+// Recovery keys are as sensitive as passwords, so they shouldn't end up in logs.
+impl fmt::Debug for RecoveryKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecoveryKey(..)")
+    }
+}
+
+impl RecoveryKey {
+    pub fn generate() -> RecoveryKey {
+        let mut bytes = [0u8; 32];
+        OsRng {}.fill_bytes(&mut bytes);
+        RecoveryKey { bytes }
+    }
+
+    /// Use an existing key (e.g. combined from Shamir shares).
+    pub fn from_bytes(bytes: [u8; 32]) -> RecoveryKey {
+        RecoveryKey { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// Encode the key to 24 words, the last of which contains an 8-bit checksum.
+    /// Specification can be found here: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+    pub fn to_words(&self) -> String {
+        let words: Vec<&str> = parse_wordlist(WORDLIST).collect();
+        let mut bits = self.bytes.to_vec();
+        bits.push(sha256::hash(&self.bytes)[0]);
+        // 264 bits are split into 24 indexes of 11 bits.
+        (0..24)
+            .map(|i| {
+                let index = (0..11).fold(0usize, |acc, j| {
+                    let bit = i * 11 + j;
+                    (acc << 1) | ((bits[bit / 8] >> (7 - bit % 8)) & 1) as usize
+                });
+                words[index]
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Decode a key encoded by `to_words`, ignoring case and separators (whitespace or '-').
+    pub fn from_words(encoded: &str) -> Result<RecoveryKey, String> {
+        let words: Vec<&str> = parse_wordlist(WORDLIST).collect();
+        let encoded = encoded.to_lowercase();
+        let encoded: Vec<&str> = encoded.split(|c: char| c.is_whitespace() || c == '-').filter(|w| !w.is_empty()).collect();
+        if encoded.len() != 24 {
+            return Err(format!("expected 24 words, got {}", encoded.len()));
+        }
+        let mut bits = [0u8; 33];
+        for (i, word) in encoded.iter().enumerate() {
+            let index = words.iter().position(|w| w == word).ok_or_else(|| format!("unknown word #{}: {}", i + 1, word))?;
+            for j in 0..11 {
+                let bit = i * 11 + j;
+                bits[bit / 8] |= (((index >> (10 - j)) & 1) as u8) << (7 - bit % 8);
+            }
+        }
+        let bytes: [u8; 32] = bits[..32].try_into().unwrap();
+        if sha256::hash(&bytes)[0] != bits[32] {
+            return Err(String::from("invalid checksum: some words are wrong or in the wrong order"));
+        }
+        Ok(RecoveryKey { bytes })
+    }
+
+    /// Encode the key to base32 with an 8-bit checksum, in groups of 4 chars (e.g. "AEVB-UNG7-...").
+    pub fn to_base32(&self) -> String {
+        let mut bytes = self.bytes.to_vec();
+        bytes.push(sha256::hash(&self.bytes)[0]);
+        let encoded = base32::encode_unpadded(bytes);
+        let groups: Vec<&str> = encoded.as_bytes().chunks(4).map(|g| std::str::from_utf8(g).unwrap()).collect();
+        groups.join("-")
+    }
+
+    /// Decode a key encoded by `to_base32`, ignoring case and separators (whitespace or '-').
+    pub fn from_base32(encoded: &str) -> Result<RecoveryKey, String> {
+        let normalized: String = encoded.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        let decoded = base32::decode(&normalized)?;
+        if decoded.len() != 33 {
+            return Err(format!("invalid recovery key length: {} bytes", decoded.len()));
+        }
+        let bytes: [u8; 32] = decoded[..32].try_into().unwrap();
+        if sha256::hash(&bytes)[0] != decoded[32] {
+            return Err(String::from("invalid checksum"));
+        }
+        Ok(RecoveryKey { bytes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_words() {
+        // Test vectors can be found here: https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+        let test_cases = vec![
+            ([0u8; 32], "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art"),
+            ([0x7fu8; 32], "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title"),
+            ([0xffu8; 32], "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote"),
+        ];
+        for (bytes, words) in test_cases {
+            let key = RecoveryKey::from_bytes(bytes);
+            assert_eq!(key.to_words(), words);
+            assert_eq!(RecoveryKey::from_words(words).unwrap(), key);
+        }
+        let key = RecoveryKey::generate();
+        assert_eq!(RecoveryKey::from_words(&key.to_words().to_uppercase().replace(' ', "-\n")).unwrap(), key);
+        assert_eq!(RecoveryKey::from_words("abandon art"), Err(String::from("expected 24 words, got 2")));
+        let words = "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth";
+        assert_eq!(RecoveryKey::from_words(&format!("{} tilte", words)), Err(String::from("unknown word #24: tilte")));
+        assert_eq!(
+            RecoveryKey::from_words(&format!("{} vote", words)),
+            Err(String::from("invalid checksum: some words are wrong or in the wrong order"))
+        );
+    }
+
+    #[test]
+    fn encode_decode_base32() {
+        let key = RecoveryKey::from_bytes([42u8; 32]);
+        let encoded = key.to_base32();
+        assert_eq!(encoded.len(), 53 + 13);
+        assert_eq!(RecoveryKey::from_base32(&encoded).unwrap(), key);
+        assert_eq!(RecoveryKey::from_base32(&encoded.to_lowercase().replace('-', " ")).unwrap(), key);
+        assert_eq!(RecoveryKey::from_base32(&encoded.replacen('K', "L", 1)), Err(String::from("invalid checksum")));
+        assert_eq!(RecoveryKey::from_base32("FIVC-UKQ"), Err(String::from("invalid recovery key length: 4 bytes")));
+        assert_eq!(format!("{:?}", key), "RecoveryKey(..)");
+    }
+}
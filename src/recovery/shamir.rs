@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::base32;
+use crate::crypto::hmac;
+use crate::gf256;
+use crate::hash::sha256::{self, Sha256};
+
+const FORMAT_VERSION: u8 = 1;
+// [u8: version][4*u8: identifier][u8: index][u8: threshold][32*u8: value][4*u8: checksum]
+const SHARE_LEN: usize = 43;
+const CHECKSUM_LEN: usize = 4;
+// The polynomial evaluates to the secret at x = 255 and to its digest at x = 254, so shares are numbered from 1 to 253.
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const MAX_SHARES: u8 = 253;
+const DIGEST_LEN: usize = 4;
+
+/// A share of a 32-byte secret: any `threshold` shares of the same secret are enough to recover it, while fewer shares
+/// reveal nothing about it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+    /// Random identifier of the shares created together, so that shares of different secrets are never mixed.
+    pub identifier: [u8; 4],
+    /// Non-zero x coordinate of the share (shares are numbered from 1).
+    pub index: u8,
+    pub threshold: u8,
+    pub value: [u8; 32],
+}
+
+impl Share {
+    /// Encode the share to base32 with its checksum, in groups of 4 chars (e.g. "AEVB UNG7 ...").
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(SHARE_LEN);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend(self.identifier);
+        bytes.extend([self.index, self.threshold]);
+        bytes.extend(self.value);
+        let checksum = sha256::hash(&bytes);
+        bytes.extend(&checksum[..CHECKSUM_LEN]);
+        let encoded = base32::encode_unpadded(bytes);
+        let groups: Vec<&str> = encoded.as_bytes().chunks(4).map(|g| std::str::from_utf8(g).unwrap()).collect();
+        groups.join(" ")
+    }
+
+    /// Decode a share, ignoring whitespace and case.
+    pub fn decode(encoded: &str) -> Result<Share, String> {
+        let normalized: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = base32::decode(&normalized)?;
+        if bytes.len() != SHARE_LEN {
+            return Err(format!("invalid share length: {} bytes", bytes.len()));
+        }
+        let (content, checksum) = bytes.split_at(SHARE_LEN - CHECKSUM_LEN);
+        if sha256::hash(content)[..CHECKSUM_LEN] != *checksum {
+            return Err(String::from("invalid share checksum"));
+        }
+        if content[0] != FORMAT_VERSION {
+            return Err(format!("unsupported share version: {}", content[0]));
+        }
+        let share = Share {
+            identifier: content[1..5].try_into().unwrap(),
+            index: content[5],
+            threshold: content[6],
+            value: content[7..].try_into().unwrap(),
+        };
+        // Shares at the secret and digest indexes would reveal them.
+        if !(1..=MAX_SHARES).contains(&share.index) || !(1..=MAX_SHARES).contains(&share.threshold) {
+            return Err(String::from("invalid share numbering"));
+        }
+        Ok(share)
+    }
+}
+
+/// Split a secret (e.g. a recovery key) into `shares` shares, any `threshold` of which can recover it.
+/// When at least 2 shares are required, the polynomial also encodes a digest of the secret, which is used to detect
+/// invalid shares when combining them, without revealing anything about the secret.
+/// This scheme is inspired by SLIP-39 (https://github.com/satoshilabs/slips/blob/master/slip-0039.md), but doesn't
+/// implement it: shares aren't encoded as mnemonics, and there are no groups.
+pub fn split(secret: &[u8; 32], threshold: u8, shares: u8) -> Result<Vec<Share>, String> {
+    if threshold == 0 || threshold > shares {
+        return Err(format!("invalid threshold: {} of {} shares", threshold, shares));
+    }
+    if shares > MAX_SHARES {
+        return Err(format!("too many shares: {} (at most {})", shares, MAX_SHARES));
+    }
+    let mut csprng = OsRng {};
+    let mut identifier = [0u8; 4];
+    csprng.fill_bytes(&mut identifier);
+    let share = |index: u8, value: [u8; 32]| Share {
+        identifier,
+        index,
+        threshold,
+        value,
+    };
+    if threshold == 1 {
+        return Ok((1..=shares).map(|x| share(x, *secret)).collect());
+    }
+    // The polynomial of degree threshold - 1 goes through threshold - 2 random points, the digest and the secret.
+    let mut points: Vec<(u8, [u8; 32])> = (1..threshold - 1)
+        .map(|x| {
+            let mut value = [0u8; 32];
+            csprng.fill_bytes(&mut value);
+            (x, value)
+        })
+        .collect();
+    let mut digest = [0u8; 32];
+    csprng.fill_bytes(&mut digest[DIGEST_LEN..]);
+    let digest_part = compute_digest(&digest[DIGEST_LEN..], secret);
+    digest[..DIGEST_LEN].copy_from_slice(&digest_part);
+    points.push((DIGEST_INDEX, digest));
+    points.push((SECRET_INDEX, *secret));
+    Ok((1..=shares)
+        .map(|x| match points.iter().find(|(i, _)| *i == x) {
+            Some((_, value)) => share(x, *value),
+            None => share(x, evaluate(&points, x)),
+        })
+        .collect())
+}
+
+/// Recover a secret from at least `threshold` distinct shares.
+pub fn combine(shares: &[Share]) -> Result<[u8; 32], String> {
+    let first = shares.first().ok_or("no shares")?;
+    if shares.iter().any(|s| (s.identifier, s.threshold) != (first.identifier, first.threshold)) {
+        return Err(String::from("shares belong to different secrets"));
+    }
+    let mut distinct: BTreeMap<u8, &Share> = BTreeMap::new();
+    shares.iter().for_each(|s| {
+        distinct.entry(s.index).or_insert(s);
+    });
+    if distinct.len() < first.threshold as usize {
+        return Err(format!("not enough shares: {} of the {} required", distinct.len(), first.threshold));
+    }
+    if first.threshold == 1 {
+        return Ok(first.value);
+    }
+    let points: Vec<(u8, [u8; 32])> = distinct.into_values().take(first.threshold as usize).map(|s| (s.index, s.value)).collect();
+    let secret = evaluate(&points, SECRET_INDEX);
+    let digest = evaluate(&points, DIGEST_INDEX);
+    if compute_digest(&digest[DIGEST_LEN..], &secret) != digest[..DIGEST_LEN] {
+        return Err(String::from("recovered secret doesn't match the shares"));
+    }
+    Ok(secret)
+}
+
+// Value at x of the polynomial going through the given points, computed byte by byte.
+fn evaluate(points: &[(u8, [u8; 32])], x: u8) -> [u8; 32] {
+    let xs: Vec<u8> = points.iter().map(|(i, _)| *i).collect();
+    let coefficients = gf256::lagrange_coefficients(&xs, x);
+    let mut value = [0u8; 32];
+    for (i, byte) in value.iter_mut().enumerate() {
+        *byte = gf256::interpolate(&coefficients, &points.iter().map(|(_, v)| v[i]).collect::<Vec<u8>>());
+    }
+    value
+}
+
+// HMAC-SHA256(random_part, secret), truncated to 4 bytes.
+fn compute_digest(random_part: &[u8], secret: &[u8; 32]) -> [u8; DIGEST_LEN] {
+    hmac::authenticate::<Sha256>(random_part, secret)[..DIGEST_LEN].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_combine() {
+        let secret = [42u8; 32];
+        let shares = split(&secret, 3, 5).unwrap();
+        assert_eq!(shares.iter().map(|s| s.index).collect::<Vec<u8>>(), vec![1, 2, 3, 4, 5]);
+        assert!(shares.iter().all(|s| s.value != secret));
+        for selected in [[0, 1, 2], [2, 3, 4], [4, 0, 2]] {
+            let selected: Vec<Share> = selected.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine(&selected).unwrap(), secret);
+        }
+        assert_eq!(combine(&shares).unwrap(), secret);
+        assert_eq!(combine(&shares[..2]), Err(String::from("not enough shares: 2 of the 3 required")));
+        assert_eq!(
+            combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]),
+            Err(String::from("not enough shares: 2 of the 3 required"))
+        );
+        let other = split(&[7u8; 32], 3, 5).unwrap();
+        assert_eq!(
+            combine(&[shares[0].clone(), shares[1].clone(), other[2].clone()]),
+            Err(String::from("shares belong to different secrets"))
+        );
+        let mut corrupted = shares[..3].to_vec();
+        corrupted[1].value[0] ^= 1;
+        assert_eq!(combine(&corrupted), Err(String::from("recovered secret doesn't match the shares")));
+        assert_eq!(combine(&[]), Err(String::from("no shares")));
+        // A single share can be enough.
+        assert_eq!(split(&secret, 1, 1).unwrap()[0].value, secret);
+        assert_eq!(split(&secret, 4, 3), Err(String::from("invalid threshold: 4 of 3 shares")));
+        assert_eq!(split(&secret, 0, 3), Err(String::from("invalid threshold: 0 of 3 shares")));
+        assert_eq!(combine(&split(&secret, 2, 253).unwrap()[251..]).unwrap(), secret);
+        assert_eq!(split(&secret, 2, 254), Err(String::from("too many shares: 254 (at most 253)")));
+    }
+
+    #[test]
+    fn shares_reveal_nothing() {
+        let secret = [42u8; 32];
+        let (shares, others) = (split(&secret, 2, 3).unwrap(), split(&secret, 2, 3).unwrap());
+        // The identifier is random, instead of being derived from the secret.
+        assert_ne!(shares[0].identifier, others[0].identifier);
+        assert_ne!(shares[0].value, others[0].value);
+        // Without the secret, the digest cannot be forged: a share tampered to recover another secret is detected.
+        let mut forged = shares[..2].to_vec();
+        forged[0].value[5] ^= 0x80;
+        forged[1].value[5] ^= 0x80;
+        assert_eq!(combine(&forged), Err(String::from("recovered secret doesn't match the shares")));
+        for threshold in 2..=5 {
+            let shares = split(&secret, threshold, 5).unwrap();
+            assert_eq!(combine(&shares[5 - threshold as usize..]).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn encode_decode_share() {
+        let share = Share {
+            identifier: [1, 2, 3, 4],
+            index: 2,
+            threshold: 3,
+            value: [42u8; 32],
+        };
+        let encoded = share.encode();
+        assert_eq!(encoded, "AEAQ EAYE AIBS UKRK FIVC UKRK FIVC UKRK FIVC UKRK FIVC UKRK FIVC UKRK FIVC UKWP GT6C G");
+        assert_eq!(Share::decode(&encoded).unwrap(), share);
+        assert_eq!(Share::decode(&format!("  {}\n", encoded.to_lowercase().replace(' ', ""))).unwrap(), share);
+        assert_eq!(Share::decode(&encoded.replacen("AIBS", "AIBT", 1)), Err(String::from("invalid share checksum")));
+        assert_eq!(Share::decode("AEAQ EAYE"), Err(String::from("invalid share length: 5 bytes")));
+        assert!(Share::decode("not base32!").is_err());
+        for (index, threshold) in [(0, 3), (DIGEST_INDEX, 3), (SECRET_INDEX, 3), (2, 0), (2, MAX_SHARES + 1)] {
+            let encoded = Share {
+                index,
+                threshold,
+                ..share.clone()
+            }
+            .encode();
+            assert_eq!(Share::decode(&encoded), Err(String::from("invalid share numbering")));
+        }
+        let encoded = Share {
+            index: MAX_SHARES,
+            threshold: MAX_SHARES,
+            ..share.clone()
+        }
+        .encode();
+        assert!(Share::decode(&encoded).is_ok());
+    }
+}
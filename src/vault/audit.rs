@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::password::{self, BreachDatabase};
+use crate::vault::item::Fingerprint;
+use crate::vault::Vault;
+use crate::Version;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Thresholds used to audit a vault.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AuditOptions {
+    /// Values with a lower strength score (see `password::estimate_strength`) are weak.
+    pub min_score: u8,
+    /// Values that haven't been modified for more days are old.
+    pub max_age_days: u64,
+    /// Different values that are at most this number of edits (insertions, deletions or substitutions) apart are similar.
+    pub max_similar_distance: usize,
+}
+
+impl Default for AuditOptions {
+    fn default() -> AuditOptions {
+        AuditOptions {
+            min_score: 3,
+            max_age_days: 365,
+            max_similar_distance: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The value is identical to the value of other items.
+    Reused {
+        items: Vec<String>,
+    },
+    /// The value is almost identical to the value of other items (e.g. "Summer2023!" and "Summer2024!").
+    Similar {
+        items: Vec<String>,
+    },
+    Weak {
+        score: u8,
+        warning: Option<String>,
+    },
+    Old {
+        days: u64,
+    },
+    Breached {
+        count: u64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemReport {
+    pub name: String,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Every item that could be decrypted, in the order of the vault, even when there is nothing to report.
+    pub items: Vec<ItemReport>,
+    /// Items that cannot be decrypted with the given password, and thus haven't been audited.
+    pub locked: Vec<String>,
+}
+
+impl Vault {
+    /// Audit the items that can be decrypted with the given password.
+    /// Breached values are only reported when a local breach database is provided.
+    pub fn audit(&self, password: &str, options: &AuditOptions, mut breach_db: Option<&mut BreachDatabase>) -> Result<AuditReport, String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs();
+        let mut locked = Vec::new();
+        let mut unlocked = Vec::new();
+        for item in &self.items {
+            match item.decrypt(password).and_then(|v| String::from_utf8(v).ok()) {
+                Some(value) => unlocked.push((item, value)),
+                None => locked.push(item.name.clone()),
+            }
+        }
+        let mut items = Vec::with_capacity(unlocked.len());
+        for (i, (item, value)) in unlocked.iter().enumerate() {
+            let mut findings = Vec::new();
+            let others = unlocked.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, other)| other);
+            let (reused, others): (Vec<_>, Vec<_>) = others.partition(|(_, other)| other == value);
+            if !reused.is_empty() {
+                findings.push(Finding::Reused {
+                    items: reused.iter().map(|(other, _)| other.name.clone()).collect(),
+                });
+            }
+            let similar: Vec<String> = others
+                .iter()
+                .filter(|(_, other)| edit_distance(value, other) <= options.max_similar_distance)
+                .map(|(other, _)| other.name.clone())
+                .collect();
+            if !similar.is_empty() {
+                findings.push(Finding::Similar { items: similar });
+            }
+            let strength = password::estimate_strength(value, &[&item.name]);
+            if strength.score < options.min_score {
+                findings.push(Finding::Weak {
+                    score: strength.score,
+                    warning: strength.feedback.warning,
+                });
+            }
+            if let Some(timestamps) = item.timestamps {
+                let days = now.saturating_sub(timestamps.modified_at) / SECONDS_PER_DAY;
+                if days > options.max_age_days {
+                    findings.push(Finding::Old { days });
+                }
+            }
+            if let Some(db) = breach_db.as_deref_mut() {
+                let count = password::check_breached(value, db)?;
+                if count > 0 {
+                    findings.push(Finding::Breached { count });
+                }
+            }
+            items.push(ItemReport {
+                name: item.name.clone(),
+                findings,
+            });
+        }
+        Ok(AuditReport { items, locked })
+    }
+
+    /// Return the groups of items that share the same value, comparing their fingerprints instead of decrypting them.
+    /// Items without an up-to-date fingerprint (see `update_fingerprints`) are ignored.
+    pub fn find_reused(&self) -> Vec<Vec<String>> {
+        let key_id = Fingerprint::key_id(&self.audit_key);
+        let mut groups: Vec<Vec<String>> = Vec::new();
+        let mut group_indexes: HashMap<[u8; 32], usize> = HashMap::new();
+        for item in &self.items {
+            match item.fingerprint {
+                Some(fingerprint) if fingerprint.key_id == key_id => {
+                    let index = *group_indexes.entry(fingerprint.digest).or_insert_with(|| {
+                        groups.push(Vec::new());
+                        groups.len() - 1
+                    });
+                    groups[index].push(item.name.clone());
+                }
+                _ => {}
+            }
+        }
+        groups.retain(|g| g.len() > 1);
+        groups
+    }
+
+    /// Compute the missing or outdated fingerprints of the items that can be decrypted with the given password (e.g.
+    /// items whose fingerprint was computed with another audit key, or removed). Returns the number of updated items.
+    pub fn update_fingerprints(&mut self, password: &str) -> Option<usize> {
+        if self.version == Version::V1 {
+            eprintln!("V1 items cannot contain fingerprints");
+            return None;
+        }
+        let key_id = Fingerprint::key_id(&self.audit_key);
+        let mut updated = 0;
+        for item in self.items.iter_mut().filter(|i| i.fingerprint.map(|f| f.key_id) != Some(key_id)) {
+            if let Some(value) = item.decrypt(password) {
+                item.fingerprint = Some(Fingerprint::compute(&self.audit_key, &value));
+                updated += 1;
+            }
+        }
+        Some(updated)
+    }
+}
+
+// Levenshtein distance between two strings (counted in chars).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hash::sha1;
+    use crate::hex;
+    use crate::vault::item::Timestamps;
+    use crate::Version;
+
+    #[test]
+    fn compute_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("Summer2023!", "Summer2024!"), 1);
+        assert_eq!(edit_distance("p4ssword", "password"), 1);
+        assert_eq!(edit_distance("é€", "e€"), 1);
+    }
+
+    #[test]
+    fn audit_vault() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("email", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("bank", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("forum", "Summer2023!", "m4st3r").unwrap();
+        vault.add("shop", "Summer2024!", "m4st3r").unwrap();
+        vault.add("router", "Zq8&Rt5^Hn2*Wd6%", "m4st3r").unwrap();
+        vault.add("work", "hunter2", "0th3r m4st3r").unwrap();
+        // The router password hasn't been modified for two years.
+        let two_years_ago = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 730 * SECONDS_PER_DAY;
+        vault.items[4].timestamps = Some(Timestamps {
+            created_at: two_years_ago,
+            modified_at: two_years_ago,
+        });
+        let path = std::env::temp_dir().join(format!("cybele-audit-breach-{}", std::process::id()));
+        std::fs::write(&path, format!("{}:42\n", hex::encode_upper(sha1::hash(b"Summer2024!")))).unwrap();
+        let mut db = BreachDatabase::open(&path).unwrap();
+
+        let report = vault.audit("m4st3r", &AuditOptions::default(), Some(&mut db)).unwrap();
+        assert_eq!(report.locked, vec!["work"]);
+        let findings: Vec<(&str, &Vec<Finding>)> = report.items.iter().map(|i| (i.name.as_str(), &i.findings)).collect();
+        assert_eq!(
+            findings[0],
+            (
+                "email",
+                &vec![Finding::Reused {
+                    items: vec![String::from("bank")]
+                }]
+            )
+        );
+        assert_eq!(
+            findings[1],
+            (
+                "bank",
+                &vec![Finding::Reused {
+                    items: vec![String::from("email")]
+                }]
+            )
+        );
+        assert_eq!(findings[2].0, "forum");
+        assert_eq!(
+            findings[2].1[0],
+            Finding::Similar {
+                items: vec![String::from("shop")]
+            }
+        );
+        assert!(matches!(findings[2].1[1], Finding::Weak { .. }));
+        assert_eq!(findings[2].1.len(), 2);
+        assert_eq!(findings[3].0, "shop");
+        assert_eq!(
+            findings[3].1[0],
+            Finding::Similar {
+                items: vec![String::from("forum")]
+            }
+        );
+        assert!(matches!(findings[3].1[1], Finding::Weak { .. }));
+        assert_eq!(findings[3].1[2], Finding::Breached { count: 42 });
+        assert_eq!(findings[4], ("router", &vec![Finding::Old { days: 730 }]));
+
+        // Without a breach database and with stricter thresholds.
+        let options = AuditOptions {
+            min_score: 5,
+            max_age_days: 800,
+            max_similar_distance: 0,
+        };
+        let report = vault.audit("0th3r m4st3r", &options, None).unwrap();
+        assert_eq!(report.locked.len(), 5);
+        assert_eq!(report.items.len(), 1);
+        assert_eq!(report.items[0].name, "work");
+        assert!(matches!(report.items[0].findings[..], [Finding::Weak { score: 1, .. }]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rotated_items_are_not_old() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("router", "Zq8&Rt5^Hn2*Wd6%", "m4st3r").unwrap();
+        vault.add("wifi", "Zq8&Rt5^Hn2*Wd6%", "m4st3r").unwrap();
+        let two_years_ago = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 730 * SECONDS_PER_DAY;
+        vault.items[0].timestamps = Some(Timestamps {
+            created_at: two_years_ago,
+            modified_at: two_years_ago,
+        });
+        let findings = |vault: &Vault| vault.audit("m4st3r", &AuditOptions::default(), None).unwrap().items[0].findings.clone();
+        assert_eq!(
+            findings(&vault),
+            vec![
+                Finding::Reused {
+                    items: vec![String::from("wifi")]
+                },
+                Finding::Old { days: 730 }
+            ]
+        );
+        vault.update("router", "Xw3!Lp9@Fs7#Km4&", "m4st3r").unwrap();
+        assert_eq!(findings(&vault), Vec::new());
+        assert_eq!(vault.timestamps("router").unwrap().created_at, two_years_ago);
+        assert_eq!(vault.get("router", "m4st3r").unwrap(), "Xw3!Lp9@Fs7#Km4&");
+        // The fingerprint follows the new value.
+        assert_eq!(vault.find_reused(), Vec::<Vec<String>>::new());
+        assert_eq!(vault.update("router", "Zq8&Rt5^Hn2*Wd6%", "wr0ng"), None);
+        assert_eq!(vault.update("unknown", "Zq8&Rt5^Hn2*Wd6%", "m4st3r"), None);
+        vault.update("router", "Zq8&Rt5^Hn2*Wd6%", "m4st3r").unwrap();
+        assert_eq!(vault.find_reused(), vec![vec!["router", "wifi"]]);
+    }
+
+    #[test]
+    fn find_reused_values() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("email", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("forum", "Summer2023!", "m4st3r").unwrap();
+        vault.add("bank", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("shop", "Summer2023!", "m4st3r").unwrap();
+        vault.add("router", "Zq8&Rt5^Hn2*Wd6%", "m4st3r").unwrap();
+        // Items encrypted with another password are fingerprinted with the same audit key.
+        vault.add("work", "kT9#vLq2$wXz7!mP", "0th3r m4st3r").unwrap();
+        let expected = vec![vec!["email", "bank", "work"], vec!["forum", "shop"]];
+        assert_eq!(vault.find_reused(), expected);
+        // Fingerprints and the audit key survive serialization.
+        let vault = Vault::deserialize(&vault.serialize("f1l3").unwrap(), "f1l3").unwrap();
+        assert_eq!(vault.find_reused(), expected);
+    }
+
+    #[test]
+    fn update_missing_fingerprints() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("email", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("bank", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.add("work", "kT9#vLq2$wXz7!mP", "0th3r m4st3r").unwrap();
+        vault.add("home", "kT9#vLq2$wXz7!mP", "0th3r m4st3r").unwrap();
+        vault.items[..2].iter_mut().for_each(|i| i.fingerprint = None);
+        assert_eq!(vault.find_reused(), vec![vec!["work", "home"]]);
+        assert_eq!(vault.update_fingerprints("m4st3r"), Some(2));
+        assert_eq!(vault.update_fingerprints("m4st3r"), Some(0));
+        assert_eq!(vault.find_reused(), vec![vec!["email", "bank", "work", "home"]]);
+        // Vaults serialized before audit keys were introduced get a new audit key, which outdates their fingerprints.
+        vault.audit_key = [7u8; 32];
+        assert_eq!(vault.find_reused(), Vec::<Vec<String>>::new());
+        assert_eq!(vault.update_fingerprints("m4st3r"), Some(2));
+        assert_eq!(vault.find_reused(), vec![vec!["email", "bank"]]);
+        assert_eq!(vault.update_fingerprints("0th3r m4st3r"), Some(2));
+        assert_eq!(vault.find_reused(), vec![vec!["email", "bank", "work", "home"]]);
+        vault.version = Version::V1;
+        assert_eq!(vault.update_fingerprints("m4st3r"), None);
+    }
+}
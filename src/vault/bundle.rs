@@ -0,0 +1,378 @@
+use std::io::{BufReader, Read, Write};
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::otp::Otp;
+use crate::vault::item::VaultItem;
+use crate::vault::{Attachment, ItemKind, Timestamps, Vault};
+use crate::{cipher, keys, Kdf, Purpose, Version};
+
+const BUNDLE_MAGIC: &[u8; 4] = b"CYBB";
+const BUNDLE_VERSION: u8 = 1;
+
+// Tags of the optional records that can be attached to bundled items.
+const OTP_RECORD: u8 = 1;
+const TIMESTAMPS_RECORD: u8 = 2;
+const KIND_RECORD: u8 = 3;
+const ATTACHMENT_RECORD: u8 = 4;
+
+// We reject parameters that would spend an unreasonable amount of resources.
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+const MAX_ITERATIONS: u32 = 256;
+const MAX_PARALLELISM: u32 = 16;
+
+/// What to do when an imported item has the same name as an existing item.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep both items, renaming the imported item (e.g. "GitHub (2)").
+    Rename,
+    /// Keep the existing item.
+    Skip,
+    /// Replace the existing item with the imported item.
+    Replace,
+}
+
+struct BundledItem {
+    name: String,
+    value: String,
+    otp: Option<Otp>,
+    timestamps: Option<Timestamps>,
+    kind: ItemKind,
+    attachments: Vec<Attachment>,
+}
+
+impl Vault {
+    /// Export the given items to a bundle encrypted with a dedicated password, which can be imported in another vault.
+    /// All items must be decryptable with the given vault password.
+    pub fn export_items(&self, names: &[&str], password: &str, export_password: &str) -> Option<Vec<u8>> {
+        let mut payload: Vec<u8> = Vec::new();
+        // [u16: items_len]
+        payload.write_all(&(names.len() as u16).to_be_bytes()).unwrap();
+        for name in names {
+            let Some(item) = self.items.iter().find(|i| i.name == *name) else {
+                eprintln!("Unknown item: {}", name);
+                return None;
+            };
+            let Some(value) = item.decrypt(password) else {
+                eprintln!("Cannot decrypt item: {}", name);
+                return None;
+            };
+            // [u16: name_len][name_len*u8: name][u32: value_len][value_len*u8: value]
+            payload.write_all(&(name.len() as u16).to_be_bytes()).unwrap();
+            payload.write_all(name.as_bytes()).unwrap();
+            payload.write_all(&(value.len() as u32).to_be_bytes()).unwrap();
+            payload.write_all(&value).unwrap();
+            let mut records = Vec::new();
+            if let Some(otp) = item.decrypt_otp(password) {
+                records.push((OTP_RECORD, otp.to_uri().into_bytes()));
+            }
+            if let Some(timestamps) = item.timestamps {
+                let mut data = timestamps.created_at.to_be_bytes().to_vec();
+                data.extend(timestamps.modified_at.to_be_bytes());
+                records.push((TIMESTAMPS_RECORD, data));
+            }
+            if item.kind != ItemKind::default() {
+                records.push((KIND_RECORD, vec![item.kind.to_byte()]));
+            }
+            let Some(attachments) = item.decrypt_attachments(password) else {
+                eprintln!("Cannot decrypt attachments of item: {}", name);
+                return None;
+            };
+            for attachment in attachments {
+                // [u16: name_len][name_len*u8: name][...data]
+                let mut data = (attachment.name.len() as u16).to_be_bytes().to_vec();
+                data.extend(attachment.name.as_bytes());
+                data.extend(attachment.data);
+                records.push((ATTACHMENT_RECORD, data));
+            }
+            // [u8: records_count]([u8: tag][u32: data_len][data_len*u8: data])*
+            payload.write_all(&[records.len() as u8]).unwrap();
+            for (tag, data) in records {
+                payload.write_all(&[tag]).unwrap();
+                payload.write_all(&(data.len() as u32).to_be_bytes()).unwrap();
+                payload.write_all(&data).unwrap();
+            }
+        }
+        let (memory_kib, iterations, parallelism) = match Kdf::from_version(self.version) {
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => (memory_kib, iterations, parallelism),
+            _ => return None,
+        };
+        let mut csprng = OsRng {};
+        let mut salt = [0u8; 32];
+        csprng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; 12];
+        csprng.fill_bytes(&mut nonce);
+        let encryption_key = keys::derive_key_with(Kdf::from_version(self.version), export_password, &salt, Purpose::Bundle)?;
+        let encrypted_payload = cipher::encrypt_with_nonce(encryption_key, nonce, &payload)?;
+        let mut w: Vec<u8> = Vec::with_capacity(4 + 1 + 12 + 32 + 12 + encrypted_payload.len());
+        // [4*u8: magic][u8: version]
+        w.write_all(BUNDLE_MAGIC).unwrap();
+        w.write_all(&[BUNDLE_VERSION]).unwrap();
+        // [u32: memory_kib][u32: iterations][u32: parallelism]
+        w.write_all(&memory_kib.to_be_bytes()).unwrap();
+        w.write_all(&iterations.to_be_bytes()).unwrap();
+        w.write_all(&parallelism.to_be_bytes()).unwrap();
+        // [32*u8: salt][12*u8: nonce]
+        w.write_all(&salt).unwrap();
+        w.write_all(&nonce).unwrap();
+        // encrypted([u16: items_len][...items])
+        w.write_all(&encrypted_payload).unwrap();
+        Some(w)
+    }
+
+    /// Import the items of a bundle created by `export_items`, encrypted with the given vault password.
+    /// Returns the names of the added items.
+    pub fn import_bundle(&mut self, bundle: &[u8], export_password: &str, password: &str, conflicts: ConflictPolicy) -> Option<Vec<String>> {
+        let items = read_bundle(bundle, export_password)?;
+        if self.version == Version::V1 && items.iter().any(|i| i.otp.is_some()) {
+            eprintln!("V1 items cannot contain one-time passwords");
+            return None;
+        }
+        if self.version == Version::V1 && items.iter().any(|i| i.kind != ItemKind::default() || !i.attachments.is_empty()) {
+            eprintln!("V1 items cannot have a kind or contain attachments");
+            return None;
+        }
+        // Items are only added (and replaced) once they have all been encrypted, so that a failure leaves the vault unchanged.
+        let mut imported: Vec<VaultItem> = Vec::with_capacity(items.len());
+        let mut names = Vec::with_capacity(items.len());
+        for item in items {
+            let exists = self.items.iter().chain(&imported).any(|i| i.name == item.name);
+            let name = match conflicts {
+                ConflictPolicy::Rename => self.available_name(&item.name, &imported),
+                ConflictPolicy::Skip if exists => continue,
+                ConflictPolicy::Skip => item.name,
+                ConflictPolicy::Replace => {
+                    imported.retain(|i| i.name != item.name);
+                    item.name
+                }
+            };
+            let mut added = self.encrypt_imported(&name, &item.value, item.otp.as_ref(), item.kind, &item.attachments, password)?;
+            // Imported items keep their original timestamps.
+            if let (Some(timestamps), Some(_)) = (item.timestamps, added.timestamps) {
+                added.timestamps = Some(timestamps);
+            }
+            imported.push(added);
+            names.push(name);
+        }
+        if conflicts == ConflictPolicy::Replace {
+            self.items.retain(|i| !imported.iter().any(|r| r.name == i.name));
+        }
+        self.items.append(&mut imported);
+        Some(names)
+    }
+}
+
+fn read_bundle(bundle: &[u8], export_password: &str) -> Option<Vec<BundledItem>> {
+    let mut r = BufReader::new(bundle);
+    // [4*u8: magic][u8: version]
+    let mut header = [0u8; 5];
+    r.read_exact(&mut header).ok()?;
+    if &header[..4] != BUNDLE_MAGIC || header[4] != BUNDLE_VERSION {
+        eprintln!("Invalid bundle header");
+        return None;
+    }
+    // [u32: memory_kib][u32: iterations][u32: parallelism]
+    let mut parameters = [0u32; 3];
+    for parameter in parameters.iter_mut() {
+        let mut bytes = [0u8; 4];
+        r.read_exact(&mut bytes).ok()?;
+        *parameter = u32::from_be_bytes(bytes);
+    }
+    let [memory_kib, iterations, parallelism] = parameters;
+    if memory_kib > MAX_MEMORY_KIB || iterations > MAX_ITERATIONS || parallelism > MAX_PARALLELISM {
+        eprintln!("Bundle key derivation parameters are too expensive");
+        return None;
+    }
+    // [32*u8: salt][12*u8: nonce]
+    let mut salt = [0u8; 32];
+    r.read_exact(&mut salt).ok()?;
+    let mut nonce = [0u8; 12];
+    r.read_exact(&mut nonce).ok()?;
+    let mut encrypted_payload = Vec::new();
+    r.read_to_end(&mut encrypted_payload).ok()?;
+    let kdf = Kdf::Argon2id {
+        memory_kib,
+        iterations,
+        parallelism,
+    };
+    let encryption_key = keys::derive_key_with(kdf, export_password, &salt, Purpose::Bundle)?;
+    let payload = cipher::decrypt_with_nonce(encryption_key, nonce, &encrypted_payload)?;
+    let mut r = BufReader::new(payload.as_slice());
+    // [u16: items_len]
+    let mut items_len = [0u8; 2];
+    r.read_exact(&mut items_len).ok()?;
+    let mut items = Vec::with_capacity(u16::from_be_bytes(items_len) as usize);
+    for _ in 0..u16::from_be_bytes(items_len) {
+        // [u16: name_len][name_len*u8: name][u32: value_len][value_len*u8: value]
+        let mut name_len = [0u8; 2];
+        r.read_exact(&mut name_len).ok()?;
+        let mut name = vec![0u8; u16::from_be_bytes(name_len) as usize];
+        r.read_exact(&mut name).ok()?;
+        let mut value_len = [0u8; 4];
+        r.read_exact(&mut value_len).ok()?;
+        let mut value = vec![0u8; u32::from_be_bytes(value_len) as usize];
+        r.read_exact(&mut value).ok()?;
+        let mut item = BundledItem {
+            name: String::from_utf8(name).ok()?,
+            value: String::from_utf8(value).ok()?,
+            otp: None,
+            timestamps: None,
+            kind: ItemKind::default(),
+            attachments: Vec::new(),
+        };
+        // [u8: records_count]([u8: tag][u32: data_len][data_len*u8: data])*
+        let mut records_count = [0u8];
+        r.read_exact(&mut records_count).ok()?;
+        for _ in 0..records_count[0] {
+            let mut tag = [0u8];
+            r.read_exact(&mut tag).ok()?;
+            let mut data_len = [0u8; 4];
+            r.read_exact(&mut data_len).ok()?;
+            let mut data = vec![0u8; u32::from_be_bytes(data_len) as usize];
+            r.read_exact(&mut data).ok()?;
+            match tag[0] {
+                OTP_RECORD if item.otp.is_none() => item.otp = Some(Otp::from_uri(std::str::from_utf8(&data).ok()?)?),
+                TIMESTAMPS_RECORD if item.timestamps.is_none() && data.len() == 16 => {
+                    item.timestamps = Some(Timestamps {
+                        created_at: u64::from_be_bytes(data[..8].try_into().ok()?),
+                        modified_at: u64::from_be_bytes(data[8..].try_into().ok()?),
+                    })
+                }
+                KIND_RECORD if item.kind == ItemKind::default() && data.len() == 1 => item.kind = ItemKind::from_byte(data[0]).filter(|&k| k != ItemKind::default())?,
+                ATTACHMENT_RECORD if data.len() >= 2 => {
+                    let name_len = u16::from_be_bytes([data[0], data[1]]) as usize;
+                    item.attachments.push(Attachment {
+                        name: String::from_utf8(data.get(2..2 + name_len)?.to_vec()).ok()?,
+                        data: data[2 + name_len..].to_vec(),
+                    });
+                }
+                // We reject unknown and duplicate records.
+                _ => return None,
+            }
+        }
+        items.push(item);
+    }
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_vault() -> Vault {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.set_otp("GitHub", &Otp::totp("alice", b"12345678901234567890"), "m4st3r").unwrap();
+        vault.set_kind("GitHub", ItemKind::Login).unwrap();
+        vault.add_attachment("GitHub", &recovery_codes(), "m4st3r").unwrap();
+        vault.add("Wifi", "c0rr3ct-h0rs3", "m4st3r").unwrap();
+        vault.add("Bank", "0th3r p4ss", "other password").unwrap();
+        vault
+    }
+
+    fn recovery_codes() -> Attachment {
+        Attachment {
+            name: String::from("recovery-codes.txt"),
+            data: b"1f2e-3d4c\n5b6a-7980\n".to_vec(),
+        }
+    }
+
+    #[test]
+    fn export_import_bundle() {
+        let vault = test_vault();
+        let bundle = vault.export_items(&["GitHub", "Wifi"], "m4st3r", "bundle p4ss").unwrap();
+        assert_eq!(&bundle[..5], b"CYBB\x01");
+        // Test parameters: 512 KiB, 1 iteration, 1 lane.
+        assert_eq!(&bundle[5..17], &[0, 0, 2, 0, 0, 0, 0, 1, 0, 0, 0, 1]);
+        let mut other = Vault::create(None);
+        other.version = Version::Test;
+        other.add("Wifi", "0ld-h0rs3", "n3w m4st3r").unwrap();
+        assert_eq!(other.import_bundle(&bundle, "wrong password", "n3w m4st3r", ConflictPolicy::Rename), None);
+        let names = other.import_bundle(&bundle, "bundle p4ss", "n3w m4st3r", ConflictPolicy::Rename).unwrap();
+        assert_eq!(names, vec!["GitHub", "Wifi (2)"]);
+        assert_eq!(other.get("GitHub", "n3w m4st3r").unwrap(), "kT9#vLq2$wXz7!mP");
+        assert_eq!(other.get_otp("GitHub", "n3w m4st3r").unwrap(), Otp::totp("alice", b"12345678901234567890"));
+        assert_eq!(other.timestamps("GitHub"), vault.timestamps("GitHub"));
+        assert_eq!(other.kind("GitHub"), Some(ItemKind::Login));
+        assert_eq!(other.get_attachments("GitHub", "n3w m4st3r").unwrap(), vec![recovery_codes()]);
+        assert_eq!(other.kind("Wifi (2)"), Some(ItemKind::Password));
+        assert_eq!(other.get("Wifi", "n3w m4st3r").unwrap(), "0ld-h0rs3");
+        assert_eq!(other.get("Wifi (2)", "n3w m4st3r").unwrap(), "c0rr3ct-h0rs3");
+        // Imported items are fingerprinted with the audit key of the vault.
+        other.add("Forum", "kT9#vLq2$wXz7!mP", "f0rum").unwrap();
+        assert_eq!(other.find_reused(), vec![vec!["GitHub", "Forum"]]);
+    }
+
+    #[test]
+    fn import_bundle_conflicts() {
+        let bundle = test_vault().export_items(&["Wifi"], "m4st3r", "bundle p4ss").unwrap();
+        let mut other = Vault::create(None);
+        other.version = Version::Test;
+        other.add("Wifi", "0ld-h0rs3", "m4st3r").unwrap();
+        assert_eq!(other.import_bundle(&bundle, "bundle p4ss", "m4st3r", ConflictPolicy::Skip).unwrap(), Vec::<String>::new());
+        assert_eq!(other.get("Wifi", "m4st3r").unwrap(), "0ld-h0rs3");
+        assert_eq!(other.import_bundle(&bundle, "bundle p4ss", "m4st3r", ConflictPolicy::Replace).unwrap(), vec!["Wifi"]);
+        assert_eq!(other.list(), vec!["Wifi"]);
+        assert_eq!(other.get("Wifi", "m4st3r").unwrap(), "c0rr3ct-h0rs3");
+        // V1 vaults cannot import one-time passwords.
+        let bundle = test_vault().export_items(&["GitHub"], "m4st3r", "bundle p4ss").unwrap();
+        other.version = Version::V1;
+        assert_eq!(other.import_bundle(&bundle, "bundle p4ss", "m4st3r", ConflictPolicy::Rename), None);
+    }
+
+    #[test]
+    fn failed_imports_keep_the_vault_unchanged() {
+        let mut vault = test_vault();
+        // Kinds don't count towards the records limit when they are set last, but imported items get their kind first.
+        vault.add("Notes", "n0t3s", "m4st3r").unwrap();
+        while vault.add_attachment("Notes", &recovery_codes(), "m4st3r").is_some() {}
+        vault.set_kind("Notes", ItemKind::Document).unwrap();
+        let bundle = vault.export_items(&["Wifi", "Notes"], "m4st3r", "bundle p4ss").unwrap();
+        let mut other = Vault::create(None);
+        other.version = Version::Test;
+        other.add("Wifi", "0ld-h0rs3", "m4st3r").unwrap();
+        for conflicts in [ConflictPolicy::Rename, ConflictPolicy::Skip, ConflictPolicy::Replace] {
+            assert_eq!(other.import_bundle(&bundle, "bundle p4ss", "m4st3r", conflicts), None);
+            assert_eq!(other.list(), vec!["Wifi"]);
+            assert_eq!(other.get("Wifi", "m4st3r").unwrap(), "0ld-h0rs3");
+        }
+    }
+
+    #[test]
+    fn export_items_failure() {
+        let vault = test_vault();
+        assert_eq!(vault.export_items(&["GitHub", "Unknown"], "m4st3r", "bundle p4ss"), None);
+        assert_eq!(vault.export_items(&["GitHub", "Bank"], "m4st3r", "bundle p4ss"), None);
+    }
+
+    #[test]
+    fn read_invalid_bundle() {
+        let bundle = test_vault().export_items(&["Wifi"], "m4st3r", "bundle p4ss").unwrap();
+        assert!(read_bundle(&bundle, "bundle p4ss").is_some());
+        assert!(read_bundle(&bundle[..bundle.len() - 1], "bundle p4ss").is_none());
+        assert!(read_bundle(&bundle[..20], "bundle p4ss").is_none());
+        // Header fields are authenticated through the derived key.
+        let test_cases = vec![
+            (0, b'X'),
+            (4, 2),
+            // Memory (128 GiB) and iterations (513) that are too expensive.
+            (5, 8),
+            (11, 2),
+            // Cheaper parameters produce a different key.
+            (16, 2),
+            (20, 0),
+            (50, 0),
+        ];
+        for (index, byte) in test_cases {
+            let mut corrupted = bundle.clone();
+            corrupted[index] = byte;
+            assert!(read_bundle(&corrupted, "bundle p4ss").is_none(), "{}", index);
+        }
+    }
+}
@@ -1,9 +1,101 @@
 use std::io::{BufReader, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rand::rngs::OsRng;
 use rand::RngCore;
 
-use crate::{cipher, keys, Purpose, Version};
+use crate::crypto::hmac;
+use crate::hash::sha256::Sha256;
+use crate::otp::{Otp, OtpCode, OtpKind};
+use crate::{cipher, keys, Kdf, Purpose, Version};
+
+// Tags of the optional records that can be attached to items (since V2).
+const OTP_RECORD: u8 = 1;
+const TIMESTAMPS_RECORD: u8 = 2;
+const FINGERPRINT_RECORD: u8 = 3;
+const KIND_RECORD: u8 = 4;
+const ATTACHMENT_RECORD: u8 = 5;
+
+/// Creation and last modification time of an item (in seconds since the unix epoch).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Timestamps {
+    pub created_at: u64,
+    pub modified_at: u64,
+}
+
+/// Category of an item (e.g. from another password manager), which doesn't change how its value is stored.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ItemKind {
+    #[default]
+    Password,
+    Login,
+    SecureNote,
+    CreditCard,
+    Identity,
+    Document,
+    SshKey,
+    ApiCredential,
+    WirelessRouter,
+    Other,
+}
+
+impl ItemKind {
+    const ALL: [ItemKind; 10] = [
+        ItemKind::Password,
+        ItemKind::Login,
+        ItemKind::SecureNote,
+        ItemKind::CreditCard,
+        ItemKind::Identity,
+        ItemKind::Document,
+        ItemKind::SshKey,
+        ItemKind::ApiCredential,
+        ItemKind::WirelessRouter,
+        ItemKind::Other,
+    ];
+
+    pub(crate) fn to_byte(self) -> u8 {
+        ItemKind::ALL.iter().position(|&k| k == self).unwrap() as u8
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Option<ItemKind> {
+        ItemKind::ALL.get(byte as usize).copied()
+    }
+}
+
+/// File attached to an item (e.g. a scanned document), encrypted with the item's password.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Keyed fingerprint of an item value, used to detect reused values without decrypting items.
+/// Fingerprints are computed with the random audit key of the vault, so every item can be compared whatever its password.
+/// The key id identifies the audit key, without revealing it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Fingerprint {
+    pub(crate) key_id: [u8; 8],
+    pub(crate) digest: [u8; 32],
+}
+
+impl Fingerprint {
+    pub(crate) fn compute(audit_key: &[u8; 32], value: &[u8]) -> Fingerprint {
+        // HMAC-SHA256(audit_key, [1][value])
+        let mut message = vec![1u8];
+        message.extend(value);
+        Fingerprint {
+            key_id: Fingerprint::key_id(audit_key),
+            digest: hmac::authenticate::<Sha256>(audit_key, &message),
+        }
+    }
+
+    pub(crate) fn key_id(audit_key: &[u8; 32]) -> [u8; 8] {
+        // HMAC-SHA256(audit_key, [0]), truncated to 8 bytes.
+        let mut key_id = [0u8; 8];
+        key_id.copy_from_slice(&hmac::authenticate::<Sha256>(audit_key, &[0u8])[..8]);
+        key_id
+    }
+}
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct VaultItem {
@@ -11,6 +103,15 @@ pub struct VaultItem {
     pub name: String,
     salt: [u8; 32],
     pub encrypted_value: Vec<u8>,
+    // [12*u8: nonce][encrypted(otp_uri)]
+    encrypted_otp: Option<Vec<u8>>,
+    // V1 items don't have timestamps.
+    pub(crate) timestamps: Option<Timestamps>,
+    // V1 items and items imported before fingerprints were introduced don't have fingerprints.
+    pub(crate) fingerprint: Option<Fingerprint>,
+    pub(crate) kind: ItemKind,
+    // [12*u8: nonce][encrypted([u16: name_len][name_len*u8: name][...data])]
+    encrypted_attachments: Vec<Vec<u8>>,
 }
 
 impl VaultItem {
@@ -22,11 +123,26 @@ impl VaultItem {
         // Encrypt item content.
         let encryption_key = keys::derive_key(version, password, &salt, Purpose::Password)?;
         let encrypted_value = cipher::encrypt(encryption_key, value.as_bytes())?;
+        let timestamps = match version {
+            Version::V1 => None,
+            Version::Test | Version::V2 => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+                Some(Timestamps {
+                    created_at: now,
+                    modified_at: now,
+                })
+            }
+        };
         let item = VaultItem {
             version,
             name: String::from(name),
             salt,
             encrypted_value,
+            encrypted_otp: None,
+            timestamps,
+            fingerprint: None,
+            kind: ItemKind::default(),
+            encrypted_attachments: Vec::new(),
         };
         Some(item)
     }
@@ -36,9 +152,182 @@ impl VaultItem {
         cipher::decrypt(encryption_key, &self.encrypted_value)
     }
 
+    /// Replace the value of this item (e.g. when rotating a password), keeping its creation time, one-time password and
+    /// attachments.
+    pub fn update(&mut self, value: &str, password: &str) -> Option<()> {
+        let kdf = Kdf::from_version(self.version);
+        let master_key = keys::derive_master_key(kdf, password, &self.salt)?;
+        cipher::decrypt(keys::derive_subkey(&master_key, Purpose::Password), &self.encrypted_value)?;
+        let otp = match self.encrypted_otp {
+            Some(_) => Some(self.otp_with(&master_key)?),
+            None => None,
+        };
+        let attachments = self.attachments_with(&master_key)?;
+        // Values are encrypted with a constant nonce, so the item gets a new salt, and thus new keys.
+        let mut salt = [0u8; 32];
+        OsRng {}.fill_bytes(&mut salt);
+        let master_key = keys::derive_master_key(kdf, password, &salt)?;
+        let encrypted_value = cipher::encrypt(keys::derive_subkey(&master_key, Purpose::Password), value.as_bytes())?;
+        let encrypted_otp = match otp {
+            Some(otp) => Some(VaultItem::encrypt_otp(&master_key, &otp)?),
+            None => None,
+        };
+        let encrypted_attachments = attachments
+            .iter()
+            .map(|a| VaultItem::encrypt_attachment(&master_key, a))
+            .collect::<Option<Vec<Vec<u8>>>>()?;
+        self.salt = salt;
+        self.encrypted_value = encrypted_value;
+        self.encrypted_otp = encrypted_otp;
+        self.encrypted_attachments = encrypted_attachments;
+        self.mark_modified();
+        Some(())
+    }
+
+    /// Attach a one-time password generator to this item, encrypted with the item's password.
+    pub fn set_otp(&mut self, otp: &Otp, password: &str) -> Option<()> {
+        if self.version == Version::V1 {
+            eprintln!("V1 items cannot contain one-time passwords");
+            return None;
+        }
+        let master_key = keys::derive_master_key(Kdf::from_version(self.version), password, &self.salt)?;
+        // We make sure the password is correct, otherwise the item would become inconsistent.
+        cipher::decrypt(keys::derive_subkey(&master_key, Purpose::Password), &self.encrypted_value)?;
+        self.encrypted_otp = Some(VaultItem::encrypt_otp(&master_key, otp)?);
+        self.mark_modified();
+        Some(())
+    }
+
+    pub fn decrypt_otp(&self, password: &str) -> Option<Otp> {
+        self.encrypted_otp.as_ref()?;
+        self.otp_with(&keys::derive_master_key(Kdf::from_version(self.version), password, &self.salt)?)
+    }
+
+    /// Generate a one-time password. Counter-based generators are advanced (and encrypted again), since each of their
+    /// codes can only be used once.
+    pub fn generate_otp_code(&mut self, password: &str, timestamp: u64) -> Option<OtpCode> {
+        self.encrypted_otp.as_ref()?;
+        let master_key = keys::derive_master_key(Kdf::from_version(self.version), password, &self.salt)?;
+        let mut otp = self.otp_with(&master_key)?;
+        let code = otp.generate(timestamp)?;
+        if let OtpKind::Hotp { counter } = &mut otp.kind {
+            *counter = counter.checked_add(1)?;
+            // Using a code doesn't modify the item.
+            self.encrypted_otp = Some(VaultItem::encrypt_otp(&master_key, &otp)?);
+        }
+        Some(code)
+    }
+
+    fn encrypt_otp(master_key: &[u8; 32], otp: &Otp) -> Option<Vec<u8>> {
+        // The key is the same every time the seed is replaced, so each seed gets its own random nonce.
+        let encryption_key = keys::derive_subkey(master_key, Purpose::Otp);
+        let mut nonce = [0u8; 12];
+        OsRng {}.fill_bytes(&mut nonce);
+        let mut encrypted_otp = nonce.to_vec();
+        encrypted_otp.extend(cipher::encrypt_with_nonce(encryption_key, nonce, otp.to_uri().as_bytes())?);
+        Some(encrypted_otp)
+    }
+
+    fn otp_with(&self, master_key: &[u8; 32]) -> Option<Otp> {
+        let (nonce, encrypted_uri) = self.encrypted_otp.as_ref()?.split_at(12);
+        let encryption_key = keys::derive_subkey(master_key, Purpose::Otp);
+        let uri = String::from_utf8(cipher::decrypt_with_nonce(encryption_key, nonce.try_into().ok()?, encrypted_uri)?).ok()?;
+        Otp::from_uri(&uri)
+    }
+
+    /// Attach a file to this item, encrypted with the item's password.
+    pub fn add_attachment(&mut self, attachment: &Attachment, password: &str) -> Option<()> {
+        if self.version == Version::V1 {
+            eprintln!("V1 items cannot contain attachments");
+            return None;
+        }
+        // Records are counted with a single byte.
+        if self.records().len() >= u8::MAX as usize || attachment.name.len() > u16::MAX as usize {
+            eprintln!("Too many attachments or attachment name too long");
+            return None;
+        }
+        let master_key = keys::derive_master_key(Kdf::from_version(self.version), password, &self.salt)?;
+        // We make sure the password is correct, otherwise the item would become inconsistent.
+        cipher::decrypt(keys::derive_subkey(&master_key, Purpose::Password), &self.encrypted_value)?;
+        self.encrypted_attachments.push(VaultItem::encrypt_attachment(&master_key, attachment)?);
+        self.mark_modified();
+        Some(())
+    }
+
+    pub fn decrypt_attachments(&self, password: &str) -> Option<Vec<Attachment>> {
+        if self.encrypted_attachments.is_empty() {
+            return Some(Vec::new());
+        }
+        self.attachments_with(&keys::derive_master_key(Kdf::from_version(self.version), password, &self.salt)?)
+    }
+
+    fn encrypt_attachment(master_key: &[u8; 32], attachment: &Attachment) -> Option<Vec<u8>> {
+        let encryption_key = keys::derive_subkey(master_key, Purpose::Attachment);
+        let mut nonce = [0u8; 12];
+        OsRng {}.fill_bytes(&mut nonce);
+        let mut plaintext = (attachment.name.len() as u16).to_be_bytes().to_vec();
+        plaintext.extend(attachment.name.as_bytes());
+        plaintext.extend(&attachment.data);
+        let mut encrypted_attachment = nonce.to_vec();
+        encrypted_attachment.extend(cipher::encrypt_with_nonce(encryption_key, nonce, &plaintext)?);
+        Some(encrypted_attachment)
+    }
+
+    fn attachments_with(&self, master_key: &[u8; 32]) -> Option<Vec<Attachment>> {
+        let encryption_key = keys::derive_subkey(master_key, Purpose::Attachment);
+        let mut attachments = Vec::with_capacity(self.encrypted_attachments.len());
+        for encrypted_attachment in &self.encrypted_attachments {
+            let (nonce, ciphertext) = encrypted_attachment.split_at(12);
+            let plaintext = cipher::decrypt_with_nonce(encryption_key, nonce.try_into().ok()?, ciphertext)?;
+            let name_len = u16::from_be_bytes(plaintext.get(..2)?.try_into().ok()?) as usize;
+            let name = String::from_utf8(plaintext.get(2..2 + name_len)?.to_vec()).ok()?;
+            attachments.push(Attachment {
+                name,
+                data: plaintext[2 + name_len..].to_vec(),
+            });
+        }
+        Some(attachments)
+    }
+
+    // Items without timestamps (V1) are left unchanged.
+    fn mark_modified(&mut self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs());
+        if let (Some(timestamps), Ok(now)) = (self.timestamps.as_mut(), now) {
+            timestamps.modified_at = now;
+        }
+    }
+
+    fn records(&self) -> Vec<(u8, Vec<u8>)> {
+        let mut records = Vec::new();
+        if let Some(encrypted_otp) = &self.encrypted_otp {
+            records.push((OTP_RECORD, encrypted_otp.clone()));
+        }
+        if let Some(timestamps) = &self.timestamps {
+            // [u64: created_at][u64: modified_at]
+            let mut data = timestamps.created_at.to_be_bytes().to_vec();
+            data.extend(timestamps.modified_at.to_be_bytes());
+            records.push((TIMESTAMPS_RECORD, data));
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            // [8*u8: key_id][32*u8: digest]
+            let mut data = fingerprint.key_id.to_vec();
+            data.extend(fingerprint.digest);
+            records.push((FINGERPRINT_RECORD, data));
+        }
+        if self.kind != ItemKind::default() {
+            records.push((KIND_RECORD, vec![self.kind.to_byte()]));
+        }
+        for encrypted_attachment in &self.encrypted_attachments {
+            records.push((ATTACHMENT_RECORD, encrypted_attachment.clone()));
+        }
+        records
+    }
+
     pub(crate) fn size(&self) -> usize {
+        let fields_len = 2 + self.name.len() + 32 + 2 + self.encrypted_value.len();
         match self.version {
-            Version::Test | Version::V1 => 2 + self.name.as_bytes().len() + 32 + 1 + self.encrypted_value.len(),
+            Version::V1 => fields_len,
+            Version::Test | Version::V2 => fields_len + 1 + self.records().iter().map(|(_, data)| 1 + 4 + data.len()).sum::<usize>(),
         }
     }
 
@@ -50,19 +339,31 @@ impl VaultItem {
     }
 
     pub(crate) fn serialize_into(&self, w: &mut Vec<u8>) {
+        // [u16: name_len]
+        let name_bytes: &[u8] = self.name.as_bytes();
+        w.write_all(&[(name_bytes.len() >> 8) as u8, name_bytes.len() as u8]).unwrap();
+        // [name_len*u8: name]
+        w.write_all(name_bytes).unwrap();
+        // [32*u8: salt]
+        w.write_all(&self.salt).unwrap();
+        // [u16: encrypted_value_len]
+        w.write_all(&[(self.encrypted_value.len() >> 8) as u8, self.encrypted_value.len() as u8]).unwrap();
+        // [encrypted_value_len*u8: encrypted_value]
+        w.write_all(&self.encrypted_value).unwrap();
         match self.version {
-            Version::Test | Version::V1 => {
-                // [u16: name_len]
-                let name_bytes: &[u8] = self.name.as_bytes();
-                w.write_all(&[(name_bytes.len() >> 8) as u8, name_bytes.len() as u8]).unwrap();
-                // [name_len*u8: name]
-                w.write_all(name_bytes).unwrap();
-                // [32*u8: salt]
-                w.write_all(&self.salt).unwrap();
-                // [u16: encrypted_value_len]
-                w.write_all(&[(self.encrypted_value.len() >> 8) as u8, self.encrypted_value.len() as u8]).unwrap();
-                // [encrypted_value_len*u8: encrypted_value]
-                w.write_all(&self.encrypted_value).unwrap();
+            Version::V1 => (),
+            Version::Test | Version::V2 => {
+                let records = self.records();
+                // [u8: records_count]
+                w.write_all(&[records.len() as u8]).unwrap();
+                for (tag, data) in records {
+                    // [u8: tag]
+                    w.write_all(&[tag]).unwrap();
+                    // [u32: data_len]
+                    w.write_all(&(data.len() as u32).to_be_bytes()).unwrap();
+                    // [data_len*u8: data]
+                    w.write_all(&data).unwrap();
+                }
             }
         }
     }
@@ -74,43 +375,85 @@ impl VaultItem {
     }
 
     pub(crate) fn deserialize_from(version: Version, r: &mut BufReader<&[u8]>) -> Option<VaultItem> {
+        // [u16: name_len]
+        let mut name_len_bytes = [0u8; 2];
+        r.read_exact(&mut name_len_bytes).ok()?;
+        let name_len = (((name_len_bytes[0] as u16) << 8) | name_len_bytes[1] as u16) as usize;
+        // [name_len*u8: name]
+        let mut name = vec![0u8; name_len];
+        r.read_exact(&mut name).ok()?;
+        // [32*u8: salt]
+        let mut salt = [0u8; 32];
+        r.read_exact(&mut salt).ok()?;
+        // [u16: encrypted_value_len]
+        let mut encrypted_value_len_bytes = [0u8; 2];
+        r.read_exact(&mut encrypted_value_len_bytes).ok()?;
+        let encrypted_value_len = (((encrypted_value_len_bytes[0] as u16) << 8) | encrypted_value_len_bytes[1] as u16) as usize;
+        // [encrypted_value_len*u8: encrypted_value]
+        let mut encrypted_value = vec![0u8; encrypted_value_len];
+        r.read_exact(&mut encrypted_value).ok()?;
+        let mut item = VaultItem {
+            version,
+            name: String::from_utf8(name).ok()?,
+            salt,
+            encrypted_value,
+            encrypted_otp: None,
+            timestamps: None,
+            fingerprint: None,
+            kind: ItemKind::default(),
+            encrypted_attachments: Vec::new(),
+        };
+        if name_len == 0 || encrypted_value_len == 0 {
+            return None;
+        }
         match version {
-            Version::Test | Version::V1 => {
-                // [u16: name_len]
-                let mut name_len_bytes = [0u8; 2];
-                r.read_exact(&mut name_len_bytes).ok()?;
-                let name_len = (((name_len_bytes[0] as u16) << 8) | name_len_bytes[1] as u16) as usize;
-                // [name_len*u8: name]
-                let mut name = vec![0u8; name_len];
-                r.read_exact(&mut name).ok()?;
-                // [32*u8: salt]
-                let mut salt = [0u8; 32];
-                r.read_exact(&mut salt).ok()?;
-                // [u16: encrypted_value_len]
-                let mut encrypted_value_len_bytes = [0u8; 2];
-                r.read_exact(&mut encrypted_value_len_bytes).ok()?;
-                let encrypted_value_len = (((encrypted_value_len_bytes[0] as u16) << 8) | encrypted_value_len_bytes[1] as u16) as usize;
-                // [encrypted_value_len*u8: encrypted_value]
-                let mut encrypted_value = vec![0u8; encrypted_value_len];
-                r.read_exact(&mut encrypted_value).ok()?;
-                let item = VaultItem {
-                    version,
-                    name: String::from_utf8(name).ok()?,
-                    salt,
-                    encrypted_value,
-                };
-                if name_len == 0 || encrypted_value_len == 0 {
-                    return None;
+            Version::V1 => (),
+            Version::Test | Version::V2 => {
+                // [u8: records_count]
+                let mut records_count = [0u8];
+                r.read_exact(&mut records_count).ok()?;
+                for _ in 0..records_count[0] {
+                    // [u8: tag]
+                    let mut tag = [0u8];
+                    r.read_exact(&mut tag).ok()?;
+                    // [u32: data_len]
+                    let mut data_len_bytes = [0u8; 4];
+                    r.read_exact(&mut data_len_bytes).ok()?;
+                    // [data_len*u8: data]
+                    let mut data = vec![0u8; u32::from_be_bytes(data_len_bytes) as usize];
+                    r.read_exact(&mut data).ok()?;
+                    match tag[0] {
+                        OTP_RECORD if item.encrypted_otp.is_none() && data.len() >= 12 => item.encrypted_otp = Some(data),
+                        TIMESTAMPS_RECORD if item.timestamps.is_none() && data.len() == 16 => {
+                            item.timestamps = Some(Timestamps {
+                                created_at: u64::from_be_bytes(data[..8].try_into().ok()?),
+                                modified_at: u64::from_be_bytes(data[8..].try_into().ok()?),
+                            })
+                        }
+                        FINGERPRINT_RECORD if item.fingerprint.is_none() && data.len() == 40 => {
+                            item.fingerprint = Some(Fingerprint {
+                                key_id: data[..8].try_into().ok()?,
+                                digest: data[8..].try_into().ok()?,
+                            })
+                        }
+                        KIND_RECORD if item.kind == ItemKind::default() && data.len() == 1 => item.kind = ItemKind::from_byte(data[0]).filter(|&k| k != ItemKind::default())?,
+                        // Items can contain several attachments.
+                        ATTACHMENT_RECORD if data.len() >= 12 => item.encrypted_attachments.push(data),
+                        // We reject unknown and duplicate records.
+                        _ => return None,
+                    }
                 }
-                Some(item)
             }
         }
+        Some(item)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::hex;
+    use crate::otp::Otp;
+    use crate::vault::item::{Attachment, Fingerprint, ItemKind, Timestamps};
     use crate::vault::VaultItem;
     use crate::Version;
 
@@ -134,6 +477,11 @@ mod tests {
             name: String::from("4chan pwd"),
             salt: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1],
             encrypted_value: hex::decode("deadbeef").unwrap(),
+            encrypted_otp: None,
+            timestamps: None,
+            fingerprint: None,
+            kind: ItemKind::default(),
+            encrypted_attachments: Vec::new(),
         };
         let serialized: Vec<u8> = item.serialize();
         let deserialized = VaultItem::deserialize(Version::V1, &serialized);
@@ -151,6 +499,11 @@ mod tests {
             name: String::from("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb cccccccccccccccccccccccccccccccccccccccccccccccccc dddddddddddddddddddddddddddddddddddddddddddddddddd eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee ffffffffffffffffffffffffffffffffffffffffffffffffff"),
             salt: [42u8; 32],
             encrypted_value: hex::decode("03958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf908903958e0a08d2d23e708d0b0778c87c83140e089fdf9089").unwrap(),
+            encrypted_otp: None,
+            timestamps: None,
+            fingerprint: None,
+            kind: ItemKind::default(),
+            encrypted_attachments: Vec::new(),
         };
         let serialized: Vec<u8> = item.serialize();
         let deserialized = VaultItem::deserialize(Version::V1, &serialized);
@@ -170,4 +523,222 @@ mod tests {
             assert_eq!(VaultItem::deserialize(Version::V1, &t), None);
         }
     }
+
+    #[test]
+    fn encrypt_decrypt_otp() {
+        let mut item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        assert_eq!(item.decrypt_otp("p4ssw0rd"), None);
+        let otp = Otp::totp("alice", b"12345678901234567890");
+        assert_eq!(item.set_otp(&otp, "password"), None);
+        item.set_otp(&otp, "p4ssw0rd").unwrap();
+        assert_eq!(item.decrypt_otp("p4ssw0rd").unwrap(), otp);
+        assert_eq!(item.decrypt_otp("password"), None);
+        // The one-time password seed and the item value are encrypted with different keys.
+        assert_ne!(item.encrypted_otp.as_ref().unwrap()[..16], item.encrypted_value[..16]);
+    }
+
+    #[test]
+    fn replace_otp() {
+        let mut item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        let first = Otp::totp("alice", b"12345678901234567890");
+        let second = Otp::totp("alice", b"09876543210987654321");
+        item.set_otp(&first, "p4ssw0rd").unwrap();
+        let first_encrypted = item.encrypted_otp.clone().unwrap();
+        item.set_otp(&second, "p4ssw0rd").unwrap();
+        let second_encrypted = item.encrypted_otp.clone().unwrap();
+        assert_eq!(item.decrypt_otp("p4ssw0rd").unwrap(), second);
+        // Both seeds are encrypted with the same key, so they must use different nonces and keystreams.
+        assert_ne!(first_encrypted[..12], second_encrypted[..12]);
+        let ciphertexts_xor: Vec<u8> = first_encrypted[12..].iter().zip(&second_encrypted[12..]).map(|(a, b)| a ^ b).collect();
+        let plaintexts_xor: Vec<u8> = first.to_uri().bytes().zip(second.to_uri().bytes()).map(|(a, b)| a ^ b).collect();
+        assert_ne!(ciphertexts_xor[..plaintexts_xor.len()], plaintexts_xor[..]);
+    }
+
+    #[test]
+    fn v1_items_cannot_contain_otp() {
+        let mut item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        item.version = Version::V1;
+        assert_eq!(item.set_otp(&Otp::totp("alice", b"12345678901234567890"), "p4ssw0rd"), None);
+    }
+
+    #[test]
+    fn serialize_v2_vault_item() {
+        let item = VaultItem {
+            version: Version::V2,
+            name: String::from("4chan pwd"),
+            salt: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1],
+            encrypted_value: hex::decode("deadbeef").unwrap(),
+            encrypted_otp: None,
+            timestamps: None,
+            fingerprint: None,
+            kind: ItemKind::default(),
+            encrypted_attachments: Vec::new(),
+        };
+        assert_eq!(
+            hex::encode(item.serialize()),
+            "0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef00"
+        );
+        assert_eq!(item.size(), item.serialize().len());
+        assert_eq!(item, VaultItem::deserialize(Version::V2, &item.serialize()).unwrap());
+        let item = VaultItem {
+            encrypted_otp: Some(hex::decode("0102030405060708090a0b0ccafebabe").unwrap()),
+            ..item
+        };
+        assert_eq!(
+            hex::encode(item.serialize()),
+            "0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef0101000000100102030405060708090a0b0ccafebabe"
+        );
+        assert_eq!(item.size(), item.serialize().len());
+        assert_eq!(item, VaultItem::deserialize(Version::V2, &item.serialize()).unwrap());
+        let item = VaultItem {
+            timestamps: Some(Timestamps {
+                created_at: 1_700_000_000,
+                modified_at: 1_710_000_000,
+            }),
+            ..item
+        };
+        assert_eq!(
+            hex::encode(item.serialize()),
+            "0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef0201000000100102030405060708090a0b0ccafebabe0200000010000000006553f1000000000065ec8780"
+        );
+        assert_eq!(item.size(), item.serialize().len());
+        assert_eq!(item, VaultItem::deserialize(Version::V2, &item.serialize()).unwrap());
+        let item = VaultItem {
+            fingerprint: Some(Fingerprint {
+                key_id: [1, 2, 3, 4, 5, 6, 7, 8],
+                digest: [0xaa; 32],
+            }),
+            ..item
+        };
+        assert_eq!(
+            hex::encode(item.serialize()),
+            "0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef0301000000100102030405060708090a0b0ccafebabe0200000010000000006553f1000000000065ec878003000000280102030405060708aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+        );
+        assert_eq!(item.size(), item.serialize().len());
+        assert_eq!(item, VaultItem::deserialize(Version::V2, &item.serialize()).unwrap());
+    }
+
+    #[test]
+    fn encrypt_decrypt_attachments() {
+        let mut item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        assert_eq!(item.decrypt_attachments("p4ssw0rd").unwrap(), Vec::new());
+        let scan = Attachment {
+            name: String::from("passport.pdf"),
+            data: b"%PDF-1.7".to_vec(),
+        };
+        let empty = Attachment {
+            name: String::new(),
+            data: Vec::new(),
+        };
+        assert_eq!(item.add_attachment(&scan, "password"), None);
+        item.add_attachment(&scan, "p4ssw0rd").unwrap();
+        item.add_attachment(&empty, "p4ssw0rd").unwrap();
+        assert_eq!(item.decrypt_attachments("p4ssw0rd").unwrap(), vec![scan.clone(), empty]);
+        assert_eq!(item.decrypt_attachments("password"), None);
+        // Attachments are encrypted with random nonces.
+        item.add_attachment(&scan, "p4ssw0rd").unwrap();
+        assert_ne!(item.encrypted_attachments[0], item.encrypted_attachments[2]);
+        let item = VaultItem { kind: ItemKind::Document, ..item };
+        assert_eq!(item.size(), item.serialize().len());
+        assert_eq!(item, VaultItem::deserialize(Version::Test, &item.serialize()).unwrap());
+        let mut v1_item = VaultItem::encrypt(Version::V1, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        assert_eq!(v1_item.add_attachment(&scan, "p4ssw0rd"), None);
+    }
+
+    #[test]
+    fn update_vault_item() {
+        let mut item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        let otp = Otp::totp("alice", b"12345678901234567890");
+        let scan = Attachment {
+            name: String::from("passport.pdf"),
+            data: b"%PDF-1.7".to_vec(),
+        };
+        item.set_otp(&otp, "p4ssw0rd").unwrap();
+        item.add_attachment(&scan, "p4ssw0rd").unwrap();
+        let old = Timestamps {
+            created_at: 1_600_000_000,
+            modified_at: 1_600_000_000,
+        };
+        item.timestamps = Some(old);
+        assert_eq!(item.update("n3w stufF", "password"), None);
+        assert_eq!(item.timestamps, Some(old));
+        let salt = item.salt;
+        item.update("n3w stufF", "p4ssw0rd").unwrap();
+        assert_eq!(item.decrypt("p4ssw0rd").unwrap(), b"n3w stufF");
+        assert_eq!(item.decrypt_otp("p4ssw0rd").unwrap(), otp);
+        assert_eq!(item.decrypt_attachments("p4ssw0rd").unwrap(), vec![scan]);
+        // The value is encrypted with new keys, since the nonce is constant.
+        assert_ne!(item.salt, salt);
+        let timestamps = item.timestamps.unwrap();
+        assert_eq!(timestamps.created_at, old.created_at);
+        assert!(timestamps.modified_at > 1_700_000_000);
+        // Changing the one-time password is a modification too.
+        item.timestamps = Some(old);
+        item.set_otp(&otp, "p4ssw0rd").unwrap();
+        assert!(item.timestamps.unwrap().modified_at > 1_700_000_000);
+        let mut v1_item = VaultItem::encrypt(Version::V1, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        v1_item.update("n3w stufF", "p4ssw0rd").unwrap();
+        assert_eq!(v1_item.decrypt("p4ssw0rd").unwrap(), b"n3w stufF");
+        assert_eq!(v1_item.timestamps, None);
+    }
+
+    #[test]
+    fn item_kinds() {
+        for kind in ItemKind::ALL {
+            assert_eq!(ItemKind::from_byte(kind.to_byte()), Some(kind));
+        }
+        assert_eq!(ItemKind::from_byte(10), None);
+    }
+
+    #[test]
+    fn compute_fingerprints() {
+        // Cross-checked with Python's hmac module.
+        let fingerprint = Fingerprint::compute(&[42u8; 32], b"s3cr3t stufF");
+        assert_eq!(hex::encode(fingerprint.key_id), "42a1774d9896b943");
+        assert_eq!(hex::encode(fingerprint.digest), "626b2ebc8595020ef480ce296367534995c75abd9c3033dda079310fabf38a00");
+        assert_eq!(fingerprint, Fingerprint::compute(&[42u8; 32], b"s3cr3t stufF"));
+        assert_ne!(fingerprint.digest, Fingerprint::compute(&[42u8; 32], b"s3cr3t stuff").digest);
+        let other_key = Fingerprint::compute(&[43u8; 32], b"s3cr3t stufF");
+        assert_ne!(fingerprint.key_id, other_key.key_id);
+        assert_ne!(fingerprint.digest, other_key.digest);
+    }
+
+    #[test]
+    fn encrypted_items_timestamps() {
+        let item = VaultItem::encrypt(Version::Test, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap();
+        let timestamps = item.timestamps.unwrap();
+        assert!(timestamps.created_at > 1_700_000_000);
+        assert_eq!(timestamps.created_at, timestamps.modified_at);
+        assert_eq!(VaultItem::encrypt(Version::V1, "item 1", "s3cr3t stufF", "p4ssw0rd").unwrap().timestamps, None);
+    }
+
+    #[test]
+    fn deserialize_v2_vault_item_failure() {
+        let test_cases = vec![
+            // missing records count
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef").unwrap(),
+            // truncated record
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef010100000004cafe").unwrap(),
+            // unknown record
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef01ff00000004cafebabe").unwrap(),
+            // duplicate record
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef02010000000c000102030405060708090a0b010000000c000102030405060708090a0b").unwrap(),
+            // invalid timestamps record
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef01020000000f000000006553f1000000000065ec87").unwrap(),
+            // one-time password record without nonce
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef010100000004cafebabe").unwrap(),
+            // invalid fingerprint record
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef010300000004cafebabe").unwrap(),
+            // unknown or default kind
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef0104000000010a").unwrap(),
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef01040000000100").unwrap(),
+            // duplicate kind
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef02040000000101040000000102").unwrap(),
+            // attachment record without nonce
+            hex::decode("0009346368616e2070776400010203040506070809000102030405060708090001020304050607080900010004deadbeef010500000004cafebabe").unwrap(),
+        ];
+        for t in test_cases {
+            assert_eq!(VaultItem::deserialize(Version::V2, &t), None);
+        }
+    }
 }
@@ -1,19 +1,48 @@
 use std::io::{BufReader, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rand::rngs::OsRng;
 use rand::RngCore;
 
-use crate::vault::item::VaultItem;
+use crate::armor::{self, Kind};
+use crate::import::csv::{self, CsvFormat};
+use crate::import::{Import, ImportedItem};
+use crate::kdbx::Database;
+use crate::otp::{Otp, OtpCode};
+use crate::password::{self, BreachDatabase};
+use crate::vault::item::{Fingerprint, VaultItem};
+use crate::vault::recovery::{Recovery, WRAPPED_KEY_LEN};
 use crate::{cipher, keys, Purpose, Version};
 
+pub use self::audit::{AuditOptions, AuditReport, Finding, ItemReport};
+pub use self::bundle::ConflictPolicy;
+pub use self::item::{Attachment, ItemKind, Timestamps};
+
+mod audit;
+mod bundle;
 mod item;
+mod recovery;
 
 pub struct Vault {
     pub version: Version,
     salt: [u8; 32],
     items: Vec<VaultItem>,
+    recovery: Option<Recovery>,
+    // Random key used to fingerprint every item, stored with the encrypted items (since V2).
+    audit_key: [u8; 32],
+}
+
+// Unencrypted part of serialized vaults.
+struct Header {
+    version: Version,
+    salt: [u8; 32],
+    // File keys wrapped with the password and with the recovery key, for vaults that have one.
+    wrapped_file_keys: Option<([u8; WRAPPED_KEY_LEN], [u8; WRAPPED_KEY_LEN])>,
 }
 
+// Set in the version byte of serialized vaults that have a recovery key.
+const RECOVERY_FLAG: u8 = 0x80;
+
 impl Vault {
     pub fn create(salt: Option<[u8; 32]>) -> Vault {
         let salt: [u8; 32] = salt.unwrap_or_else(|| {
@@ -23,14 +52,34 @@ impl Vault {
             salt
         });
         Vault {
-            version: Version::V1,
+            version: Version::V2,
             salt,
             items: Vec::new(),
+            recovery: None,
+            audit_key: random_audit_key(),
         }
     }
 
     pub fn add(&mut self, name: &str, value: &str, password: &str) -> Option<()> {
-        VaultItem::encrypt(self.version, name, value, password).map(|item| self.items.push(item))
+        let mut item = VaultItem::encrypt(self.version, name, value, password)?;
+        // V1 items cannot store fingerprints.
+        if self.version != Version::V1 {
+            item.fingerprint = Some(Fingerprint::compute(&self.audit_key, value.as_bytes()));
+        }
+        self.items.push(item);
+        Some(())
+    }
+
+    /// Replace the value of an existing item (e.g. when rotating a password), which must use the same password. The item
+    /// keeps its creation time, one-time password and attachments.
+    pub fn update(&mut self, name: &str, value: &str, password: &str) -> Option<()> {
+        let item = self.items.iter_mut().find(|i| i.name == name)?;
+        item.update(value, password)?;
+        // V1 items cannot store fingerprints.
+        if self.version != Version::V1 {
+            item.fingerprint = Some(Fingerprint::compute(&self.audit_key, value.as_bytes()));
+        }
+        Some(())
     }
 
     pub fn remove(&mut self, name: &str) {
@@ -45,27 +94,198 @@ impl Vault {
             .and_then(|v| String::from_utf8(v).ok())
     }
 
+    /// Attach a one-time password generator to an existing item, which must use the same password.
+    pub fn set_otp(&mut self, name: &str, otp: &Otp, password: &str) -> Option<()> {
+        self.items.iter_mut().find(|i| i.name == name)?.set_otp(otp, password)
+    }
+
+    pub fn get_otp(&self, name: &str, password: &str) -> Option<Otp> {
+        self.items.iter().find(|i| i.name == name).and_then(|i| i.decrypt_otp(password))
+    }
+
+    /// Return the current one-time password for the given item, and how long it remains valid. The counter of HOTP
+    /// generators is incremented, so every call returns a new code.
+    pub fn get_otp_code(&mut self, name: &str, password: &str) -> Option<OtpCode> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        self.items.iter_mut().find(|i| i.name == name)?.generate_otp_code(password, timestamp)
+    }
+
+    /// Return the items that appear in known data breaches, with the number of times they appear.
+    /// Only items that can be decrypted with the given password are checked.
+    pub fn check_breached(&self, password: &str, db: &mut BreachDatabase) -> Result<Vec<(String, u64)>, String> {
+        let mut breached = Vec::new();
+        for item in &self.items {
+            let Some(value) = item.decrypt(password).and_then(|v| String::from_utf8(v).ok()) else {
+                continue;
+            };
+            let count = password::check_breached(&value, db)?;
+            if count > 0 {
+                breached.push((item.name.clone(), count));
+            }
+        }
+        Ok(breached)
+    }
+
+    /// Categorize an existing item, e.g. to display it with the relevant fields.
+    pub fn set_kind(&mut self, name: &str, kind: ItemKind) -> Option<()> {
+        if self.version == Version::V1 {
+            eprintln!("V1 items cannot have a kind");
+            return None;
+        }
+        self.items.iter_mut().find(|i| i.name == name)?.kind = kind;
+        Some(())
+    }
+
+    pub fn kind(&self, name: &str) -> Option<ItemKind> {
+        self.items.iter().find(|i| i.name == name).map(|i| i.kind)
+    }
+
+    /// Attach a file to an existing item, which must use the same password.
+    pub fn add_attachment(&mut self, name: &str, attachment: &Attachment, password: &str) -> Option<()> {
+        self.items.iter_mut().find(|i| i.name == name)?.add_attachment(attachment, password)
+    }
+
+    pub fn get_attachments(&self, name: &str, password: &str) -> Option<Vec<Attachment>> {
+        self.items.iter().find(|i| i.name == name)?.decrypt_attachments(password)
+    }
+
+    pub fn timestamps(&self, name: &str) -> Option<Timestamps> {
+        self.items.iter().find(|i| i.name == name).and_then(|i| i.timestamps)
+    }
+
+    /// Add items imported from another password manager, encrypted with the given password. Items are renamed when
+    /// their name is already used (e.g. "GitHub (2)"). Returns the names of the added items.
+    pub fn import(&mut self, import: &Import, password: &str) -> Option<Vec<String>> {
+        self.import_with(import, |_| String::from(password))
+    }
+
+    /// Add imported items, encrypting each of them with the password chosen by the given policy (e.g. to use a
+    /// different password for a folder).
+    pub fn import_with<F: FnMut(&ImportedItem) -> String>(&mut self, import: &Import, mut password_policy: F) -> Option<Vec<String>> {
+        if self.version == Version::V1 && import.items.iter().any(|i| i.otp.is_some()) {
+            eprintln!("V1 items cannot contain one-time passwords");
+            return None;
+        }
+        if self.version == Version::V1 && import.items.iter().any(|i| i.kind != ItemKind::default() || !i.attachments.is_empty()) {
+            eprintln!("V1 items cannot have a kind or contain attachments");
+            return None;
+        }
+        // Items are only added once they have all been encrypted, so that a failure leaves the vault unchanged.
+        let mut imported: Vec<VaultItem> = Vec::with_capacity(import.items.len());
+        for item in &import.items {
+            let name = self.available_name(&item.name, &imported);
+            let password = password_policy(item);
+            imported.push(self.encrypt_imported(&name, &item.value, item.otp.as_ref(), item.kind, &item.attachments, &password)?);
+        }
+        let names = imported.iter().map(|i| i.name.clone()).collect();
+        self.items.append(&mut imported);
+        Some(names)
+    }
+
+    // Encrypt an imported item without adding it to the vault.
+    fn encrypt_imported(&self, name: &str, value: &str, otp: Option<&Otp>, kind: ItemKind, attachments: &[Attachment], password: &str) -> Option<VaultItem> {
+        let mut item = VaultItem::encrypt(self.version, name, value, password)?;
+        // V1 items cannot store fingerprints.
+        if self.version != Version::V1 {
+            item.fingerprint = Some(Fingerprint::compute(&self.audit_key, value.as_bytes()));
+        }
+        if let Some(otp) = otp {
+            item.set_otp(otp, password)?;
+        }
+        item.kind = kind;
+        for attachment in attachments {
+            item.add_attachment(attachment, password)?;
+        }
+        Some(item)
+    }
+
+    // Return the given name, or the first name with a numeric suffix (e.g. "GitHub (2)") that is used neither in the vault
+    // nor by the items being imported.
+    fn available_name(&self, name: &str, imported: &[VaultItem]) -> String {
+        let mut available = String::from(name);
+        let mut suffix = 2;
+        while self.items.iter().chain(imported).any(|i| i.name == available) {
+            available = format!("{} ({})", name, suffix);
+            suffix += 1;
+        }
+        available
+    }
+
+    /// Export the items that can be decrypted with the given password as unencrypted CSV. Since anyone who can read the
+    /// result can read all passwords, `plaintext_acknowledged` must be set to confirm that the user understands it.
+    pub fn export_csv(&self, password: &str, format: &CsvFormat, plaintext_acknowledged: bool) -> Result<String, String> {
+        if !plaintext_acknowledged {
+            return Err(String::from("plaintext exports must be explicitly acknowledged"));
+        }
+        let items: Vec<ImportedItem> = self
+            .items
+            .iter()
+            .filter_map(|i| {
+                let value = i.decrypt(password).and_then(|v| String::from_utf8(v).ok())?;
+                Some(ImportedItem {
+                    name: i.name.clone(),
+                    value,
+                    otp: i.decrypt_otp(password),
+                    ..ImportedItem::default()
+                })
+            })
+            .collect();
+        Ok(csv::write(&items, format))
+    }
+
+    /// Export the items that can be decrypted with the given password as unencrypted KeePass 2 XML, which requires the
+    /// same acknowledgement as CSV exports.
+    pub fn export_keepass_xml(&self, password: &str, plaintext_acknowledged: bool) -> Result<String, String> {
+        if !plaintext_acknowledged {
+            return Err(String::from("plaintext exports must be explicitly acknowledged"));
+        }
+        Ok(Database::from_vault(self, password, "cybele").to_xml())
+    }
+
     pub fn list(&self) -> Vec<String> {
         self.items.iter().map(|i| i.name.clone()).collect()
     }
 
     pub fn serialize(&self, password: &str) -> Option<Vec<u8>> {
         match self.version {
-            Version::Test | Version::V1 => {
+            Version::Test | Version::V1 | Version::V2 => {
                 let items_len: usize = self.items.iter().map(|i| i.size()).sum();
                 let mut items_writer: Vec<u8> = Vec::with_capacity(2 + items_len);
                 items_writer.write_all(&[(self.items.len() >> 8) as u8, self.items.len() as u8]).unwrap();
                 self.items.iter().for_each(|i| i.serialize_into(&mut items_writer));
-                // We encrypt the serialized items, including the length.
-                let encryption_key = keys::derive_key(self.version, password, &self.salt, Purpose::File)?;
-                let encrypted_items = cipher::encrypt(encryption_key, &items_writer)?;
-                // We serialize the result.
-                let mut w: Vec<u8> = Vec::with_capacity(1 + 32 + encrypted_items.len());
-                // [u8: version]
-                w.write_all(&[self.version.to_byte()]).unwrap();
+                // [32*u8: audit_key]
+                if self.version != Version::V1 {
+                    items_writer.write_all(&self.audit_key).unwrap();
+                }
+                let password_key = keys::derive_key(self.version, password, &self.salt, Purpose::File)?;
+                let Some(recovery) = &self.recovery else {
+                    // We encrypt the serialized items, including the length.
+                    let encrypted_items = cipher::encrypt(password_key, &items_writer)?;
+                    // We serialize the result.
+                    let mut w: Vec<u8> = Vec::with_capacity(1 + 32 + encrypted_items.len());
+                    // [u8: version]
+                    w.write_all(&[self.version.to_byte()]).unwrap();
+                    // [32*u8: salt]
+                    w.write_all(&self.salt).unwrap();
+                    // encrypted([u16: items_len][...items][32*u8: audit_key])
+                    w.write_all(&encrypted_items).unwrap();
+                    return Some(w);
+                };
+                // Vaults with a recovery key are encrypted with their random file key instead.
+                let mut nonce = [0u8; 12];
+                OsRng {}.fill_bytes(&mut nonce);
+                let encrypted_items = cipher::encrypt_with_nonce(recovery.file_key, nonce, &items_writer)?;
+                let mut w: Vec<u8> = Vec::with_capacity(1 + 32 + 2 * WRAPPED_KEY_LEN + 12 + encrypted_items.len());
+                // [u8: version | RECOVERY_FLAG]
+                w.write_all(&[self.version.to_byte() | RECOVERY_FLAG]).unwrap();
                 // [32*u8: salt]
                 w.write_all(&self.salt).unwrap();
-                // encrypted([u16: items_len][...items])
+                // [60*u8: file key wrapped with the password][60*u8: file key wrapped with the recovery key]
+                w.write_all(&recovery::wrap_key(password_key, &recovery.file_key)?).unwrap();
+                w.write_all(&recovery.wrapped_file_key).unwrap();
+                // [12*u8: nonce]
+                w.write_all(&nonce).unwrap();
+                // encrypted([u16: items_len][...items][32*u8: audit_key])
                 w.write_all(&encrypted_items).unwrap();
                 Some(w)
             }
@@ -73,20 +293,44 @@ impl Vault {
     }
 
     pub fn deserialize(bin: &[u8], password: &str) -> Option<Vault> {
+        Vault::deserialize_with(bin, |header| {
+            let password_key = keys::derive_key(header.version, password, &header.salt, Purpose::File)?;
+            match header.wrapped_file_keys {
+                Some((wrapped_file_key, _)) => recovery::unwrap_key(password_key, &wrapped_file_key),
+                None => Some(password_key),
+            }
+        })
+    }
+
+    // Deserialize a vault, whose items are decrypted with the key returned by `unlock`.
+    fn deserialize_with<F: FnOnce(&Header) -> Option<[u8; 32]>>(bin: &[u8], unlock: F) -> Option<Vault> {
         // [u8: version]
         let mut r = BufReader::new(bin);
         let mut version_byte = [0u8];
         r.read_exact(&mut version_byte).ok()?;
-        let version = Version::from_byte(version_byte[0])?;
+        let version = Version::from_byte(version_byte[0] & !RECOVERY_FLAG)?;
         // [32*u8: salt]
         let mut salt = [0u8; 32];
         r.read_exact(&mut salt).ok()?;
-        // encrypted([u16: items_len][...items])
-        let mut encrypted_items = vec![0u8; bin.len() - 32 - 1];
-        r.read_exact(&mut encrypted_items).ok()?;
+        // Vaults without a recovery key use a zero nonce (see `cipher::encrypt`).
+        let mut nonce = [0u8; 12];
+        let mut wrapped_file_keys = None;
+        if version_byte[0] & RECOVERY_FLAG != 0 {
+            // [60*u8: file key wrapped with the password][60*u8: file key wrapped with the recovery key][12*u8: nonce]
+            let mut wrapped_with_password = [0u8; WRAPPED_KEY_LEN];
+            r.read_exact(&mut wrapped_with_password).ok()?;
+            let mut wrapped_with_recovery_key = [0u8; WRAPPED_KEY_LEN];
+            r.read_exact(&mut wrapped_with_recovery_key).ok()?;
+            r.read_exact(&mut nonce).ok()?;
+            wrapped_file_keys = Some((wrapped_with_password, wrapped_with_recovery_key));
+        }
+        let header = Header { version, salt, wrapped_file_keys };
+        // encrypted([u16: items_len][...items][32*u8: audit_key])
+        let mut encrypted_items = Vec::new();
+        r.read_to_end(&mut encrypted_items).ok()?;
         // We decrypt the serialized items.
-        let encryption_key = keys::derive_key(version, password, &salt, Purpose::File)?;
-        let decrypted_items = cipher::decrypt(encryption_key, &encrypted_items)?;
+        let encryption_key = unlock(&header)?;
+        let decrypted_items = cipher::decrypt_with_nonce(encryption_key, nonce, &encrypted_items)?;
         // We deserialize the resulting items.
         let mut items_reader = BufReader::new(decrypted_items.as_slice());
         let mut items_len_bytes = [0u8; 2];
@@ -97,32 +341,99 @@ impl Vault {
             let item = VaultItem::deserialize_from(version, &mut items_reader).unwrap();
             items.push(item);
         });
-        Some(Vault { version, salt, items })
+        // V1 vaults cannot store fingerprints, so their audit key is never stored.
+        let mut rest = Vec::new();
+        items_reader.read_to_end(&mut rest).ok()?;
+        let audit_key = match (version, rest.len()) {
+            (Version::V1, 0) => random_audit_key(),
+            (Version::Test | Version::V2, 32) => rest.try_into().ok()?,
+            _ => return None,
+        };
+        let recovery = wrapped_file_keys.map(|(_, wrapped_file_key)| Recovery {
+            file_key: encryption_key,
+            wrapped_file_key,
+        });
+        Some(Vault {
+            version,
+            salt,
+            items,
+            recovery,
+            audit_key,
+        })
+    }
+
+    /// Serialize the vault to ASCII-armored text, which can be pasted in emails, chats or printed.
+    pub fn serialize_armored(&self, password: &str) -> Option<String> {
+        Some(armor::encode(Kind::Vault, &self.serialize(password)?))
+    }
+
+    pub fn deserialize_armored(text: &str, password: &str) -> Option<Vault> {
+        match armor::decode(text) {
+            Ok((Kind::Vault, bin)) => Vault::deserialize(&bin, password),
+            Ok((kind, _)) => {
+                eprintln!("Expected an armored vault, got: {:?}", kind);
+                None
+            }
+            Err(e) => {
+                eprintln!("Invalid armored vault: {}", e);
+                None
+            }
+        }
     }
 }
 
+fn random_audit_key() -> [u8; 32] {
+    let mut audit_key = [0u8; 32];
+    OsRng {}.fill_bytes(&mut audit_key);
+    audit_key
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::hash::sha1;
     use crate::hex;
-    use crate::vault::Vault;
-    use crate::Version;
+    use std::path::Path;
+
+    use crate::armor::{self, Kind};
+    use crate::import::csv::{self, CsvFormat};
+    use crate::import::{pass, Import, ImportedItem};
+    use crate::kdbx::Database;
+    use crate::otp::{Otp, OtpKind};
+    use crate::password::BreachDatabase;
+    use crate::vault::{Attachment, ItemKind, Vault};
+    use crate::{cipher, keys, Purpose, Version};
 
     #[test]
     fn serialize_deserialize_empty_vault() {
         let mut vault = Vault::create(Some([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1]));
         vault.version = Version::Test;
         let serialized = vault.serialize("file password").unwrap();
-        assert_eq!(serialized.len(), 51); // don't forget the 16-byte trailing mac!
-        assert_eq!(
-            serialized.starts_with(&hex::decode("000001020304050607080900010203040506070809000102030405060708090001").unwrap()),
-            true
-        );
+        assert_eq!(serialized.len(), 83); // don't forget the 32-byte audit key and the 16-byte trailing mac!
+        assert!(serialized.starts_with(&hex::decode("000001020304050607080900010203040506070809000102030405060708090001").unwrap()));
         let deserialized = Vault::deserialize(&serialized, "file password").unwrap();
         assert_eq!(deserialized.version, vault.version);
         assert_eq!(deserialized.salt, vault.salt);
         assert_eq!(deserialized.items.len(), 0);
     }
 
+    #[test]
+    fn deserialize_vault_without_audit_key() {
+        // [u8: version][32*u8: salt][encrypted([u16: items_len])]
+        let file_key = keys::derive_key(Version::Test, "file password", &[42u8; 32], Purpose::File).unwrap();
+        let v1_file_key = keys::derive_key(Version::V1, "file password", &[42u8; 32], Purpose::File).unwrap();
+        let serialized = [&[1u8], &[42u8; 32][..], &cipher::encrypt(v1_file_key, &[0, 0]).unwrap()].concat();
+        let vault = Vault::deserialize(&serialized, "file password").unwrap();
+        assert_eq!(vault.items.len(), 0);
+        assert_ne!(vault.audit_key, [0u8; 32]);
+        // Other vaults always store their audit key, so missing or partial keys mean the data is corrupted.
+        let serialized = [&[0u8], &[42u8; 32][..], &cipher::encrypt(file_key, &[0, 0]).unwrap()].concat();
+        assert!(Vault::deserialize(&serialized, "file password").is_none());
+        let serialized = [&[0u8], &[42u8; 32][..], &cipher::encrypt(file_key, &[0, 0, 1, 2, 3]).unwrap()].concat();
+        assert!(Vault::deserialize(&serialized, "file password").is_none());
+        let serialized = [&[1u8], &[42u8; 32][..], &cipher::encrypt(v1_file_key, &[&[0u8; 2][..], &[7u8; 32]].concat()).unwrap()].concat();
+        assert!(Vault::deserialize(&serialized, "file password").is_none());
+    }
+
     #[test]
     fn serialize_deserialize_vault() {
         let mut vault = Vault::create(Some([42u8; 32]));
@@ -137,6 +448,21 @@ mod tests {
         assert_eq!(deserialized.items, vault.items);
     }
 
+    #[test]
+    fn serialize_deserialize_armored_vault() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("item 1", "secret stuff", "s3cr3t p4ss0rd");
+        let armored = vault.serialize_armored("f1l3 p4ssw0rd").unwrap();
+        assert!(armored.starts_with("-----BEGIN CYBELE VAULT-----\nVersion: 1\n\nAC"));
+        let deserialized = Vault::deserialize_armored(&format!("  {}", armored.replace('\n', "\r\n")), "f1l3 p4ssw0rd").unwrap();
+        assert_eq!(deserialized.items, vault.items);
+        assert!(Vault::deserialize_armored(&armored, "wrong p4ssw0rd").is_none());
+        assert!(Vault::deserialize_armored(&armored.replacen("AC", "AD", 1), "f1l3 p4ssw0rd").is_none());
+        let bundle = armor::encode(Kind::Bundle, &vault.serialize("f1l3 p4ssw0rd").unwrap());
+        assert!(Vault::deserialize_armored(&bundle, "f1l3 p4ssw0rd").is_none());
+    }
+
     #[test]
     fn deserialize_failure() {
         let mut vault = Vault::create(Some([42u8; 32]));
@@ -145,17 +471,17 @@ mod tests {
         vault.add("item 2", "more secret stuff", "another s3cr3t p4ss0rd");
         let serialized = vault.serialize("password").unwrap();
         // Truncated in the middle of salt.
-        assert_eq!(Vault::deserialize(&serialized[0..16], "password").is_none(), true);
+        assert!(Vault::deserialize(&serialized[0..16], "password").is_none());
         // Truncated after salt.
-        assert_eq!(Vault::deserialize(&serialized[0..33], "password").is_none(), true);
+        assert!(Vault::deserialize(&serialized[0..33], "password").is_none());
         // Mac truncated.
-        assert_eq!(Vault::deserialize(&serialized[0..serialized.len() - 4], "password").is_none(), true);
+        assert!(Vault::deserialize(&serialized[0..serialized.len() - 4], "password").is_none());
         // Invalid password.
-        assert_eq!(Vault::deserialize(&serialized, "passw0rd").is_none(), true);
+        assert!(Vault::deserialize(&serialized, "passw0rd").is_none());
         // Additional trailing bytes.
-        let mut trailing = Vec::from(serialized);
+        let mut trailing = serialized;
         trailing.push(42u8);
-        assert_eq!(Vault::deserialize(&trailing, "password").is_none(), true);
+        assert!(Vault::deserialize(&trailing, "password").is_none());
     }
 
     #[test]
@@ -177,4 +503,177 @@ mod tests {
         assert_eq!("secret stuff", vault.get("item 1", "password1").unwrap());
         assert_eq!(None, vault.get("item 2", "password2"));
     }
+
+    #[test]
+    fn add_otp_items() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("email", "s3cr3t", "password");
+        let otp = Otp::from_uri("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&digits=8").unwrap();
+        assert_eq!(vault.get_otp("email", "password"), None);
+        assert_eq!(vault.set_otp("email", &otp, "p4ssword"), None);
+        assert_eq!(vault.set_otp("unknown item", &otp, "password"), None);
+        vault.set_otp("email", &otp, "password").unwrap();
+        let serialized = vault.serialize("file password").unwrap();
+        let mut deserialized = Vault::deserialize(&serialized, "file password").unwrap();
+        assert_eq!(deserialized.get_otp("email", "password").unwrap(), otp);
+        assert_eq!(deserialized.get_otp("email", "p4ssword"), None);
+        assert_eq!(deserialized.get("email", "password").unwrap(), "s3cr3t");
+        let code = deserialized.get_otp_code("email", "password").unwrap();
+        assert_eq!(code.code.len(), 8);
+        assert!(code.remaining_seconds.unwrap() <= 30);
+        assert_eq!(deserialized.get_otp("email", "password").unwrap(), otp);
+    }
+
+    #[test]
+    fn advance_hotp_counters() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("bank", "s3cr3t", "password");
+        // Test vector from https://www.rfc-editor.org/rfc/rfc4226#appendix-D
+        let otp = Otp::from_uri("otpauth://hotp/Bank:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=3").unwrap();
+        vault.set_otp("bank", &otp, "password").unwrap();
+        let timestamps = vault.timestamps("bank");
+        assert_eq!(vault.get_otp_code("bank", "p4ssword"), None);
+        assert_eq!(vault.get_otp_code("bank", "password").unwrap().code, "969429");
+        assert_eq!(vault.get_otp_code("bank", "password").unwrap().code, "338314");
+        let mut deserialized = Vault::deserialize(&vault.serialize("file password").unwrap(), "file password").unwrap();
+        let code = deserialized.get_otp_code("bank", "password").unwrap();
+        assert_eq!(code.code, "254676");
+        assert_eq!(code.remaining_seconds, None);
+        assert_eq!(deserialized.get_otp("bank", "password").unwrap().kind, OtpKind::Hotp { counter: 6 });
+        assert_eq!(deserialized.timestamps("bank"), timestamps);
+        assert_eq!(deserialized.get_otp_code("unknown item", "password"), None);
+    }
+
+    #[test]
+    fn add_kinds_and_attachments() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("passport", "X1234567", "password");
+        let scan = Attachment {
+            name: String::from("passport.pdf"),
+            data: b"%PDF-1.7".to_vec(),
+        };
+        assert_eq!(vault.kind("passport"), Some(ItemKind::Password));
+        vault.set_kind("passport", ItemKind::Document).unwrap();
+        assert_eq!(vault.set_kind("unknown item", ItemKind::Document), None);
+        assert_eq!(vault.add_attachment("passport", &scan, "p4ssword"), None);
+        vault.add_attachment("passport", &scan, "password").unwrap();
+        let deserialized = Vault::deserialize(&vault.serialize("file password").unwrap(), "file password").unwrap();
+        assert_eq!(deserialized.kind("passport"), Some(ItemKind::Document));
+        assert_eq!(deserialized.get_attachments("passport", "password").unwrap(), vec![scan.clone()]);
+        assert_eq!(deserialized.get_attachments("passport", "p4ssword"), None);
+        // V1 items cannot store kinds or attachments.
+        vault.version = Version::V1;
+        assert_eq!(vault.set_kind("passport", ItemKind::Login), None);
+        let mut import = Import::default();
+        import.items.push(ImportedItem {
+            name: String::from("scan"),
+            attachments: vec![scan],
+            ..ImportedItem::default()
+        });
+        assert_eq!(vault.import(&import, "password"), None);
+    }
+
+    #[test]
+    fn check_breached_items() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("email", "password", "m4st3r");
+        vault.add("bank", "correct horse battery staple 42", "m4st3r");
+        vault.add("forum", "123456", "m4st3r");
+        vault.add("work", "qwerty", "0th3r m4st3r");
+        let mut lines: Vec<String> = ["password:10", "123456:20", "qwerty:30"]
+            .iter()
+            .map(|l| {
+                let (p, count) = l.split_once(':').unwrap();
+                format!("{}:{}", hex::encode_upper(sha1::hash(p.as_bytes())), count)
+            })
+            .collect();
+        lines.sort();
+        let path = std::env::temp_dir().join(format!("cybele-vault-breach-{}", std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let mut db = BreachDatabase::open(&path).unwrap();
+        let breached = vault.check_breached("m4st3r", &mut db).unwrap();
+        assert_eq!(breached, vec![(String::from("email"), 10), (String::from("forum"), 20)]);
+        assert_eq!(vault.check_breached("0th3r m4st3r", &mut db).unwrap(), vec![(String::from("work"), 30)]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn import_items() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("GitHub", "0ld-p4ss", "m4st3r").unwrap();
+        let mut import = Import::default();
+        import.add_item("GitHub", "kT9#vLq2$wXz7!mP", Some(Otp::totp("alice", b"12345678901234567890")));
+        import.add_item("GitHub", "Zq8&Rt5^Hn2*Wd6%", None);
+        import.add_item("Wifi", "c0rr3ct-h0rs3", None);
+        let names = vault.import(&import, "m4st3r").unwrap();
+        assert_eq!(names, vec!["GitHub (2)", "GitHub (3)", "Wifi"]);
+        assert_eq!(vault.list(), vec!["GitHub", "GitHub (2)", "GitHub (3)", "Wifi"]);
+        assert_eq!(vault.get("GitHub", "m4st3r").unwrap(), "0ld-p4ss");
+        assert_eq!(vault.get("GitHub (2)", "m4st3r").unwrap(), "kT9#vLq2$wXz7!mP");
+        assert_eq!(vault.get_otp("GitHub (2)", "m4st3r").unwrap(), Otp::totp("alice", b"12345678901234567890"));
+        assert_eq!(vault.get_otp("GitHub (3)", "m4st3r"), None);
+        // V1 vaults cannot store one-time passwords.
+        vault.version = Version::V1;
+        assert_eq!(vault.import(&import, "m4st3r"), None);
+        assert_eq!(vault.list().len(), 4);
+        // Items are not added when another item cannot be imported.
+        vault.version = Version::Test;
+        import.items[2].attachments.push(Attachment {
+            name: "a".repeat(u16::MAX as usize + 1),
+            data: Vec::new(),
+        });
+        assert_eq!(vault.import(&import, "m4st3r"), None);
+        assert_eq!(vault.list().len(), 4);
+    }
+
+    #[test]
+    fn import_items_with_password_policy() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        let import = pass::parse_dir(Path::new("src/import/fixtures/pass")).unwrap();
+        let policy = |item: &ImportedItem| match item.name.starts_with("Internet/") {
+            true => String::from("internet p4ss"),
+            false => String::from("m4st3r"),
+        };
+        let names = vault.import_with(&import, policy).unwrap();
+        assert_eq!(names.len(), 5);
+        assert_eq!(vault.get("Internet/email", "internet p4ss").unwrap(), "Zq8&Rt5^Hn2*Wd6%");
+        assert!(vault.get_otp("Internet/email", "internet p4ss").is_some());
+        assert_eq!(vault.get("Internet/email", "m4st3r"), None);
+        assert_eq!(vault.get("github.com / security answer", "m4st3r").unwrap(), "Cerberus");
+    }
+
+    #[test]
+    fn export_plaintext() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.set_otp("GitHub", &Otp::totp("alice", b"12345678901234567890"), "m4st3r").unwrap();
+        vault.add("Internet/Email", "p4ss, \"quoted\"", "m4st3r").unwrap();
+        vault.add("Other", "0th3r", "other password").unwrap();
+        let error = Err(String::from("plaintext exports must be explicitly acknowledged"));
+        assert_eq!(vault.export_csv("m4st3r", &CsvFormat::lastpass(), false), error);
+        assert_eq!(vault.export_keepass_xml("m4st3r", false), error);
+        // Items that cannot be decrypted are not exported.
+        let exported = vault.export_csv("m4st3r", &CsvFormat::lastpass(), true).unwrap();
+        let import = csv::parse(&exported, &CsvFormat::lastpass()).unwrap();
+        let mut imported = Vault::create(None);
+        imported.version = Version::Test;
+        imported.import(&import, "m4st3r").unwrap();
+        assert_eq!(imported.list(), vec!["GitHub", "Internet/Email"]);
+        assert_eq!(imported.get("Internet/Email", "m4st3r").unwrap(), "p4ss, \"quoted\"");
+        assert_eq!(imported.get_otp("GitHub", "m4st3r").unwrap(), Otp::totp("alice", b"12345678901234567890"));
+        let exported = vault.export_keepass_xml("m4st3r", true).unwrap();
+        assert!(exported.contains("<Value ProtectInMemory=\"True\">kT9#vLq2$wXz7!mP</Value>"));
+        let import = Database::from_xml(&exported).unwrap().to_import();
+        assert!(import.unmapped.is_empty());
+        let names: Vec<&str> = import.items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["GitHub", "Internet/Email"]);
+        assert_eq!(import.items[0].otp, Some(Otp::totp("alice", b"12345678901234567890")));
+    }
 }
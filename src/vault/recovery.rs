@@ -0,0 +1,165 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::recovery::RecoveryKey;
+use crate::vault::Vault;
+use crate::{cipher, keys, Purpose};
+
+// [12*u8: nonce][encrypted(32*u8: key)][16*u8: tag]
+pub(crate) const WRAPPED_KEY_LEN: usize = 12 + 32 + 16;
+
+/// Vaults that can be opened with a recovery key are encrypted with a random file key, which is wrapped with both the
+/// password (when serializing) and the recovery key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Recovery {
+    pub(crate) file_key: [u8; 32],
+    pub(crate) wrapped_file_key: [u8; WRAPPED_KEY_LEN],
+}
+
+pub(crate) fn wrap_key(wrapping_key: [u8; 32], key: &[u8; 32]) -> Option<[u8; WRAPPED_KEY_LEN]> {
+    let mut nonce = [0u8; 12];
+    OsRng {}.fill_bytes(&mut nonce);
+    let encrypted_key = cipher::encrypt_with_nonce(wrapping_key, nonce, key)?;
+    let mut wrapped = [0u8; WRAPPED_KEY_LEN];
+    wrapped[..12].copy_from_slice(&nonce);
+    wrapped[12..].copy_from_slice(&encrypted_key);
+    Some(wrapped)
+}
+
+pub(crate) fn unwrap_key(wrapping_key: [u8; 32], wrapped: &[u8; WRAPPED_KEY_LEN]) -> Option<[u8; 32]> {
+    let key = cipher::decrypt_with_nonce(wrapping_key, wrapped[..12].try_into().unwrap(), &wrapped[12..])?;
+    key.try_into().ok()
+}
+
+impl Vault {
+    /// Create a vault that can also be opened with the returned recovery key, which must be shown to the user once
+    /// (e.g. with `RecoveryKey::to_words`) since it cannot be retrieved later.
+    pub fn create_with_recovery_key(salt: Option<[u8; 32]>) -> Option<(Vault, RecoveryKey)> {
+        let mut vault = Vault::create(salt);
+        let recovery_key = vault.rotate_recovery_key()?;
+        Some((vault, recovery_key))
+    }
+
+    /// Replace the recovery key with a new random one (e.g. when it may have leaked), which must be shown to the user.
+    pub fn rotate_recovery_key(&mut self) -> Option<RecoveryKey> {
+        let recovery_key = RecoveryKey::generate();
+        self.set_recovery_key(&recovery_key)?;
+        Some(recovery_key)
+    }
+
+    /// Let the given recovery key open serialized vaults, e.g. after combining Shamir shares when the password has been
+    /// lost. A new file key is generated, so that previous recovery keys cannot open the vault anymore. Items remain
+    /// encrypted with their own passwords.
+    pub fn set_recovery_key(&mut self, recovery_key: &RecoveryKey) -> Option<()> {
+        let mut file_key = [0u8; 32];
+        OsRng {}.fill_bytes(&mut file_key);
+        let wrapped_file_key = wrap_key(keys::derive_subkey(recovery_key.as_bytes(), Purpose::Recovery), &file_key)?;
+        self.recovery = Some(Recovery { file_key, wrapped_file_key });
+        Some(())
+    }
+
+    /// Go back to a file key derived from the password only.
+    pub fn remove_recovery_key(&mut self) {
+        self.recovery = None;
+    }
+
+    pub fn has_recovery_key(&self) -> bool {
+        self.recovery.is_some()
+    }
+
+    /// Open a serialized vault with its recovery key instead of its password.
+    pub fn deserialize_with_recovery_key(bin: &[u8], recovery_key: &RecoveryKey) -> Option<Vault> {
+        Vault::deserialize_with(bin, |header| {
+            let Some((_, wrapped_file_key)) = header.wrapped_file_keys else {
+                eprintln!("This vault cannot be opened with a recovery key");
+                return None;
+            };
+            unwrap_key(keys::derive_subkey(recovery_key.as_bytes(), Purpose::Recovery), &wrapped_file_key)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::recovery::shamir;
+    use crate::Version;
+
+    #[test]
+    fn wrap_unwrap_key() {
+        let wrapped = wrap_key([1u8; 32], &[2u8; 32]).unwrap();
+        assert_ne!(wrapped, wrap_key([1u8; 32], &[2u8; 32]).unwrap());
+        assert_eq!(unwrap_key([1u8; 32], &wrapped).unwrap(), [2u8; 32]);
+        assert_eq!(unwrap_key([3u8; 32], &wrapped), None);
+    }
+
+    #[test]
+    fn open_with_recovery_key() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        vault.set_recovery_key(&RecoveryKey::from_bytes([7u8; 32])).unwrap();
+        assert!(vault.has_recovery_key());
+        let serialized = vault.serialize("f1l3 p4ss").unwrap();
+        // [u8: version][32*u8: salt][2*60*u8: wrapped file keys][12*u8: nonce][encrypted([u16: items_len][...items][32*u8: audit_key])]
+        assert_eq!(serialized[0], 0x80);
+        assert_eq!(serialized.len(), 1 + 32 + 2 * WRAPPED_KEY_LEN + 12 + vault.items[0].size() + 2 + 32 + 16);
+        let recovered = Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([7u8; 32])).unwrap();
+        assert_eq!(recovered.items, vault.items);
+        assert_eq!(recovered.get("GitHub", "m4st3r").unwrap(), "kT9#vLq2$wXz7!mP");
+        let opened = Vault::deserialize(&serialized, "f1l3 p4ss").unwrap();
+        assert_eq!(opened.items, vault.items);
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([8u8; 32])).is_none());
+        assert!(Vault::deserialize(&serialized, "wrong p4ss").is_none());
+        // The recovery key still works once the vault has been saved with a new password.
+        let serialized = recovered.serialize("n3w p4ss").unwrap();
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([7u8; 32])).is_some());
+        assert!(Vault::deserialize(&serialized, "n3w p4ss").is_some());
+        // Previous recovery keys are revoked.
+        let mut vault = Vault::deserialize(&serialized, "n3w p4ss").unwrap();
+        vault.set_recovery_key(&RecoveryKey::from_bytes([9u8; 32])).unwrap();
+        let serialized = vault.serialize("n3w p4ss").unwrap();
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([7u8; 32])).is_none());
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([9u8; 32])).is_some());
+        vault.remove_recovery_key();
+        let serialized = vault.serialize("n3w p4ss").unwrap();
+        assert_eq!(serialized[0], 0);
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes([9u8; 32])).is_none());
+        assert!(!Vault::deserialize(&serialized, "n3w p4ss").unwrap().has_recovery_key());
+    }
+
+    #[test]
+    fn create_and_rotate_recovery_key() {
+        let (mut vault, recovery_key) = Vault::create_with_recovery_key(Some([42u8; 32])).unwrap();
+        vault.version = Version::Test;
+        vault.add("GitHub", "kT9#vLq2$wXz7!mP", "m4st3r").unwrap();
+        let serialized = vault.serialize("f1l3 p4ss").unwrap();
+        // The recovery key is shown to the user, who types it back once the password has been forgotten.
+        let typed = RecoveryKey::from_words(&recovery_key.to_words()).unwrap();
+        let recovered = Vault::deserialize_with_recovery_key(&serialized, &typed).unwrap();
+        assert_eq!(recovered.get("GitHub", "m4st3r").unwrap(), "kT9#vLq2$wXz7!mP");
+        let rotated_key = vault.rotate_recovery_key().unwrap();
+        assert_ne!(rotated_key, recovery_key);
+        let serialized = vault.serialize("f1l3 p4ss").unwrap();
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &recovery_key).is_none());
+        let typed = RecoveryKey::from_base32(&rotated_key.to_base32()).unwrap();
+        assert!(Vault::deserialize_with_recovery_key(&serialized, &typed).is_some());
+        assert!(Vault::deserialize(&serialized, "f1l3 p4ss").unwrap().has_recovery_key());
+    }
+
+    #[test]
+    fn open_with_shares() {
+        let mut vault = Vault::create(Some([42u8; 32]));
+        vault.version = Version::Test;
+        vault.add("Wifi", "c0rr3ct-h0rs3", "m4st3r").unwrap();
+        let recovery_key = RecoveryKey::from_bytes([5u8; 32]);
+        vault.set_recovery_key(&recovery_key).unwrap();
+        let serialized = vault.serialize("f1l3 p4ss").unwrap();
+        // Any 2 of 3 colleagues can open the vault.
+        let encoded: Vec<String> = shamir::split(recovery_key.as_bytes(), 2, 3).unwrap().iter().map(|s| s.encode()).collect();
+        let shares: Vec<shamir::Share> = [&encoded[2], &encoded[0]].iter().map(|s| shamir::Share::decode(s).unwrap()).collect();
+        let recovered = Vault::deserialize_with_recovery_key(&serialized, &RecoveryKey::from_bytes(shamir::combine(&shares).unwrap())).unwrap();
+        assert_eq!(recovered.get("Wifi", "m4st3r").unwrap(), "c0rr3ct-h0rs3");
+    }
+}
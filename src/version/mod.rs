@@ -2,6 +2,8 @@
 pub enum Version {
     Test,
     V1,
+    /// V2 adds optional records to vault items (e.g. one-time password seeds).
+    V2,
 }
 
 impl Version {
@@ -9,6 +11,7 @@ impl Version {
         match self {
             Version::Test => 0,
             Version::V1 => 1,
+            Version::V2 => 2,
         }
     }
 
@@ -16,6 +19,7 @@ impl Version {
         match version {
             0 => Some(Version::Test),
             1 => Some(Version::V1),
+            2 => Some(Version::V2),
             _ => None,
         }
     }
@@ -33,7 +37,10 @@ mod tests {
         let v1 = Version::from_byte(1u8);
         assert_eq!(v1, Some(Version::V1));
         assert_eq!(1u8, v1.unwrap().to_byte());
-        assert_eq!(Version::from_byte(2u8), None);
+        let v2 = Version::from_byte(2u8);
+        assert_eq!(v2, Some(Version::V2));
+        assert_eq!(2u8, v2.unwrap().to_byte());
+        assert_eq!(Version::from_byte(3u8), None);
         assert_eq!(Version::from_byte(255u8), None);
     }
 }
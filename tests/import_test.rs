@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use cybele_core::import::{bitwarden, one_password};
+    use cybele_core::vault::{ItemKind, Vault};
+    use cybele_core::Version;
+
+    #[test]
+    fn import_bitwarden_export() {
+        let export = include_str!("../src/import/fixtures/bitwarden_argon2.json");
+        let import = bitwarden::parse(export, Some("fixture p4ssword")).unwrap();
+        let mut vault: Vault = Vault::create(None);
+        vault.version = Version::Test;
+        vault.import(&import, "m4st3r_p4ss0rd").unwrap();
+        assert_eq!(vault.list().len(), 11);
+        assert_eq!("kT9#vLq2$wXz7!mP", vault.get("Work/GitHub", "m4st3r_p4ss0rd").unwrap());
+        assert_eq!("123", vault.get("Work/Visa / code", "m4st3r_p4ss0rd").unwrap());
+        assert!(vault.get_otp_code("Work/GitHub", "m4st3r_p4ss0rd").is_some());
+        assert!(!import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn import_1password_export() {
+        let export = include_bytes!("../src/import/fixtures/1password.1pux");
+        let import = one_password::parse(export).unwrap();
+        let mut vault: Vault = Vault::create(None);
+        vault.version = Version::Test;
+        vault.import(&import, "m4st3r_p4ss0rd").unwrap();
+        assert_eq!(vault.list().len(), 11);
+        assert_eq!("kT9#vLq2$wXz7!mP", vault.get("Personal/GitHub", "m4st3r_p4ss0rd").unwrap());
+        assert_eq!("sk_test_123", vault.get("Work/Stripe", "m4st3r_p4ss0rd").unwrap());
+        assert!(vault.get_otp_code("Personal/Twitter", "m4st3r_p4ss0rd").is_some());
+        // Documents are stored as attachments.
+        assert_eq!(vault.kind("Work/Passport scan"), Some(ItemKind::Document));
+        let attachments = vault.get_attachments("Work/Passport scan", "m4st3r_p4ss0rd").unwrap();
+        assert_eq!(attachments[0].name, "passport.pdf");
+        assert_eq!(attachments[0].data, b"pdf");
+        assert_eq!(vault.get_attachments("Personal/GitHub", "m4st3r_p4ss0rd").unwrap().len(), 2);
+        assert!(!import.unmapped.is_empty());
+    }
+}